@@ -1,7 +1,7 @@
 use crate::{
     config::*,
     core::{tasks::jobs::ProfilesJobGuard, updater::ManifestVersionLatest, *},
-    feat, ret_err,
+    feat, log_err, ret_err,
     utils::{
         candy, dirs, help,
         resolve::{self, save_window_state},
@@ -10,6 +10,7 @@ use crate::{
 };
 use anyhow::{Context, Result};
 use chrono::Local;
+use indexmap::IndexMap;
 use log::debug;
 use serde_yaml::Mapping;
 use std::collections::{HashMap, VecDeque};
@@ -36,28 +37,35 @@ pub fn is_portable() -> CmdResult<bool> {
     Ok(false)
 }
 
+/// 重新生成并推送配置，返回核心是否已确认生效
 #[tauri::command]
-pub async fn enhance_profiles() -> CmdResult {
-    wrap_err!(CoreManager::global().update_config().await)?;
+pub async fn enhance_profiles() -> CmdResult<crate::core::ConfigApplyStatus> {
+    let status = wrap_err!(CoreManager::global().update_config().await)?;
     handle::Handle::refresh_clash();
-    Ok(())
+    Ok(status)
 }
 
 #[tauri::command]
 pub async fn import_profile(url: String, option: Option<PrfOption>) -> CmdResult {
     let item = wrap_err!(ProfileItem::from_url(&url, None, None, option).await)?;
-    wrap_err!(Config::profiles().data().append_item(item))
+    wrap_err!(Config::profiles().data().append_item(item))?;
+    log_err!(handle::Handle::update_systray());
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn create_profile(item: ProfileItem, file_data: Option<String>) -> CmdResult {
     let item = wrap_err!(ProfileItem::duplicate(item, file_data).await)?;
-    wrap_err!(Config::profiles().data().append_item(item))
+    wrap_err!(Config::profiles().data().append_item(item))?;
+    log_err!(handle::Handle::update_systray());
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn reorder_profile(active_id: String, over_id: String) -> CmdResult {
-    wrap_err!(Config::profiles().data().reorder(active_id, over_id))
+    wrap_err!(Config::profiles().data().reorder(active_id, over_id))?;
+    log_err!(handle::Handle::update_systray());
+    Ok(())
 }
 
 #[tauri::command]
@@ -72,6 +80,7 @@ pub async fn delete_profile(index: String) -> CmdResult {
         wrap_err!(CoreManager::global().update_config().await)?;
         handle::Handle::refresh_clash();
     }
+    log_err!(handle::Handle::update_systray());
 
     Ok(())
 }
@@ -79,21 +88,7 @@ pub async fn delete_profile(index: String) -> CmdResult {
 /// 修改profiles的
 #[tauri::command]
 pub async fn patch_profiles_config(profiles: IProfiles) -> CmdResult {
-    wrap_err!({ Config::profiles().draft().patch_config(profiles) })?;
-
-    match CoreManager::global().update_config().await {
-        Ok(_) => {
-            handle::Handle::refresh_clash();
-            Config::profiles().apply();
-            wrap_err!(Config::profiles().data().save_file())?;
-            Ok(())
-        }
-        Err(err) => {
-            Config::profiles().discard();
-            log::error!(target: "app", "{err}");
-            Err(format!("{err}"))
-        }
-    }
+    wrap_err!(feat::patch_profiles_config(profiles).await)
 }
 
 /// 修改某个profile item的
@@ -101,6 +96,7 @@ pub async fn patch_profiles_config(profiles: IProfiles) -> CmdResult {
 pub fn patch_profile(index: String, profile: ProfileItem) -> CmdResult {
     wrap_err!(Config::profiles().data().patch_item(index, profile))?;
     ProfilesJobGuard::global().lock().refresh();
+    log_err!(handle::Handle::update_systray());
     Ok(())
 }
 
@@ -191,17 +187,132 @@ pub async fn patch_verge_config(payload: IVerge) -> CmdResult {
     wrap_err!(feat::patch_verge(payload).await)
 }
 
+/// 回滚到上一次切换前的核心与配置
+#[tauri::command]
+pub async fn rollback_core() -> CmdResult {
+    wrap_err!(CoreManager::global().rollback_core().await)?;
+    handle::Handle::refresh_clash();
+    handle::Handle::refresh_verge();
+    Ok(())
+}
+
+/// 开关 TUN 模式，核心支持时无需重启
+#[tauri::command]
+pub async fn set_tun_mode(enabled: bool) -> CmdResult {
+    wrap_err!(
+        feat::patch_verge(IVerge {
+            enable_tun_mode: Some(enabled),
+            ..IVerge::default()
+        })
+        .await
+    )
+}
+
+#[tauri::command]
+pub async fn change_clash_core(
+    clash_core: Option<nyanpasu::ClashCore>,
+    force: Option<bool>,
+) -> Result<Vec<String>, ChangeCoreError> {
+    CoreManager::global()
+        .change_core(clash_core, force.unwrap_or(false))
+        .await
+        .map_err(|err| {
+            log::error!(target: "app", "{err}");
+            err
+        })
+}
+
+#[tauri::command]
+pub fn has_any_core_installed() -> bool {
+    dirs::has_any_core_installed()
+}
+
+#[tauri::command]
+pub async fn get_tun_status() -> CmdResult<TunStatus> {
+    wrap_err!(CoreManager::global().tun_status().await)
+}
+
+/// 逐个阶段检查外部控制接口的连接，用于排查无法获取代理的问题
+#[tauri::command]
+pub async fn diagnose_controller() -> CmdResult<clash::api::ControllerDiagnostics> {
+    Ok(clash::api::ControllerEndpoint::resolve().diagnose().await)
+}
+
+/// 核心当前运行方式的选择依据
+#[tauri::command]
+pub async fn run_type_diagnostics() -> CmdResult<crate::core::clash::core::RunTypeDiagnostics> {
+    Ok(CoreManager::global().run_type_diagnostics().await)
+}
+
+/// 检查服务模式是否已安装并且可用
+#[tauri::command]
+pub async fn check_service_mode() -> CmdResult<crate::core::clash::core::ServiceModeCheck> {
+    #[cfg(windows)]
+    let check = crate::core::win_service::check_service_mode().await;
+    #[cfg(not(windows))]
+    let check = crate::core::clash::core::ServiceModeCheck::failed(
+        crate::core::clash::core::ServiceModeFailure::Unsupported,
+        "the service mode is only available on Windows",
+    );
+    Ok(check)
+}
+
+/// 将以子进程运行的核心迁移到服务中运行
+#[tauri::command]
+pub async fn migrate_to_service() -> CmdResult {
+    #[cfg(windows)]
+    {
+        wrap_err!(CoreManager::global().migrate_to_service().await)
+    }
+
+    #[cfg(not(windows))]
+    {
+        Err("the service mode is only available on Windows".to_string())
+    }
+}
+
+/// 清空核心的 DNS 缓存
 #[tauri::command]
-pub async fn change_clash_core(clash_core: Option<nyanpasu::ClashCore>) -> CmdResult {
-    wrap_err!(CoreManager::global().change_core(clash_core).await)
+pub async fn flush_dns_cache() -> CmdResult {
+    wrap_err!(clash::api::flush_dns_cache().await)
+}
+
+/// 测量外部控制接口的往返延迟
+#[tauri::command]
+pub async fn controller_latency(rounds: Option<usize>) -> CmdResult<clash::api::ControllerLatency> {
+    let rounds = rounds.unwrap_or(5).clamp(1, 20);
+    wrap_err!(
+        clash::api::ControllerEndpoint::resolve()
+            .measure_latency(rounds)
+            .await
+    )
+}
+
+/// 获取实时速率与累计流量
+#[tauri::command]
+pub fn get_traffic() -> CmdResult<clash::traffic::TrafficStats> {
+    Ok(clash::traffic::TrafficMonitor::global().stats())
+}
+
+/// 取消正在进行的核心启动
+#[tauri::command]
+pub fn cancel_core_start() -> CmdResult {
+    CoreManager::global().cancel_start();
+    Ok(())
 }
 
 /// restart the sidecar
 #[tauri::command]
-pub async fn restart_sidecar() -> CmdResult {
+pub async fn restart_sidecar() -> CmdResult<CoreAction> {
     wrap_err!(CoreManager::global().run_core().await)
 }
 
+/// 使用当前的配置文件原样重启核心
+#[tauri::command]
+pub async fn restart_core_preserve_config() -> CmdResult<CoreAction> {
+    wrap_err!(CoreManager::global().restart_core_preserve_config().await)
+}
+
 #[tauri::command]
 pub fn grant_permission(_core: String) -> CmdResult {
     #[cfg(any(target_os = "macos", target_os = "linux"))]
@@ -228,8 +339,22 @@ pub fn get_sys_proxy() -> CmdResult<Mapping> {
 }
 
 #[tauri::command]
-pub fn get_clash_logs() -> CmdResult<VecDeque<String>> {
-    Ok(logger::Logger::global().get_log())
+pub async fn get_clash_logs() -> CmdResult<VecDeque<String>> {
+    Ok(logger::Logger::global().get_log().await)
+}
+
+/// 导出核心日志，返回导出文件的路径
+#[tauri::command]
+pub async fn export_core_logs() -> CmdResult<String> {
+    let path = wrap_err!(feat::export_core_logs().await)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// 导出用于反馈问题的诊断包，返回 zip 文件的路径
+#[tauri::command]
+pub async fn export_diagnostic_bundle() -> CmdResult<String> {
+    let path = wrap_err!(feat::export_diagnostic_bundle().await)?;
+    Ok(path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
@@ -273,13 +398,92 @@ pub async fn fetch_latest_core_versions() -> CmdResult<ManifestVersionLatest> {
 
 #[tauri::command]
 pub async fn get_core_version(core_type: nyanpasu::ClashCore) -> CmdResult<String> {
-    match tokio::task::spawn_blocking(move || resolve::resolve_core_version(&core_type)).await {
+    match tokio::task::spawn_blocking(move || manager::resolve_core_version(&core_type)).await {
         Ok(Ok(version)) => Ok(version),
         Ok(Err(err)) => Err(format!("{err}")),
         Err(err) => Err(format!("{err}")),
     }
 }
 
+/// 列出所有已安装的核心
+#[tauri::command]
+pub async fn list_installed_cores() -> CmdResult<Vec<manager::InstalledCore>> {
+    wrap_err!(tokio::task::spawn_blocking(manager::list_installed_cores).await)
+}
+
+/// 列出所有核心、支持的功能以及是否已安装
+#[tauri::command]
+pub async fn supported_cores() -> CmdResult<Vec<clash::capability::SupportedCore>> {
+    wrap_err!(tokio::task::spawn_blocking(clash::capability::supported_cores).await)
+}
+
+/// 固定核心的版本，固定后不会被更新
+#[tauri::command]
+pub fn pin_core(core_type: nyanpasu::ClashCore, version: String) -> CmdResult {
+    wrap_err!(manager::pin_core(core_type, version))
+}
+
+#[tauri::command]
+pub fn unpin_core(core_type: nyanpasu::ClashCore) -> CmdResult {
+    wrap_err!(manager::unpin_core(&core_type))
+}
+
+/// 使用最小配置测试核心能否正常运行
+#[tauri::command]
+pub async fn smoke_test_core(core_type: nyanpasu::ClashCore) -> CmdResult<SmokeTestResult> {
+    wrap_err!(CoreManager::global().smoke_test_core(&core_type).await)
+}
+
+/// 测试核心的启动耗时，默认重复 3 次
+#[tauri::command]
+pub async fn benchmark_startup(
+    core_type: nyanpasu::ClashCore,
+    runs: Option<u32>,
+) -> CmdResult<StartupBenchmark> {
+    wrap_err!(
+        CoreManager::global()
+            .benchmark_startup(&core_type, runs.unwrap_or(3))
+            .await
+    )
+}
+
+/// 使用指定的核心检查配置，不切换当前核心
+#[tauri::command]
+pub async fn check_config_for_core(
+    core_type: nyanpasu::ClashCore,
+    config_path: Option<std::path::PathBuf>,
+) -> CmdResult<Vec<String>> {
+    match tokio::task::spawn_blocking(move || {
+        CoreManager::global().check_config_for(&core_type, config_path)
+    })
+    .await
+    {
+        Ok(Ok(details)) => Ok(details),
+        Ok(Err(err)) => Err(format!("{err}")),
+        Err(err) => Err(format!("{err}")),
+    }
+}
+
+/// 预览指定配置合并后的结果及其是否能被当前核心使用
+#[tauri::command]
+pub async fn preview_profile(uid: String) -> CmdResult<feat::ProfilePreview> {
+    match tokio::task::spawn_blocking(move || feat::preview_profile(uid)).await {
+        Ok(res) => wrap_err!(res),
+        Err(err) => Err(format!("{err}")),
+    }
+}
+
+#[tauri::command]
+pub async fn get_core_log_level() -> CmdResult<nyanpasu::LoggingLevel> {
+    wrap_err!(feat::get_core_log_level().await)
+}
+
+/// 修改核心的日志级别，会同时放宽更严格的客户端过滤
+#[tauri::command]
+pub async fn set_core_log_level(level: nyanpasu::LoggingLevel) -> CmdResult {
+    wrap_err!(feat::set_core_log_level(level).await)
+}
+
 #[tauri::command]
 pub async fn collect_logs() -> CmdResult {
     let now = Local::now().format("%Y-%m-%d");
@@ -325,6 +529,21 @@ pub async fn inspect_updater(updater_id: usize) -> CmdResult<updater::UpdaterSum
     Ok(updater)
 }
 
+/// 获取核心当前的连接，可按域名或代理链过滤
+#[tauri::command]
+pub async fn get_connections(
+    filter: Option<clash::api::ConnectionFilter>,
+) -> CmdResult<Vec<clash::api::ConnectionItem>> {
+    let res = wrap_err!(clash::api::get_connections().await)?;
+    let filter = filter.unwrap_or_default();
+    Ok(res
+        .connections
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|item| filter.matches(item))
+        .collect())
+}
+
 #[tauri::command]
 pub async fn clash_api_get_proxy_delay(
     name: String,
@@ -336,6 +555,21 @@ pub async fn clash_api_get_proxy_delay(
     }
 }
 
+/// 获取自动选择类型分组实际使用的节点
+#[tauri::command]
+pub fn get_resolved_proxies(
+) -> CmdResult<IndexMap<String, crate::core::clash::proxies::ResolvedNode>> {
+    use crate::core::clash::proxies::ProxiesGuard;
+    Ok(ProxiesGuard::global().read().resolved().clone())
+}
+
+/// 获取各个可选择分组沿当前选择展开的代理链
+#[tauri::command]
+pub fn get_proxy_chains() -> CmdResult<IndexMap<String, Vec<String>>> {
+    use crate::core::clash::proxies::ProxiesGuard;
+    Ok(ProxiesGuard::global().read().inner().proxy_chains())
+}
+
 #[tauri::command]
 pub async fn get_proxies() -> CmdResult<crate::core::clash::proxies::Proxies> {
     use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
@@ -354,13 +588,94 @@ pub async fn get_proxies() -> CmdResult<crate::core::clash::proxies::Proxies> {
     }
 }
 
+/// 将测速类分组固定到当前节点或恢复自动选择
 #[tauri::command]
-pub async fn select_proxy(group: String, name: String) -> CmdResult<()> {
+pub async fn set_group_mode(
+    group: String,
+    mode: crate::core::clash::proxies::GroupMode,
+) -> CmdResult<()> {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
+    wrap_err!(ProxiesGuard::global().set_group_mode(&group, mode).await)
+}
+
+#[tauri::command]
+pub async fn refresh_proxies_now() -> CmdResult<()> {
     use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
-    wrap_err!(ProxiesGuard::global().select_proxy(&group, &name).await)?;
+    wrap_err!(ProxiesGuard::global().refresh_now().await)
+}
+
+/// 测试所有节点的延迟，每个结果通过 `nyanpasu://delay-test-result` 事件返回
+#[tauri::command]
+pub async fn test_all_groups() -> CmdResult<()> {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
+    let mut rx = ProxiesGuard::global().test_all_groups();
+    while let Some(result) = rx.recv().await {
+        handle::Handle::delay_test_result(&result);
+    }
+    wrap_err!(ProxiesGuard::global().refresh_now().await)
+}
+
+/// 核心是否以安全模式运行
+#[tauri::command]
+pub fn is_safe_mode() -> CmdResult<bool> {
+    Ok(CoreManager::global().is_safe_mode())
+}
+
+/// 本次运行期间核心启动、崩溃、恢复和推送配置的统计
+#[tauri::command]
+pub fn get_core_metrics() -> CmdResult<crate::core::clash::metrics::CoreMetricsSnapshot> {
+    Ok(crate::core::clash::metrics::CoreMetrics::global().snapshot())
+}
+
+/// 取消进行中的延迟测试，排队中的测试不再发出
+#[tauri::command]
+pub fn cancel_latency_tests() -> CmdResult<()> {
+    crate::core::clash::proxies::cancel_delay_tests();
     Ok(())
 }
 
+/// 与托盘相同的代理 diff 结果
+#[tauri::command]
+pub fn proxies_delta(
+    previous: crate::core::tray::proxies::TrayProxies,
+) -> CmdResult<crate::core::tray::proxies::ProxiesDelta> {
+    Ok(crate::core::tray::proxies::proxies_delta(&previous))
+}
+
+#[tauri::command]
+pub async fn select_proxy(group: String, name: String) -> CmdResult<()> {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt, SelectionOrigin};
+    wrap_err!(
+        ProxiesGuard::global()
+            .select_proxy(&group, &name, SelectionOrigin::Window)
+            .await
+    )?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn select_proxy_by_index(group: String, index: usize) -> CmdResult<String> {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt, SelectionOrigin};
+    wrap_err!(
+        ProxiesGuard::global()
+            .select_proxy_by_index(&group, index, SelectionOrigin::Window)
+            .await
+    )
+}
+
+#[tauri::command]
+pub async fn cycle_proxy(
+    group: String,
+    direction: crate::core::clash::proxies::CycleDirection,
+) -> CmdResult<String> {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt, SelectionOrigin};
+    wrap_err!(
+        ProxiesGuard::global()
+            .cycle_proxy(&group, direction, SelectionOrigin::Window)
+            .await
+    )
+}
+
 #[tauri::command]
 pub async fn update_proxy_provider(name: String) -> CmdResult<()> {
     use crate::core::clash::{
@@ -372,6 +687,73 @@ pub async fn update_proxy_provider(name: String) -> CmdResult<()> {
     Ok(())
 }
 
+/// 释放被占用的外部控制端口，非核心进程需要用户确认后才会结束
+#[tauri::command]
+pub async fn free_controller_port(
+    confirm: Option<bool>,
+) -> CmdResult<Option<crate::feat::PortOwner>> {
+    wrap_err!(feat::free_controller_port(confirm.unwrap_or(false)).await)
+}
+
+/// 推断访问指定域名或 URL 时匹配的规则和代理链
+#[tauri::command]
+pub async fn resolve_route(host: String) -> CmdResult<crate::core::clash::route::RouteResolution> {
+    wrap_err!(crate::core::clash::route::resolve_route(&host).await)
+}
+
+/// 保存所有分组当前选中的节点为快照
+#[tauri::command]
+pub async fn save_selection_snapshot(name: String) -> CmdResult {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
+    wrap_err!(ProxiesGuard::global().save_selection_snapshot(&name).await)?;
+    wrap_err!(handle::Handle::update_systray())?;
+    Ok(())
+}
+
+/// 应用保存的节点选择快照
+#[tauri::command]
+pub async fn apply_selection_snapshot(name: String) -> CmdResult {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt, SelectionOrigin};
+    wrap_err!(
+        ProxiesGuard::global()
+            .apply_selection_snapshot(&name, SelectionOrigin::Window)
+            .await
+    )
+}
+
+/// 获取代理集合的更新状态，随代理信息定时刷新
+#[tauri::command]
+pub async fn get_providers() -> CmdResult<Vec<crate::core::clash::proxies::ProviderInfo>> {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
+    {
+        let guard = ProxiesGuard::global().read();
+        if guard.is_updated() {
+            return Ok(guard.inner().providers.clone());
+        }
+    }
+    wrap_err!(ProxiesGuard::global().refresh_now().await)?;
+    Ok(ProxiesGuard::global().read().inner().providers.clone())
+}
+
+/// 立即更新代理集合，返回更新后的状态
+#[tauri::command]
+pub async fn refresh_provider(
+    name: String,
+) -> CmdResult<crate::core::clash::proxies::ProviderInfo> {
+    use crate::core::clash::{
+        api,
+        proxies::{ProxiesGuard, ProxiesGuardExt},
+    };
+    wrap_err!(api::update_providers_proxies_group(&name).await)?;
+    wrap_err!(ProxiesGuard::global().refresh_now().await)?;
+    let guard = ProxiesGuard::global().read();
+    let provider = guard.inner().providers.iter().find(|p| p.name == name);
+    match provider {
+        Some(provider) => Ok(provider.clone()),
+        None => Err(format!("provider `{name}` not found")),
+    }
+}
+
 #[cfg(windows)]
 #[tauri::command]
 pub fn get_custom_app_dir() -> CmdResult<Option<String>> {
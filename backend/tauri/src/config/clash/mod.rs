@@ -101,8 +101,10 @@ impl IClashTemp {
         port.parse().unwrap_or(9090)
     }
 
+    /// 按端口策略检查外部控制端口，端口被占用且允许回退时改用空闲端口
+    /// 返回替换后的 `external-controller`
     #[instrument]
-    pub fn prepare_external_controller_port(&mut self) -> Result<()> {
+    pub fn prepare_external_controller_port(&mut self) -> Result<Option<String>> {
         let strategy = Config::verge()
             .latest()
             .get_external_controller_port_strategy();
@@ -117,10 +119,11 @@ impl IClashTemp {
                 new_server
             );
             let mut map = Mapping::new();
-            map.insert("external-controller".into(), new_server.into());
+            map.insert("external-controller".into(), new_server.clone().into());
             self.patch_config(map);
+            return Ok(Some(new_server));
         }
-        Ok(())
+        Ok(None)
     }
 
     pub fn guard_mixed_port(config: &Mapping) -> u16 {
@@ -5,10 +5,13 @@ use crate::{
 };
 use anyhow::{anyhow, Result};
 use once_cell::sync::OnceCell;
+use serde_yaml::Mapping;
 use std::{env::temp_dir, path::PathBuf};
 
 pub const RUNTIME_CONFIG: &str = "clash-verge.yaml";
 pub const CHECK_CONFIG: &str = "clash-verge-check.yaml";
+pub const BENCHMARK_CONFIG: &str = "clash-nyanpasu-benchmark.yaml";
+pub const PREVIEW_CONFIG: &str = "clash-nyanpasu-preview.yaml";
 
 pub struct Config {
     clash_config: Draft<IClashTemp>,
@@ -69,6 +72,15 @@ impl Config {
         let path = match typ {
             ConfigType::Run => dirs::app_home_dir()?.join(RUNTIME_CONFIG),
             ConfigType::Check => temp_dir().join(CHECK_CONFIG),
+            ConfigType::Benchmark => {
+                let path = temp_dir().join(BENCHMARK_CONFIG);
+                help::save_yaml(
+                    &path,
+                    &Self::benchmark_config()?,
+                    Some("# Generated by Clash Nyanpasu"),
+                )?;
+                return Ok(path);
+            }
         };
 
         let runtime = Config::runtime();
@@ -82,6 +94,25 @@ impl Config {
         Ok(path)
     }
 
+    /// 用于测试核心能否正常运行的最小配置，与用户的配置无关
+    fn benchmark_config() -> Result<Mapping> {
+        let port = port_scanner::request_open_port().ok_or(anyhow!("can't find an open port"))?;
+        let config = serde_yaml::from_str(&format!(
+            r#"
+mixed-port: 0
+mode: rule
+log-level: info
+external-controller: 127.0.0.1:{port}
+tun:
+  enable: false
+proxies: []
+rules:
+  - MATCH,DIRECT
+"#
+        ))?;
+        Ok(config)
+    }
+
     /// 生成配置存好
     pub fn generate() -> Result<()> {
         let (config, exists_keys, logs) = enhance::enhance();
@@ -100,4 +131,6 @@ impl Config {
 pub enum ConfigType {
     Run,
     Check,
+    /// 测试核心用的最小配置
+    Benchmark,
 }
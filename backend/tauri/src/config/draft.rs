@@ -48,6 +48,11 @@ macro_rules! draft_define {
                 }
             }
 
+            /// 是否有尚未应用的修改
+            pub fn has_draft(&self) -> bool {
+                self.inner.lock().1.is_some()
+            }
+
             pub fn discard(&self) -> Option<$id> {
                 let mut inner = self.inner.lock();
                 inner.1.take()
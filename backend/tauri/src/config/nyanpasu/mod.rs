@@ -1,7 +1,9 @@
 use crate::utils::{dirs, help};
 use anyhow::Result;
 // use log::LevelFilter;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 mod clash_strategy;
 pub mod logging;
@@ -9,7 +11,7 @@ pub mod logging;
 pub use self::clash_strategy::{ClashStrategy, ExternalControllerPortStrategy};
 pub use logging::LoggingLevel;
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
 pub enum ClashCore {
     #[serde(rename = "clash", alias = "clash-premium")]
     ClashPremium,
@@ -21,6 +23,30 @@ pub enum ClashCore {
     MihomoAlpha,
 }
 
+impl ClashCore {
+    /// 所有支持的核心
+    pub const ALL: [ClashCore; 4] = [
+        ClashCore::ClashPremium,
+        ClashCore::ClashRs,
+        ClashCore::Mihomo,
+        ClashCore::MihomoAlpha,
+    ];
+
+    /// 是否支持通过外部控制接口热切换 TUN 模式
+    pub fn supports_tun_hot_toggle(&self) -> bool {
+        matches!(self, ClashCore::Mihomo | ClashCore::MihomoAlpha)
+    }
+
+    /// 是否支持通过 `PATCH /configs` 修改部分配置
+    /// Clash Premium 会忽略大部分配置项且不报错，所以不使用
+    pub fn supports_config_patch(&self) -> bool {
+        matches!(
+            self,
+            ClashCore::Mihomo | ClashCore::MihomoAlpha | ClashCore::ClashRs
+        )
+    }
+}
+
 impl Default for ClashCore {
     fn default() -> Self {
         match cfg!(feature = "default-meta") {
@@ -116,6 +142,10 @@ pub struct IVerge {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub clash_core: Option<ClashCore>,
 
+    /// 启动前校验核心的 SHA256，默认为假
+    /// 使用自行构建的核心时请保持关闭
+    pub verify_core_checksum: Option<bool>,
+
     /// hotkey map
     /// format: {func},{key}
     pub hotkeys: Option<Vec<String>>,
@@ -163,6 +193,122 @@ pub struct IVerge {
 
     /// 是否启用代理托盘选择
     pub clash_tray_selector: Option<bool>,
+
+    /// 外部修改当前配置文件后自动重新加载，默认为假
+    pub watch_profile_changes: Option<bool>,
+
+    /// 查询服务状态的超时时间，单位毫秒，默认 3000
+    pub service_status_timeout: Option<u64>,
+
+    /// 推送配置前等待核心就绪的最长时间，单位毫秒，默认 3000，为 0 时不等待
+    pub core_warmup_timeout: Option<u64>,
+
+    /// 检查配置的超时时间，单位秒，默认 30
+    pub config_check_timeout: Option<u64>,
+
+    /// 用户在各分组中手动选择的节点，核心重启后重新应用
+    pub proxy_selections: Option<HashMap<String, String>>,
+
+    /// 用户保存的节点选择快照，键为快照名称，值为各分组选中的节点
+    pub selection_snapshots: Option<IndexMap<String, HashMap<String, String>>>,
+
+    /// 同时进行的延迟测试数量上限，默认 5
+    pub delay_test_concurrency: Option<usize>,
+
+    /// 自定义外部控制接口的连接参数，用于反向代理或 TLS 的控制接口
+    pub controller_override: Option<ControllerOverride>,
+
+    /// 核心反复崩溃时合并通知的时间窗口，单位秒，默认 30
+    pub crash_notify_window: Option<u64>,
+
+    /// 无法获取代理信息多久后提示前端，单位秒，默认 30
+    pub proxies_grace_period: Option<u64>,
+
+    /// 定时完整刷新代理信息的间隔，单位秒，默认 10
+    /// 手动选择节点后只刷新对应的分组，不依赖定时刷新
+    pub proxies_update_interval: Option<u64>,
+
+    /// 低于该级别的核心日志不记录，为空时不过滤，修改后立即生效
+    pub core_log_min_level: Option<logging::LoggingLevel>,
+
+    /// 托盘中每个分组最多显示的节点数，超出时显示打开完整选择器的入口，默认 100
+    pub tray_max_nodes_per_group: Option<usize>,
+
+    /// 不在托盘中显示的分组，主窗口中仍会显示
+    pub tray_hidden_groups: Option<Vec<String>>,
+
+    /// 托盘复制环境变量时使用的语法，可选 sh、fish、cmd、ps，默认 Windows 为 ps，其他为 sh
+    pub env_type: Option<String>,
+
+    /// 启动核心时额外设置的环境变量，与继承的环境变量合并，重启核心后生效
+    /// 仅对非服务模式生效，由应用管理的变量见 `backend::RESERVED_CORE_ENV`
+    pub core_env: Option<HashMap<String, String>>,
+
+    /// 各核心启动时追加的参数，重启核心后生效
+    /// 仅对非服务模式生效，由应用管理的参数见 `backend::RESERVED_CORE_ARGS`
+    pub core_extra_args: Option<HashMap<ClashCore, Vec<String>>>,
+
+    /// 关闭窗口后保持应用与核心在后台运行，关闭时关闭窗口即退出应用，默认开启
+    pub keep_core_on_window_close: Option<bool>,
+
+    /// 观察模式，核心由外部启动和管理，只连接其外部控制接口，重启核心后生效
+    pub enable_observer_mode: Option<bool>,
+
+    /// 固定版本的核心，不会被更新，存在多份时优先使用该版本
+    pub pinned_core_versions: Option<HashMap<ClashCore, String>>,
+
+    /// 更新配置时只修改变化的部分，保留节点选择与现有连接，默认关闭
+    /// 变化的配置项无法单独修改时仍会完整重新加载
+    pub partial_config_reload: Option<bool>,
+
+    /// 各核心正常退出时的退出码与信号，以这些状态退出时不会自动恢复
+    /// 退出码 0 与 SIGTERM 始终视为正常退出
+    pub core_clean_exits: Option<HashMap<ClashCore, CoreCleanExit>>,
+
+    /// 重启或恢复核心时，停止后至少等待多久再启动，单位毫秒，默认 300
+    /// 之后还会等待端口被释放，给系统释放 TUN 设备留出时间
+    pub core_restart_grace_ms: Option<u64>,
+
+    /// 推送配置时无法连接核心，在核心恢复运行后自动重试，默认开启
+    pub defer_config_update: Option<bool>,
+
+    /// 当前核心启动失败时尝试的备用核心，启动成功后切换为当前核心
+    pub fallback_core: Option<ClashCore>,
+
+    /// 外部控制接口认证失败时，从运行配置中重新读取密钥并重试，默认开启
+    pub controller_auth_recovery: Option<bool>,
+
+    /// 连续多少分钟没有流量后自动停止核心，为空或 0 时不启用
+    /// 之后打开或切换到主窗口时会重新启动核心
+    pub idle_auto_stop_minutes: Option<u64>,
+
+    /// 是否将核心的输出另外写入日志目录下的 `core.log`，重启核心后生效
+    pub persist_core_logs: Option<bool>,
+
+    /// `core.log` 超过该大小后轮换，单位 MiB，默认 10
+    pub core_log_file_max_size: Option<u64>,
+
+    /// 保留的已轮换核心日志文件数量，默认 5
+    pub core_log_file_max_count: Option<usize>,
+}
+
+/// 核心正常退出时的状态
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CoreCleanExit {
+    #[serde(default)]
+    pub codes: Vec<i32>,
+    #[serde(default)]
+    pub signals: Vec<i32>,
+}
+
+/// 未设置的字段沿用生成配置中的 `external-controller` 与 `secret`
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ControllerOverride {
+    /// `http` 或 `https`
+    pub scheme: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub secret: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
@@ -227,6 +373,10 @@ impl IVerge {
             config.enable_service_mode = template.enable_service_mode;
         }
 
+        if config.keep_core_on_window_close.is_none() {
+            config.keep_core_on_window_close = template.keep_core_on_window_close;
+        }
+
         config
     }
 
@@ -258,6 +408,7 @@ impl IVerge {
             enable_auto_check_update: Some(true),
             clash_tray_selector: Some(true),
             enable_service_mode: Some(false),
+            keep_core_on_window_close: Some(true),
             ..Self::default()
         }
     }
@@ -301,6 +452,7 @@ impl IVerge {
         patch!(theme_setting);
         patch!(web_ui_list);
         patch!(clash_core);
+        patch!(verify_core_checksum);
         patch!(hotkeys);
 
         patch!(auto_close_connection);
@@ -313,5 +465,35 @@ impl IVerge {
         patch!(window_size_state);
         patch!(clash_strategy);
         patch!(clash_tray_selector);
+        patch!(watch_profile_changes);
+        patch!(service_status_timeout);
+        patch!(core_warmup_timeout);
+        patch!(config_check_timeout);
+        patch!(proxy_selections);
+        patch!(selection_snapshots);
+        patch!(delay_test_concurrency);
+        patch!(controller_override);
+        patch!(crash_notify_window);
+        patch!(proxies_grace_period);
+        patch!(proxies_update_interval);
+        patch!(core_log_min_level);
+        patch!(tray_max_nodes_per_group);
+        patch!(tray_hidden_groups);
+        patch!(env_type);
+        patch!(core_env);
+        patch!(core_extra_args);
+        patch!(keep_core_on_window_close);
+        patch!(enable_observer_mode);
+        patch!(pinned_core_versions);
+        patch!(partial_config_reload);
+        patch!(core_clean_exits);
+        patch!(core_restart_grace_ms);
+        patch!(defer_config_update);
+        patch!(fallback_core);
+        patch!(controller_auth_recovery);
+        patch!(idle_auto_stop_minutes);
+        patch!(persist_core_logs);
+        patch!(core_log_file_max_size);
+        patch!(core_log_file_max_count);
     }
 }
@@ -1,5 +1,6 @@
 use crate::{
     config::Config,
+    core::watcher::ProfileWatcher,
     enhance::ScriptType,
     utils::{dirs, help, tmpl},
 };
@@ -402,7 +403,8 @@ impl ProfileItem {
         }
 
         let file = self.file.clone().unwrap();
-        let path = dirs::app_profiles_dir()?.join(file);
+        let path = dirs::app_profiles_dir()?.join(&file);
+        ProfileWatcher::global().record_write(&file, data.as_bytes());
         fs::write(path, data.as_bytes()).context("failed to save the file")
     }
 }
@@ -1,5 +1,8 @@
 use super::{item::ProfileItem, item_type::ProfileUid};
-use crate::utils::{dirs, help};
+use crate::{
+    core::watcher::ProfileWatcher,
+    utils::{dirs, help},
+};
 use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
@@ -141,6 +144,7 @@ impl IProfiles {
             let file = item.file.clone().unwrap();
             let path = dirs::app_profiles_dir()?.join(&file);
 
+            ProfileWatcher::global().record_write(&file, file_data.as_bytes());
             fs::File::create(path)
                 .with_context(|| format!("failed to create file \"{}\"", file))?
                 .write(file_data.as_bytes())
@@ -236,6 +240,7 @@ impl IProfiles {
 
                         let path = dirs::app_profiles_dir()?.join(&file);
 
+                        ProfileWatcher::global().record_write(&file, file_data.as_bytes());
                         fs::File::create(path)
                             .with_context(|| format!("failed to create file \"{}\"", file))?
                             .write(file_data.as_bytes())
@@ -291,19 +296,20 @@ impl IProfiles {
         Ok(current == uid)
     }
 
+    /// 读取指定配置的内容
+    pub fn profile_mapping(&self, uid: &String) -> Result<Mapping> {
+        let item = self.get_item(uid)?;
+        let file_path = match item.file.as_ref() {
+            Some(file) => dirs::app_profiles_dir()?.join(file),
+            None => bail!("failed to get the file field"),
+        };
+        help::read_merge_mapping(&file_path)
+    }
+
     /// 获取current指向的配置内容
     pub fn current_mapping(&self) -> Result<Mapping> {
         match (self.current.as_ref(), self.items.as_ref()) {
-            (Some(current), Some(items)) => {
-                if let Some(item) = items.iter().find(|e| e.uid.as_ref() == Some(current)) {
-                    let file_path = match item.file.as_ref() {
-                        Some(file) => dirs::app_profiles_dir()?.join(file),
-                        None => bail!("failed to get the file field"),
-                    };
-                    return help::read_merge_mapping(&file_path);
-                }
-                bail!("failed to find the current profile \"uid:{current}\"");
-            }
+            (Some(current), Some(_)) => self.profile_mapping(current),
             _ => Ok(Mapping::new()),
         }
     }
@@ -1,12 +1,13 @@
-use crate::config::Config;
+use crate::config::{nyanpasu::ControllerOverride, ClashInfo, Config};
 use anyhow::{bail, Result};
 use indexmap::IndexMap;
-use reqwest::header::HeaderMap;
+use reqwest::{header::HeaderMap, RequestBuilder, Response};
 use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
 use std::{
     collections::HashMap,
     fmt::{self, Display, Formatter},
+    sync::atomic::{AtomicI64, Ordering},
 };
 use tracing_attributes::instrument;
 
@@ -22,7 +23,7 @@ pub async fn put_configs(path: &str) -> Result<()> {
 
     let client = reqwest::ClientBuilder::new().no_proxy().build()?;
     let builder = client.put(&url).headers(headers).json(&data);
-    let response = builder.send().await?;
+    let response = send(&client, builder).await?;
 
     match response.status().as_u16() {
         204 => Ok(()),
@@ -32,6 +33,25 @@ pub async fn put_configs(path: &str) -> Result<()> {
     }
 }
 
+/// GET /version
+/// 用于确认核心的外部控制接口是否就绪
+#[instrument]
+pub async fn get_version() -> Result<()> {
+    ControllerEndpoint::resolve().check_reachable().await
+}
+
+/// GET /configs
+#[instrument]
+pub async fn get_configs() -> Result<Mapping> {
+    let (url, headers) = clash_client_info()?;
+    let url = format!("{url}/configs");
+
+    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+    let builder = client.get(&url).headers(headers);
+    let response = send(&client, builder).await?;
+    Ok(response.json::<Mapping>().await?)
+}
+
 /// PATCH /configs
 #[instrument]
 pub async fn patch_configs(config: &Mapping) -> Result<()> {
@@ -40,7 +60,7 @@ pub async fn patch_configs(config: &Mapping) -> Result<()> {
 
     let client = reqwest::ClientBuilder::new().no_proxy().build()?;
     let builder = client.patch(&url).headers(headers.clone()).json(config);
-    builder.send().await?;
+    send(&client, builder).await?.error_for_status()?;
     Ok(())
 }
 
@@ -89,6 +109,7 @@ impl From<ProxyProviderItem> for ProxyItem {
             vehicle_type: _,
             test_url: _,
             expected_status: _,
+            updated_at: _,
         } = item;
 
         let now = proxies
@@ -125,11 +146,98 @@ pub async fn get_proxies() -> Result<ProxiesRes> {
 
     let client = reqwest::ClientBuilder::new().no_proxy().build()?;
     let builder = client.get(&url).headers(headers);
-    let response = builder.send().await?;
+    // 密钥错误时返回 401，这里转为错误以便区分
+    let response = send(&client, builder).await?.error_for_status()?;
 
     Ok(response.json::<ProxiesRes>().await?)
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConnectionMetadata {
+    pub network: String,
+    pub r#type: String,
+    pub host: String,
+    #[serde(rename = "sourceIP")]
+    pub source_ip: String,
+    pub source_port: String,
+    #[serde(rename = "destinationIP")]
+    pub destination_ip: Option<String>,
+    pub destination_port: String,
+    pub process: Option<String>,
+    pub process_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConnectionItem {
+    pub id: String,
+    pub metadata: ConnectionMetadata,
+    pub upload: u64,
+    pub download: u64,
+    pub start: String,
+    pub chains: Vec<String>,
+    pub rule: String,
+    pub rule_payload: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionsRes {
+    #[serde(default)]
+    pub download_total: u64,
+    #[serde(default)]
+    pub upload_total: u64,
+    /// 没有连接时部分核心返回 null
+    #[serde(default)]
+    pub connections: Option<Vec<ConnectionItem>>,
+}
+
+/// 连接的过滤条件，均忽略大小写
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ConnectionFilter {
+    /// 匹配域名或目标 IP 的子串
+    pub host: Option<String>,
+    /// 匹配代理链中任一节点或分组的子串
+    pub chain: Option<String>,
+}
+
+impl ConnectionFilter {
+    pub fn matches(&self, item: &ConnectionItem) -> bool {
+        let contains = |haystack: &str, needle: &str| {
+            haystack
+                .to_lowercase()
+                .contains(needle.to_lowercase().as_str())
+        };
+        let host = self.host.as_deref().map_or(true, |host| {
+            contains(&item.metadata.host, host)
+                || item
+                    .metadata
+                    .destination_ip
+                    .as_deref()
+                    .is_some_and(|ip| contains(ip, host))
+        });
+        let chain = self.chain.as_deref().map_or(true, |chain| {
+            item.chains.iter().any(|node| contains(node, chain))
+        });
+        host && chain
+    }
+}
+
+/// GET /connections
+/// 获取当前的连接
+#[instrument]
+pub async fn get_connections() -> Result<ConnectionsRes> {
+    let (url, headers) = clash_client_info()?;
+    let url = format!("{url}/connections");
+
+    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+    let builder = client.get(&url).headers(headers);
+    let response = send(&client, builder).await?.error_for_status()?;
+
+    Ok(response.json::<ConnectionsRes>().await?)
+}
+
 /// GET /proxies/{name}
 /// 获取单个代理
 /// name: 代理名称
@@ -143,7 +251,7 @@ pub async fn get_proxy(name: String) -> Result<ProxyItem> {
 
     let client = reqwest::ClientBuilder::new().no_proxy().build()?;
     let builder = client.get(&url).headers(headers);
-    let response = builder.send().await?;
+    let response = send(&client, builder).await?;
 
     Ok(response.json::<ProxyItem>().await?)
 }
@@ -162,7 +270,7 @@ pub async fn update_proxy(group: &str, name: &str) -> Result<()> {
 
     let client = reqwest::ClientBuilder::new().no_proxy().build()?;
     let builder = client.put(&url).headers(headers).json(&data);
-    let response = builder.send().await?;
+    let response = send(&client, builder).await?;
 
     match response.status().as_u16() {
         204 => Ok(()),
@@ -172,6 +280,25 @@ pub async fn update_proxy(group: &str, name: &str) -> Result<()> {
     }
 }
 
+/// DELETE /proxies/{group}
+/// 取消测速类分组固定的节点，恢复自动选择，仅 mihomo 支持
+#[instrument]
+pub async fn unfix_proxy(group: &str) -> Result<()> {
+    let (url, headers) = clash_client_info()?;
+    let url = format!("{url}/proxies/{group}");
+
+    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+    let builder = client.delete(&url).headers(headers);
+    let response = send(&client, builder).await?;
+
+    match response.status().as_u16() {
+        204 => Ok(()),
+        status => {
+            bail!("failed to unfix proxy with status \"{status}\"")
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum VehicleType {
     File,
@@ -209,6 +336,8 @@ pub struct ProxyProviderItem {
     pub test_url: Option<String>, // Mihomo Only
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expected_status: Option<String>, // Mihomo Only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>, // 最近一次更新的时间
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -227,7 +356,7 @@ pub async fn get_providers_proxies() -> Result<ProvidersProxiesRes> {
 
     let client = reqwest::ClientBuilder::new().no_proxy().build()?;
     let builder = client.get(&url).headers(headers);
-    let response = builder.send().await?;
+    let response = send(&client, builder).await?;
 
     Ok(response.json::<ProvidersProxiesRes>().await?)
 }
@@ -243,7 +372,7 @@ pub async fn get_providers_proxies_group(group: String) -> Result<ProxyProviderI
 
     let client = reqwest::ClientBuilder::new().no_proxy().build()?;
     let builder = client.get(&url).headers(headers);
-    let response = builder.send().await?;
+    let response = send(&client, builder).await?;
 
     Ok(response.json::<ProxyProviderItem>().await?)
 }
@@ -258,7 +387,7 @@ pub async fn update_providers_proxies_group(name: &str) -> Result<()> {
 
     let client = reqwest::ClientBuilder::new().no_proxy().build()?;
     let builder = client.put(&url).headers(headers);
-    let response = builder.send().await?;
+    let response = send(&client, builder).await?;
 
     match response.status().as_u16() {
         204 => Ok(()),
@@ -279,14 +408,14 @@ pub async fn get_providers_proxies_healthcheck(name: String) -> Result<Mapping>
 
     let client = reqwest::ClientBuilder::new().no_proxy().build()?;
     let builder = client.get(&url).headers(headers);
-    let response = builder.send().await?;
+    let response = send(&client, builder).await?;
 
     Ok(response.json::<Mapping>().await?)
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct DelayRes {
-    delay: u64,
+    pub delay: u64,
 }
 
 /// GET /proxies/{name}/delay
@@ -305,41 +434,480 @@ pub async fn get_proxy_delay(name: String, test_url: Option<String>) -> Result<D
         .get(&url)
         .headers(headers)
         .query(&[("timeout", "10000"), ("url", &test_url)]);
-    let response = builder.send().await?;
+    let response = send(&client, builder).await?;
 
     Ok(response.json::<DelayRes>().await?)
 }
 
-/// 根据clash info获取clash服务地址和请求头
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RuleItem {
+    pub r#type: String,
+    pub payload: String,
+    /// 匹配后使用的代理或分组
+    pub proxy: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct RulesRes {
+    #[serde(default)]
+    pub rules: Vec<RuleItem>,
+}
+
+/// GET /rules
+/// 获取按顺序排列的规则
 #[instrument]
-fn clash_client_info() -> Result<(String, HeaderMap)> {
-    let client = { Config::clash().data().get_client_info() };
+pub async fn get_rules() -> Result<RulesRes> {
+    let (url, headers) = clash_client_info()?;
+    let url = format!("{url}/rules");
+
+    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+    let builder = client.get(&url).headers(headers);
+    let response = send(&client, builder).await?.error_for_status()?;
+
+    Ok(response.json::<RulesRes>().await?)
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct DnsAnswer {
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct DnsQueryRes {
+    #[serde(default)]
+    pub answer: Vec<DnsAnswer>,
+}
+
+/// GET /dns/query
+/// 使用核心的 DNS 解析域名，Mihomo Only
+#[instrument]
+pub async fn dns_query(name: &str) -> Result<DnsQueryRes> {
+    let (url, headers) = clash_client_info()?;
+    let url = format!("{url}/dns/query");
+
+    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+    let builder = client
+        .get(&url)
+        .headers(headers)
+        .query(&[("name", name), ("type", "A")]);
+    let response = send(&client, builder).await?.error_for_status()?;
 
-    let server = format!("http://{}", client.server);
+    Ok(response.json::<DnsQueryRes>().await?)
+}
 
-    let mut headers = HeaderMap::new();
-    headers.insert("Content-Type", "application/json".parse()?);
+/// POST /cache/dns/flush
+/// 清空核心的 DNS 缓存，Mihomo Only
+#[instrument]
+pub async fn flush_dns_cache() -> Result<()> {
+    let (url, headers) = clash_client_info()?;
+    let url = format!("{url}/cache/dns/flush");
+
+    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+    let builder = client.post(&url).headers(headers);
+    let response = send(&client, builder).await?;
 
-    if let Some(secret) = client.secret {
-        let secret = format!("Bearer {}", secret).parse()?;
-        headers.insert("Authorization", secret);
+    match response.status().as_u16() {
+        200 | 204 => Ok(()),
+        404 => bail!("the current core does not support flushing the dns cache"),
+        status => {
+            bail!("failed to flush dns cache with status \"{status}\"")
+        }
     }
+}
 
-    Ok((server, headers))
+/// 外部控制接口的连接参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerEndpoint {
+    pub scheme: String,
+    pub host: String,
+    pub port: u16,
+    pub secret: Option<String>,
+}
+
+impl ControllerEndpoint {
+    /// 从生成的配置中解析，并应用 verge 中的覆盖项
+    pub fn resolve() -> Self {
+        let overrides = { Config::verge().latest().controller_override.clone() };
+        Self::resolve_with(&overrides.unwrap_or_default())
+    }
+
+    /// 使用指定的覆盖项解析，用于保存前校验
+    pub fn resolve_with(overrides: &ControllerOverride) -> Self {
+        let info = { Config::clash().data().get_client_info() };
+        Self::from_parts(info, overrides)
+    }
+
+    fn from_parts(info: ClashInfo, overrides: &ControllerOverride) -> Self {
+        let (host, port) = match info.server.rsplit_once(':') {
+            Some((host, port)) => (host.to_string(), port.parse().unwrap_or(9090)),
+            None => (info.server, 9090),
+        };
+        // 监听所有地址时无法直接连接，改为本机地址
+        let host = match host.as_str() {
+            "" | "0.0.0.0" => "127.0.0.1".to_string(),
+            "[::]" => "[::1]".to_string(),
+            _ => host,
+        };
+
+        Self {
+            scheme: overrides
+                .scheme
+                .clone()
+                .unwrap_or_else(|| "http".to_string()),
+            host: overrides.host.clone().unwrap_or(host),
+            port: overrides.port.unwrap_or(port),
+            secret: overrides.secret.clone().or(info.secret),
+        }
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("{}://{}:{}", self.scheme, self.host, self.port)
+    }
+
+    pub fn headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type", "application/json".parse()?);
+
+        if let Some(secret) = self.secret.as_ref().filter(|s| !s.is_empty()) {
+            let secret = format!("Bearer {}", secret).parse()?;
+            headers.insert("Authorization", secret);
+        }
+        Ok(headers)
+    }
+
+    /// 请求 `/version` 确认接口可以访问且密钥正确
+    pub async fn check_reachable(&self) -> Result<()> {
+        let url = format!("{}/version", self.base_url());
+        let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+        let builder = client.get(&url).headers(self.headers()?);
+        send(&client, builder).await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// 连接外部控制接口的各个阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticStage {
+    /// 建立 TCP 连接，失败通常是核心未运行、端口错误或被防火墙拦截
+    Connect,
+    /// 收到 HTTP 响应
+    Handshake,
+    /// 使用密钥认证
+    Auth,
+    /// 请求 `/version`
+    Version,
+}
+
+/// 外部控制接口的诊断结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ControllerDiagnostics {
+    /// 尝试连接的地址
+    pub endpoint: String,
+    pub has_secret: bool,
+    /// 失败的阶段，全部通过时为 None
+    pub failed_stage: Option<DiagnosticStage>,
+    pub error: Option<String>,
+    pub version: Option<String>,
+}
+
+impl ControllerEndpoint {
+    /// 逐个阶段尝试连接外部控制接口，返回第一个失败的阶段
+    pub async fn diagnose(&self) -> ControllerDiagnostics {
+        let mut report = ControllerDiagnostics {
+            endpoint: self.base_url(),
+            has_secret: self.secret.as_ref().is_some_and(|s| !s.is_empty()),
+            failed_stage: None,
+            error: None,
+            version: None,
+        };
+        match self.run_diagnose_stages().await {
+            Ok(version) => report.version = Some(version),
+            Err((stage, err)) => {
+                report.failed_stage = Some(stage);
+                report.error = Some(format!("{err:#}"));
+            }
+        }
+        report
+    }
+
+    async fn run_diagnose_stages(&self) -> Result<String, (DiagnosticStage, anyhow::Error)> {
+        const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+        let host = self.host.trim_start_matches('[').trim_end_matches(']');
+        match tokio::time::timeout(TIMEOUT, tokio::net::TcpStream::connect((host, self.port))).await
+        {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => return Err((DiagnosticStage::Connect, err.into())),
+            Err(_) => {
+                return Err((
+                    DiagnosticStage::Connect,
+                    anyhow::anyhow!("connect timed out after {TIMEOUT:?}"),
+                ))
+            }
+        }
+
+        let client = reqwest::ClientBuilder::new()
+            .no_proxy()
+            .timeout(TIMEOUT)
+            .build()
+            .map_err(|err| (DiagnosticStage::Handshake, err.into()))?;
+        let url = format!("{}/version", self.base_url());
+
+        // 先不带密钥请求，确认对端是 HTTP 服务
+        let response = client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|err| (DiagnosticStage::Handshake, err.into()))?;
+        if !response.status().is_success()
+            && !matches!(
+                response.status(),
+                reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+            )
+        {
+            return Err((
+                DiagnosticStage::Handshake,
+                anyhow::anyhow!("unexpected status \"{}\"", response.status()),
+            ));
+        }
+
+        let headers = self.headers().map_err(|err| (DiagnosticStage::Auth, err))?;
+        let response = client
+            .get(&url)
+            .headers(headers)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| (DiagnosticStage::Auth, err.into()))?;
+
+        #[derive(Deserialize)]
+        struct Version {
+            version: String,
+        }
+        response
+            .json::<Version>()
+            .await
+            .map(|res| res.version)
+            .map_err(|err| (DiagnosticStage::Version, err.into()))
+    }
+}
+
+/// 外部控制接口的往返延迟，单位毫秒
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ControllerLatency {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+    /// 成功的请求次数
+    pub samples: usize,
+    /// 失败的请求次数
+    pub failures: usize,
+}
+
+impl ControllerLatency {
+    fn from_samples(samples: &[std::time::Duration], failures: usize) -> Self {
+        let millis = samples
+            .iter()
+            .map(|sample| sample.as_secs_f64() * 1000.0)
+            .collect::<Vec<_>>();
+        if millis.is_empty() {
+            return Self {
+                failures,
+                ..Default::default()
+            };
+        }
+        Self {
+            min: millis.iter().copied().fold(f64::INFINITY, f64::min),
+            avg: millis.iter().sum::<f64>() / millis.len() as f64,
+            max: millis.iter().copied().fold(0.0, f64::max),
+            samples: millis.len(),
+            failures,
+        }
+    }
+}
+
+impl ControllerEndpoint {
+    /// 连续请求 `/version` 测量往返延迟，复用同一连接，不计入建立连接的时间
+    pub async fn measure_latency(&self, rounds: usize) -> Result<ControllerLatency> {
+        const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+        let client = reqwest::ClientBuilder::new()
+            .no_proxy()
+            .timeout(TIMEOUT)
+            .build()?;
+        let url = format!("{}/version", self.base_url());
+        let headers = self.headers()?;
+        // 预先建立连接
+        client
+            .get(&url)
+            .headers(headers.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let mut samples = Vec::with_capacity(rounds);
+        let mut failures = 0;
+        for _ in 0..rounds {
+            let start = std::time::Instant::now();
+            let res = client.get(&url).headers(headers.clone()).send().await;
+            match res.and_then(|response| response.error_for_status()) {
+                Ok(_) => samples.push(start.elapsed()),
+                Err(err) => {
+                    log::debug!(target: "app", "controller latency request failed: {err}");
+                    failures += 1;
+                }
+            }
+        }
+        Ok(ControllerLatency::from_samples(&samples, failures))
+    }
+}
+
+/// 外部控制接口返回 401 或 403，通常是保存的密钥与正在运行的核心不一致
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "controller authentication failed with status {0}, the secret does not match the running core"
+)]
+pub struct ControllerAuthError(pub u16);
+
+/// 两次认证失败通知的最短间隔，单位秒
+const AUTH_NOTICE_INTERVAL: i64 = 60;
+
+/// 发送请求并区分认证失败
+/// 开启 `controller_auth_recovery` 时，认证失败会从运行配置中重新读取密钥并重试一次
+async fn send(client: &reqwest::Client, builder: RequestBuilder) -> Result<Response> {
+    let is_auth_failure = |status: reqwest::StatusCode| {
+        matches!(
+            status,
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+        )
+    };
+
+    let request = builder.build()?;
+    let retry = request.try_clone();
+    let response = client.execute(request).await?;
+    if !is_auth_failure(response.status()) {
+        return Ok(response);
+    }
+    let mut status = response.status();
+
+    let recovery = { Config::verge().latest().controller_auth_recovery };
+    if let Some(mut retry) = retry.filter(|_| recovery.unwrap_or(true)) {
+        if let Some(secret) = recover_secret()? {
+            log::warn!(target: "app", "controller secret mismatched, retry with the secret from the run config");
+            retry
+                .headers_mut()
+                .insert("Authorization", format!("Bearer {secret}").parse()?);
+            let response = client.execute(retry).await?;
+            if !is_auth_failure(response.status()) {
+                crate::log_err!(crate::core::CoreManager::global().apply_recovered_secret(secret));
+                return Ok(response);
+            }
+            status = response.status();
+        }
+    }
+
+    notice_auth_failure();
+    bail!(ControllerAuthError(status.as_u16()))
+}
+
+/// 从运行配置中读取核心实际使用的密钥，与当前使用的密钥不同时返回
+/// 手动设置了覆盖的密钥时不做修改
+fn recover_secret() -> Result<Option<String>> {
+    let overridden = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        verge
+            .controller_override
+            .as_ref()
+            .is_some_and(|o| o.secret.is_some())
+    };
+    if overridden {
+        return Ok(None);
+    }
+
+    let path = crate::utils::dirs::app_home_dir()?.join(crate::config::RUNTIME_CONFIG);
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    let Some(secret) = parse_secret(&content) else {
+        return Ok(None);
+    };
+    let current = { Config::clash().latest().get_client_info().secret };
+    if current.as_ref() == Some(&secret) {
+        return Ok(None);
+    }
+    Ok(Some(secret))
+}
+
+/// 读取配置中的 `secret` 字段
+fn parse_secret(content: &str) -> Option<String> {
+    let config = serde_yaml::from_str::<Mapping>(content).ok()?;
+    config.get("secret")?.as_str().map(String::from)
+}
+
+/// 通知前端认证失败，短时间内只通知一次
+fn notice_auth_failure() {
+    static LAST_NOTICE: AtomicI64 = AtomicI64::new(0);
+
+    let now = chrono::Utc::now().timestamp();
+    let last = LAST_NOTICE.load(Ordering::Relaxed);
+    if now - last < AUTH_NOTICE_INTERVAL
+        || LAST_NOTICE
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+    {
+        return;
+    }
+    crate::core::handle::Handle::notice_message(
+        "controller_auth::error",
+        "controller authentication failed",
+    );
+}
+
+/// 获取clash服务地址和请求头
+#[instrument]
+fn clash_client_info() -> Result<(String, HeaderMap)> {
+    let endpoint = ControllerEndpoint::resolve();
+    Ok((endpoint.base_url(), endpoint.headers()?))
 }
 
 /// 缩短clash的日志
 #[instrument]
 pub fn parse_log(log: String) -> String {
+    // 有损解码后的替换字符占多个字节，切片前需确认落在字符边界上
     if log.starts_with("time=") && log.len() > 33 {
-        return log[33..].to_owned();
+        if let Some(short) = log.get(33..) {
+            return short.to_owned();
+        }
     }
     if log.len() > 9 {
-        return log[9..].to_owned();
+        if let Some(short) = log.get(9..) {
+            return short.to_owned();
+        }
     }
     log
 }
 
+/// 解析核心日志的级别，无法识别时返回 None
+pub fn parse_log_level(log: &str) -> Option<tracing::Level> {
+    let level = match log.find("level=") {
+        Some(i) => log[i + 6..].split_whitespace().next()?,
+        // clash-rs 的日志格式为 `<time> INFO <target>: <msg>`
+        None => log.split_whitespace().nth(1)?,
+    };
+    match level.trim_matches('"').to_ascii_lowercase().as_str() {
+        "trace" => Some(tracing::Level::TRACE),
+        "debug" | "dbg" => Some(tracing::Level::DEBUG),
+        "info" | "inf" => Some(tracing::Level::INFO),
+        "warn" | "warning" | "wrn" => Some(tracing::Level::WARN),
+        "error" | "err" | "fatal" | "panic" => Some(tracing::Level::ERROR),
+        _ => None,
+    }
+}
+
 /// 缩短clash -t的错误输出
 /// 仅适配 clash p核 8-26、clash meta 1.13.1
 #[instrument]
@@ -369,6 +937,74 @@ pub fn parse_check_output(log: String) -> String {
     log
 }
 
+/// clash -t 输出中的错误与警告
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckMessages {
+    pub errors: Vec<String>,
+    /// 检查通过时也可能输出警告，例如已废弃的配置项
+    pub warnings: Vec<String>,
+}
+
+/// 从单行日志中取出消息内容
+fn check_log_message(line: &str) -> String {
+    if let Some(i) = line.find("msg=") {
+        return line[i + 4..].trim().trim_matches('"').to_owned();
+    }
+    // `<time> WRN <msg>` 格式
+    line.split_whitespace()
+        .skip(2)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 将 clash -t 的输出分为错误与警告，`success` 为检查是否通过
+pub fn parse_check_messages(log: &str, success: bool) -> CheckMessages {
+    let warnings = log
+        .lines()
+        .filter(|line| parse_log_level(line.trim()) == Some(tracing::Level::WARN))
+        .map(|line| check_log_message(line.trim()))
+        .filter(|msg| !msg.is_empty())
+        .collect();
+    let errors = match success {
+        true => vec![],
+        false => {
+            let error = parse_check_output(log.to_owned());
+            let error = match !error.is_empty() {
+                true => error,
+                false => log.to_owned(),
+            };
+            error.lines().map(String::from).collect()
+        }
+    };
+    CheckMessages { errors, warnings }
+}
+
+#[test]
+fn test_parse_check_messages() {
+    let log = r#"time="2024-05-01T10:00:00+08:00" level=info msg="Start initial configuration in progress"
+time="2024-05-01T10:00:00+08:00" level=warning msg="The `geosite.dat` option is deprecated"
+12:00:00 WRN [Config] rule overlaps with a previous rule
+configuration file xxx test is successful"#;
+
+    let messages = parse_check_messages(log, true);
+    assert!(messages.errors.is_empty());
+    assert_eq!(
+        messages.warnings,
+        vec![
+            "The `geosite.dat` option is deprecated",
+            "[Config] rule overlaps with a previous rule"
+        ]
+    );
+
+    let log = r#"20:43:49 ERR [Config] configuration file test failed error=proxy 0: unsupport proxy type: hysteria path=xxx"#;
+    let messages = parse_check_messages(log, false);
+    assert_eq!(
+        messages.errors,
+        vec!["proxy 0: unsupport proxy type: hysteria"]
+    );
+    assert!(messages.warnings.is_empty());
+}
+
 #[test]
 fn test_parse_check_output() {
     let str1 = r#"xxxx\n time="2022-11-18T20:42:58+08:00" level=error msg="proxy 0: 'alpn' expected type 'string', got unconvertible type '[]interface {}'""#;
@@ -389,3 +1025,105 @@ fn test_parse_check_output() {
 
     assert_eq!(res1, res3);
 }
+
+#[test]
+fn test_controller_endpoint() {
+    let info = ClashInfo {
+        port: 7890,
+        server: "0.0.0.0:9090".into(),
+        secret: Some("secret".into()),
+    };
+
+    let endpoint = ControllerEndpoint::from_parts(info.clone(), &ControllerOverride::default());
+    assert_eq!(endpoint.base_url(), "http://127.0.0.1:9090");
+    assert_eq!(endpoint.secret.as_deref(), Some("secret"));
+
+    let overrides = ControllerOverride {
+        scheme: Some("https".into()),
+        host: Some("clash.example.com".into()),
+        port: Some(443),
+        secret: None,
+    };
+    let endpoint = ControllerEndpoint::from_parts(info, &overrides);
+    assert_eq!(endpoint.base_url(), "https://clash.example.com:443");
+    assert_eq!(endpoint.secret.as_deref(), Some("secret"));
+}
+
+#[test]
+fn test_parse_log_level() {
+    use tracing::Level;
+
+    let premium = r#"time="2024-01-01T00:00:00+08:00" level=warning msg="xxx""#;
+    let clash_rs = "2024-01-01T00:00:00.000000Z ERROR clash_lib::app: xxx";
+    assert_eq!(parse_log_level(premium), Some(Level::WARN));
+    assert_eq!(parse_log_level(clash_rs), Some(Level::ERROR));
+    assert_eq!(parse_log_level("configuration file test failed"), None);
+}
+
+#[test]
+fn test_connection_filter() {
+    let item: ConnectionItem = serde_json::from_str(
+        r#"{
+            "id": "1",
+            "metadata": {
+                "network": "tcp",
+                "type": "HTTP",
+                "host": "www.Example.com",
+                "sourceIP": "127.0.0.1",
+                "sourcePort": "51234",
+                "destinationIP": "93.184.216.34",
+                "destinationPort": "443"
+            },
+            "upload": 10,
+            "download": 20,
+            "start": "2024-01-01T00:00:00Z",
+            "chains": ["Node A", "Proxy"],
+            "rule": "Match",
+            "rulePayload": ""
+        }"#,
+    )
+    .unwrap();
+
+    assert!(ConnectionFilter::default().matches(&item));
+    let filter = |host: Option<&str>, chain: Option<&str>| ConnectionFilter {
+        host: host.map(String::from),
+        chain: chain.map(String::from),
+    };
+    assert!(filter(Some("example"), None).matches(&item));
+    assert!(filter(Some("93.184"), Some("node a")).matches(&item));
+    assert!(!filter(Some("example"), Some("Node B")).matches(&item));
+    assert!(!filter(Some("google"), None).matches(&item));
+}
+
+#[test]
+fn test_controller_latency_from_samples() {
+    use std::time::Duration;
+
+    let latency = ControllerLatency::from_samples(
+        &[
+            Duration::from_millis(2),
+            Duration::from_millis(4),
+            Duration::from_millis(9),
+        ],
+        1,
+    );
+    assert_eq!(latency.min, 2.0);
+    assert_eq!(latency.avg, 5.0);
+    assert_eq!(latency.max, 9.0);
+    assert_eq!(latency.samples, 3);
+    assert_eq!(latency.failures, 1);
+
+    let latency = ControllerLatency::from_samples(&[], 5);
+    assert_eq!(latency.samples, 0);
+    assert_eq!(latency.min, 0.0);
+}
+
+#[test]
+fn test_parse_secret() {
+    assert_eq!(
+        parse_secret("mixed-port: 7890\nsecret: abc\n"),
+        Some("abc".to_string())
+    );
+    assert_eq!(parse_secret("mixed-port: 7890\n"), None);
+    assert_eq!(parse_secret("- not a mapping"), None);
+}
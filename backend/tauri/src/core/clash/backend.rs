@@ -0,0 +1,517 @@
+use super::{
+    api,
+    core::{CoreManager, CoreState, RunType},
+    log_file::{CoreLogFile, CoreLogLimits},
+};
+use crate::{
+    config::{
+        nyanpasu::{ClashCore, CoreCleanExit},
+        Config,
+    },
+    core::{logger::Logger, manager},
+    utils::dirs,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, Ordering},
+        Arc,
+    },
+};
+use tauri::api::process::{Command, CommandChild, CommandEvent, Encoding, TerminatedPayload};
+use tokio::sync::watch;
+use tracing::level_filters::LevelFilter;
+
+#[cfg(target_os = "windows")]
+use crate::core::win_service;
+#[cfg(target_os = "windows")]
+use std::time::Duration;
+
+/// 创建运行核心的命令
+/// 部分核心在 Windows 上会按系统代码页输出非 UTF-8 的内容，按 UTF-8 有损解码，避免整行被丢弃
+pub fn core_command(program: &str) -> Command {
+    let utf8 = Encoding::for_label(b"utf-8").expect("utf-8 encoding is always available");
+    Command::new(program).encoding(utf8)
+}
+
+/// 由应用通过启动参数或配置管理的环境变量，用户设置的同名变量会被忽略
+pub const RESERVED_CORE_ENV: [&str; 5] = [
+    "CLASH_HOME_DIR",
+    "CLASH_CONFIG_FILE",
+    "CLASH_CONFIG_STRING",
+    "CLASH_OVERRIDE_EXTERNAL_CONTROLLER",
+    "CLASH_OVERRIDE_SECRET",
+];
+
+/// 过滤掉保留的环境变量
+fn core_env(env: HashMap<String, String>) -> HashMap<String, String> {
+    env.into_iter()
+        .filter(|(key, _)| {
+            let reserved = RESERVED_CORE_ENV
+                .iter()
+                .any(|reserved| key.eq_ignore_ascii_case(reserved));
+            if reserved {
+                log::warn!(target: "app", "ignore the reserved core env `{key}`");
+            }
+            !reserved && !key.is_empty()
+        })
+        .collect()
+}
+
+/// 由应用管理的启动参数，分别为数据目录、配置文件与检查配置，用户设置的同名参数会被忽略
+pub const RESERVED_CORE_ARGS: [&str; 4] = ["d", "f", "c", "t"];
+
+/// 过滤掉保留的参数，以 `-d dir` 形式传入时一并去掉其后的值
+fn core_extra_args(args: Vec<String>) -> Vec<String> {
+    let mut filtered = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let flag = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-'));
+        let Some(flag) = flag else {
+            filtered.push(arg);
+            continue;
+        };
+        let (name, has_value) = match flag.split_once('=') {
+            Some((name, _)) => (name, true),
+            None => (flag, false),
+        };
+        if !RESERVED_CORE_ARGS.contains(&name) {
+            filtered.push(arg);
+            continue;
+        }
+        log::warn!(target: "app", "ignore the reserved core arg `{arg}`");
+        // `-t` 不带值
+        if !has_value && name != "t" {
+            args.next();
+        }
+    }
+    filtered
+}
+
+/// SIGTERM，系统关机等情况下核心会收到该信号
+const SIGTERM: i32 = 15;
+
+/// 核心是否以正常的状态退出
+fn is_clean_exit(payload: &TerminatedPayload, clean: Option<&CoreCleanExit>) -> bool {
+    if payload.code == Some(0) || payload.signal == Some(SIGTERM) {
+        return true;
+    }
+    clean.is_some_and(|clean| {
+        payload.code.is_some_and(|code| clean.codes.contains(&code))
+            || payload
+                .signal
+                .is_some_and(|signal| clean.signals.contains(&signal))
+    })
+}
+
+/// 核心日志的最低记录级别，为 `LOG_LEVELS` 中的位置加一，0 表示不过滤
+static CORE_LOG_MIN_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+const LOG_LEVELS: [LevelFilter; 6] = [
+    LevelFilter::OFF,
+    LevelFilter::ERROR,
+    LevelFilter::WARN,
+    LevelFilter::INFO,
+    LevelFilter::DEBUG,
+    LevelFilter::TRACE,
+];
+
+/// 修改核心日志的最低记录级别，正在运行的核心的输出同样立即生效
+pub fn set_core_log_min_level(level: Option<LevelFilter>) {
+    let value = level
+        .and_then(|level| LOG_LEVELS.iter().position(|l| *l == level))
+        .map_or(0, |index| index as u8 + 1);
+    CORE_LOG_MIN_LEVEL.store(value, Ordering::Relaxed);
+}
+
+fn core_log_min_level() -> Option<LevelFilter> {
+    match CORE_LOG_MIN_LEVEL.load(Ordering::Relaxed) {
+        0 => None,
+        value => LOG_LEVELS.get(value as usize - 1).copied(),
+    }
+}
+
+/// 记录核心的输出，低于 `min_level` 的日志直接丢弃
+fn log_core_output(line: String, is_clash: bool, min_level: Option<LevelFilter>) {
+    if let (Some(min_level), Some(level)) = (min_level, api::parse_log_level(&line)) {
+        if level > min_level {
+            return;
+        }
+    }
+    if is_clash {
+        let stdout = api::parse_log(line.clone());
+        log::info!(target: "app", "[clash]: {stdout}");
+    } else {
+        log::info!(target: "app", "[clash]: {line}");
+    };
+    Logger::global().set_log(line);
+}
+
+/// 核心进程自行退出的次数，不经过 `CoreManager` 的状态变化由此通知等待者
+static CORE_EXITS: Lazy<watch::Sender<u64>> = Lazy::new(|| watch::channel(0).0);
+
+pub fn subscribe_core_exits() -> watch::Receiver<u64> {
+    CORE_EXITS.subscribe()
+}
+
+fn notify_core_exit() {
+    CORE_EXITS.send_modify(|exits| *exits += 1);
+}
+
+/// 核心的运行后端，负责核心进程的启动与停止
+#[async_trait]
+pub trait CoreBackend: Send + Sync + std::fmt::Debug {
+    fn run_type(&self) -> RunType;
+
+    /// 使用指定的核心和配置启动，返回核心进程的 pid
+    async fn start(&self, clash_core: &ClashCore, config_path: &Path) -> Result<Option<u32>>;
+
+    /// 停止核心，返回是否停止了正在运行的核心
+    async fn stop(&self) -> Result<bool>;
+
+    async fn restart(&self, clash_core: &ClashCore, config_path: &Path) -> Result<Option<u32>> {
+        self.stop().await?;
+        self.start(clash_core, config_path).await
+    }
+
+    async fn state(&self) -> CoreState;
+
+    async fn status(&self) -> (CoreState, RunType) {
+        (self.state().await, self.run_type())
+    }
+}
+
+/// 正在运行的 sidecar 进程
+#[derive(Debug)]
+struct Sidecar {
+    child: CommandChild,
+    /// 由 `stop` 主动停止时设置，每个进程各有一份
+    /// 退出事件晚于 `stop` 返回到达时也不会被当作崩溃恢复
+    stopped: Arc<AtomicBool>,
+}
+
+/// 以 sidecar 子进程运行核心
+#[derive(Debug, Default)]
+pub struct ChildBackend {
+    sidecar: Arc<Mutex<Option<Sidecar>>>,
+}
+
+#[async_trait]
+impl CoreBackend for ChildBackend {
+    fn run_type(&self) -> RunType {
+        RunType::Normal
+    }
+
+    async fn start(&self, clash_core: &ClashCore, config_path: &Path) -> Result<Option<u32>> {
+        let app_dir = dirs::app_home_dir()?;
+        let app_dir = dirs::path_to_str(&app_dir)?;
+        let config_path = config_path
+            .to_str()
+            .context("failed to convert the config path to str")?;
+        let args = CoreManager::core_args(clash_core, app_dir, config_path);
+
+        let binary = manager::find_binary_path(clash_core)?;
+        manager::check_core_binary(clash_core, &binary)?;
+        let is_clash = matches!(clash_core, ClashCore::ClashPremium);
+        let min_level = { Config::verge().latest().core_log_min_level.clone() };
+        set_core_log_min_level(min_level.map(LevelFilter::from));
+        let env = { Config::verge().latest().core_env.clone() };
+        let env = core_env(env.unwrap_or_default());
+        let extra_args = { Config::verge().latest().core_extra_args.clone() };
+        let extra_args = extra_args
+            .and_then(|mut extra_args| extra_args.remove(clash_core))
+            .map(core_extra_args)
+            .unwrap_or_default();
+        let clean_exit = { Config::verge().latest().core_clean_exits.clone() };
+        let clean_exit = clean_exit.and_then(|mut exits| exits.remove(clash_core));
+        let persist = CoreLogLimits::from_config();
+
+        // envs 只会追加变量，继承的环境变量保持不变
+        let cmd = core_command(dirs::path_to_str(&binary)?);
+        let (mut rx, cmd_child) = cmd.args(args).args(extra_args).envs(env).spawn()?;
+        let pid = cmd_child.pid();
+        let stopped = Arc::new(AtomicBool::new(false));
+        *self.sidecar.lock() = Some(Sidecar {
+            child: cmd_child,
+            stopped: stopped.clone(),
+        });
+
+        let sidecar = self.sidecar.clone();
+        tauri::async_runtime::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                // 持久化的日志不受级别过滤影响
+                if let (Some(limits), CommandEvent::Stdout(line) | CommandEvent::Stderr(line)) =
+                    (&persist, &event)
+                {
+                    CoreLogFile::global().append(line, limits);
+                }
+                match event {
+                    CommandEvent::Stdout(line) => {
+                        log_core_output(line, is_clash, core_log_min_level())
+                    }
+                    // stderr 通常是崩溃信息，始终记录
+                    CommandEvent::Stderr(err) => {
+                        // let stdout = api::parse_log(err.clone());
+                        log::error!(target: "app", "[clash]: {err}");
+                        Logger::global().set_log(err);
+                    }
+                    CommandEvent::Error(err) => {
+                        log::error!(target: "app", "[clash]: {err}");
+                        Logger::global().set_log(err);
+                    }
+                    CommandEvent::Terminated(payload) => {
+                        log::info!(target: "app", "clash core terminated with code {:?}, signal {:?}", payload.code, payload.signal);
+                        // 只清掉自己，重启后的新进程不受影响
+                        {
+                            let mut sidecar = sidecar.lock();
+                            if sidecar.as_ref().map(|sidecar| sidecar.child.pid()) == Some(pid) {
+                                sidecar.take();
+                            }
+                        }
+                        notify_core_exit();
+                        if stopped.load(Ordering::SeqCst) {
+                            log::debug!(target: "app", "the core is stopped by user, skip recovering");
+                        } else if is_clean_exit(&payload, clean_exit.as_ref()) {
+                            log::info!(target: "app", "the core exited cleanly, skip recovering");
+                        } else {
+                            log::warn!(target: "app", "the core terminated unexpectedly, try to recover");
+                            let _ = CoreManager::global().recover_core();
+                        }
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        Ok(Some(pid))
+    }
+
+    async fn stop(&self) -> Result<bool> {
+        match self.sidecar.lock().take() {
+            Some(sidecar) => {
+                log::debug!(target: "app", "stop the core by sidecar");
+                sidecar.stopped.store(true, Ordering::SeqCst);
+                let _ = sidecar.child.kill();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn state(&self) -> CoreState {
+        match self.sidecar.lock().is_some() {
+            true => CoreState::Running,
+            false => CoreState::Stopped,
+        }
+    }
+}
+
+/// 连接外部启动的核心，不启动或停止任何进程
+#[derive(Debug, Default)]
+pub struct ObserverBackend;
+
+#[async_trait]
+impl CoreBackend for ObserverBackend {
+    fn run_type(&self) -> RunType {
+        RunType::Observer
+    }
+
+    async fn start(&self, _clash_core: &ClashCore, _config_path: &Path) -> Result<Option<u32>> {
+        log::debug!(target: "app", "observer mode, the core is managed externally");
+        Ok(None)
+    }
+
+    async fn stop(&self) -> Result<bool> {
+        log::debug!(target: "app", "observer mode, leave the external core running");
+        Ok(false)
+    }
+
+    /// 外部控制接口能访问即认为核心正在运行
+    async fn state(&self) -> CoreState {
+        match tokio::time::timeout(std::time::Duration::from_secs(3), api::get_version()).await {
+            Ok(Ok(_)) => CoreState::Running,
+            Ok(Err(_)) => CoreState::Stopped,
+            Err(_) => CoreState::Unresponsive,
+        }
+    }
+}
+
+/// 由 Clash Nyanpasu Service 托管运行核心
+#[cfg(target_os = "windows")]
+#[derive(Debug, Default)]
+pub struct ServiceBackend;
+
+#[cfg(target_os = "windows")]
+#[async_trait]
+impl CoreBackend for ServiceBackend {
+    fn run_type(&self) -> RunType {
+        RunType::Service
+    }
+
+    async fn start(&self, _clash_core: &ClashCore, config_path: &Path) -> Result<Option<u32>> {
+        win_service::check_service().await?;
+        win_service::run_core_by_service(&config_path.to_path_buf()).await?;
+        Ok(None)
+    }
+
+    async fn stop(&self) -> Result<bool> {
+        log::debug!(target: "app", "stop the core by service");
+        win_service::stop_core_by_service().await?;
+        Ok(true)
+    }
+
+    /// 查询服务中核心的状态，服务卡住时不会一直阻塞调用方
+    async fn state(&self) -> CoreState {
+        let timeout = { Config::verge().latest().service_status_timeout };
+        let timeout = Duration::from_millis(timeout.unwrap_or(3000));
+        match tokio::time::timeout(timeout, win_service::check_service_with_retry()).await {
+            Ok(Ok(res)) if res.code == 0 => CoreState::Running,
+            Ok(Ok(_)) => CoreState::Stopped,
+            Ok(Err(err)) => {
+                log::error!(target: "app", "lost connection to the service: {err}");
+                CoreState::Stopped
+            }
+            Err(_) => {
+                log::warn!(target: "app", "the service did not respond in {timeout:?}");
+                CoreState::Unresponsive
+            }
+        }
+    }
+}
+
+/// 不启动任何进程的后端，用于 CoreManager 的单元测试
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    running: AtomicBool,
+    /// 模拟无法停止的核心
+    stop_fails: bool,
+}
+
+#[cfg(test)]
+impl MockBackend {
+    pub fn running() -> Self {
+        Self {
+            running: AtomicBool::new(true),
+            stop_fails: false,
+        }
+    }
+
+    pub fn unstoppable() -> Self {
+        Self {
+            running: AtomicBool::new(true),
+            stop_fails: true,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// 模拟核心自行退出，不经过 `CoreManager`
+    pub fn exit(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        notify_core_exit();
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl CoreBackend for MockBackend {
+    fn run_type(&self) -> RunType {
+        RunType::Normal
+    }
+
+    async fn start(&self, _clash_core: &ClashCore, _config_path: &Path) -> Result<Option<u32>> {
+        self.running.store(true, Ordering::SeqCst);
+        Ok(None)
+    }
+
+    async fn stop(&self) -> Result<bool> {
+        if self.stop_fails {
+            anyhow::bail!("failed to kill the core");
+        }
+        Ok(self.running.swap(false, Ordering::SeqCst))
+    }
+
+    async fn state(&self) -> CoreState {
+        match self.is_running() {
+            true => CoreState::Running,
+            false => CoreState::Stopped,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+#[test]
+fn test_non_utf8_output_reaches_logger() {
+    // \377 不是合法的 UTF-8 字节
+    let (mut rx, _child) = core_command("printf")
+        .args(["nyanpasu\\377output\\n"])
+        .spawn()
+        .unwrap();
+    let line = tauri::async_runtime::block_on(async move {
+        loop {
+            match rx.recv().await {
+                Some(CommandEvent::Stdout(line)) => break line,
+                Some(_) => continue,
+                None => panic!("the output is dropped"),
+            }
+        }
+    });
+    log_core_output(line, true, None);
+
+    assert!(tauri::async_runtime::block_on(Logger::global().get_log())
+        .iter()
+        .any(|log| log.starts_with("nyanpasu\u{FFFD}output")));
+}
+
+#[test]
+fn test_core_env_skips_reserved() {
+    let env = core_env(HashMap::from([
+        ("GOMAXPROCS".to_string(), "2".to_string()),
+        ("clash_home_dir".to_string(), "/tmp".to_string()),
+    ]));
+    assert_eq!(
+        env,
+        HashMap::from([("GOMAXPROCS".to_string(), "2".to_string())])
+    );
+}
+
+#[test]
+fn test_core_extra_args_skips_reserved() {
+    let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        core_extra_args(args(&[
+            "-ext-ctl",
+            "127.0.0.1:9091",
+            "-d",
+            "/tmp",
+            "--f=/tmp/config.yaml",
+            "-t",
+            "-debug",
+        ])),
+        args(&["-ext-ctl", "127.0.0.1:9091", "-debug"])
+    );
+}
+
+#[test]
+fn test_is_clean_exit() {
+    let exit = |code: Option<i32>, signal: Option<i32>| TerminatedPayload { code, signal };
+    let clean = CoreCleanExit {
+        codes: vec![1],
+        signals: vec![],
+    };
+
+    assert!(is_clean_exit(&exit(Some(0), None), None));
+    assert!(is_clean_exit(&exit(None, Some(SIGTERM)), None));
+    assert!(!is_clean_exit(&exit(Some(1), None), None));
+    assert!(is_clean_exit(&exit(Some(1), None), Some(&clean)));
+    assert!(!is_clean_exit(&exit(None, Some(9)), Some(&clean)));
+}
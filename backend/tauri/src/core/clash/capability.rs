@@ -0,0 +1,146 @@
+use crate::{config::nyanpasu::ClashCore, utils::dirs};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+/// 各核心不支持的配置项，使用 `.` 表示嵌套的字段
+fn unsupported_keys(core: &ClashCore) -> &'static [&'static str] {
+    match core {
+        ClashCore::Mihomo | ClashCore::MihomoAlpha => &["script"],
+        ClashCore::ClashPremium => &[
+            "sniffer",
+            "geodata-mode",
+            "geox-url",
+            "find-process-mode",
+            "global-client-fingerprint",
+            "ntp",
+            "tunnels",
+            "listeners",
+            "sub-rules",
+            "dns.respect-rules",
+            "dns.prefer-h3",
+        ],
+        ClashCore::ClashRs => &[
+            "script",
+            "sniffer",
+            "geodata-mode",
+            "geox-url",
+            "find-process-mode",
+            "global-client-fingerprint",
+            "ntp",
+            "tunnels",
+            "listeners",
+            "sub-rules",
+            "dns.respect-rules",
+            "dns.prefer-h3",
+        ],
+    }
+}
+
+/// 找出配置中使用了但目标核心不支持的配置项
+pub fn unsupported_features(core: &ClashCore, config: &Mapping) -> Vec<String> {
+    unsupported_keys(core)
+        .iter()
+        .filter(|key| {
+            let mut value = None::<&Value>;
+            for (i, part) in key.split('.').enumerate() {
+                value = match i {
+                    0 => config.get(part),
+                    _ => value.and_then(|v| v.get(part)),
+                };
+            }
+            // 显式关闭的功能不算使用
+            !matches!(value, None | Some(Value::Null) | Some(Value::Bool(false)))
+        })
+        .map(|key| key.to_string())
+        .collect()
+}
+
+/// 核心支持的功能，供界面展示
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CoreCapabilities {
+    pub tun: bool,
+    /// 是否可以不重启核心切换 TUN 模式
+    pub tun_hot_toggle: bool,
+    /// 是否可以不重启核心修改部分配置
+    pub config_patch: bool,
+    pub sniffer: bool,
+    pub script: bool,
+}
+
+impl CoreCapabilities {
+    /// 与切换核心时的功能检查使用同一份数据
+    pub fn of(core: &ClashCore) -> Self {
+        let keys = unsupported_keys(core);
+        CoreCapabilities {
+            tun: true,
+            tun_hot_toggle: core.supports_tun_hot_toggle(),
+            config_patch: core.supports_config_patch(),
+            sniffer: !keys.contains(&"sniffer"),
+            script: !keys.contains(&"script"),
+        }
+    }
+}
+
+/// 可供选择的核心
+#[derive(Debug, Clone, Serialize)]
+pub struct SupportedCore {
+    pub core: ClashCore,
+    pub name: &'static str,
+    /// 是否能找到核心的可执行文件
+    pub installed: bool,
+    pub capabilities: CoreCapabilities,
+}
+
+fn display_name(core: &ClashCore) -> &'static str {
+    match core {
+        ClashCore::ClashPremium => "Clash Premium",
+        ClashCore::Mihomo => "Mihomo",
+        ClashCore::MihomoAlpha => "Mihomo Alpha",
+        ClashCore::ClashRs => "Clash Rust",
+    }
+}
+
+/// 列出所有核心及其支持的功能
+pub fn supported_cores() -> Vec<SupportedCore> {
+    ClashCore::ALL
+        .iter()
+        .map(|core| SupportedCore {
+            core: core.clone(),
+            name: display_name(core),
+            installed: dirs::find_binary_path(core).is_ok(),
+            capabilities: CoreCapabilities::of(core),
+        })
+        .collect()
+}
+
+#[test]
+fn test_unsupported_features() {
+    let config: Mapping = serde_yaml::from_str(
+        r#"
+sniffer:
+  enable: true
+geodata-mode: false
+dns:
+  respect-rules: true
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        unsupported_features(&ClashCore::ClashRs, &config),
+        vec!["sniffer", "dns.respect-rules"]
+    );
+    assert!(unsupported_features(&ClashCore::Mihomo, &config).is_empty());
+}
+
+#[test]
+fn test_core_capabilities() {
+    let rs = CoreCapabilities::of(&ClashCore::ClashRs);
+    assert!(!rs.sniffer && !rs.script && !rs.config_patch);
+
+    let mihomo = CoreCapabilities::of(&ClashCore::Mihomo);
+    assert!(mihomo.sniffer && mihomo.tun_hot_toggle && !mihomo.script);
+
+    let premium = CoreCapabilities::of(&ClashCore::ClashPremium);
+    assert!(premium.script && !premium.sniffer);
+}
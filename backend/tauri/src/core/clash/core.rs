@@ -1,44 +1,569 @@
-use super::api;
+use super::{
+    api,
+    backend::{self, ChildBackend, CoreBackend, ObserverBackend},
+    capability,
+    metrics::CoreMetrics,
+    proxies::{ProxiesGuard, ProxiesGuardExt},
+};
 use crate::{
-    config::{nyanpasu::ClashCore, Config, ConfigType},
-    core::logger::Logger,
+    config::{nyanpasu::ClashCore, Config, ConfigType, IRuntime, IVerge, RUNTIME_CONFIG},
+    core::{handle::Handle, logger::Logger, manager},
     log_err,
-    utils::dirs,
+    utils::{
+        dirs,
+        help::{self, PortUnavailable},
+        resolve,
+    },
 };
 use anyhow::{bail, Context, Result};
+use fs2::FileExt;
+use indexmap::IndexMap;
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
-use std::{fs, io::Write, sync::Arc, time::Duration};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 use sysinfo::{Pid, System};
-use tauri::api::process::{Command, CommandChild, CommandEvent};
-use tokio::time::sleep;
+use tauri::{
+    api::process::{Command, CommandChild, CommandEvent},
+    async_runtime::Receiver,
+};
+use tokio::{
+    sync::{Mutex as TokioMutex, MutexGuard as TokioMutexGuard, Notify},
+    time::sleep,
+};
 
+#[cfg(target_os = "windows")]
+use super::backend::ServiceBackend;
 #[cfg(target_os = "windows")]
 use crate::core::win_service;
 
+/// 核心异常退出后自动恢复的最大尝试次数
+const MAX_RECOVER_ATTEMPTS: u32 = 5;
+
+/// 同一核心恢复失败多少次后建议切换到其他核心
+const SUGGEST_SWITCH_CORE_FAILURES: u32 = 3;
+
+/// 推送配置前等待核心启动或恢复完成的最长时间
+const CORE_READY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 推送配置失败后，等待核心恢复运行再重试的最长时间
+const DEFERRED_UPDATE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 停止核心后等待端口被释放的最长时间
+const PORT_RELEASE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 推送配置后等待核心确认生效的最长时间
+const CONFIG_CONFIRM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 连续启动失败多少次后进入安全模式
+const SAFE_MODE_THRESHOLD: u32 = 3;
+
+/// 启动后这段时间内核心崩溃仍计为一次启动失败
+const STARTUP_STABLE_PERIOD: Duration = Duration::from_secs(30);
+
+/// 核心的运行方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunType {
+    /// 以 sidecar 子进程运行
+    Normal,
+    /// 由 Clash Nyanpasu Service 托管运行
+    Service,
+    /// 由外部启动和管理，不启动或停止核心
+    Observer,
+}
+
+/// 核心的运行状态
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoreState {
+    Running,
+    Stopped,
+    /// 服务在超时时间内没有响应，无法确定核心状态
+    Unresponsive,
+    /// 核心正在启动
+    Starting,
+    /// 核心异常退出，正在自动恢复
+    Recovering,
+    /// 长时间没有流量而被自动停止，等待用户操作后重新启动
+    IdleStopped,
+}
+
+/// 当前运行方式的选择依据，用于向用户解释服务模式为何没有生效
+#[derive(Debug, Clone, Serialize)]
+pub struct RunTypeDiagnostics {
+    /// 核心实际使用的运行方式
+    pub chosen: RunType,
+    pub enable_service_mode: bool,
+    pub enable_observer_mode: bool,
+    /// 能否连接到 Clash Nyanpasu Service
+    pub ipc_connected: bool,
+    /// 无法连接服务的原因
+    pub ipc_error: Option<String>,
+}
+
+/// 服务模式不可用的具体原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub enum ServiceModeFailure {
+    /// 当前系统不支持服务模式
+    Unsupported,
+    NotInstalled,
+    NotRunning,
+    /// 服务已运行，但无法连接
+    IpcUnreachable,
+    /// 服务的响应无法解析，通常是服务与应用的版本不匹配
+    VersionMismatch,
+}
+
+/// 服务模式的检查结果，`failure` 为空表示服务可用
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceModeCheck {
+    pub failure: Option<ServiceModeFailure>,
+    pub error: Option<String>,
+}
+
+impl ServiceModeCheck {
+    pub fn failed(failure: ServiceModeFailure, error: impl std::fmt::Display) -> Self {
+        Self {
+            failure: Some(failure),
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// 核心冒烟测试的结果
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SmokeTestResult {
+    pub success: bool,
+    /// 核心的输出，包含 stderr
+    pub output: Vec<String>,
+}
+
+/// 核心启动各阶段耗时，单位毫秒，均从开始启动时计算
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StartupTiming {
+    /// 进程创建完成
+    pub spawn_ms: u64,
+    /// 首次输出，没有输出时为 None
+    pub first_output_ms: Option<u64>,
+    /// 外部控制接口可以访问
+    pub ready_ms: u64,
+}
+
+/// 核心启动耗时的测试结果
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StartupBenchmark {
+    pub average: StartupTiming,
+    pub runs: Vec<StartupTiming>,
+}
+
+impl StartupBenchmark {
+    fn from_runs(runs: Vec<StartupTiming>) -> Self {
+        let mean = |values: Vec<u64>| match values.len() {
+            0 => None,
+            len => Some(values.iter().sum::<u64>() / len as u64),
+        };
+        let average = StartupTiming {
+            spawn_ms: mean(runs.iter().map(|run| run.spawn_ms).collect()).unwrap_or_default(),
+            first_output_ms: mean(runs.iter().filter_map(|run| run.first_output_ms).collect()),
+            ready_ms: mean(runs.iter().map(|run| run.ready_ms).collect()).unwrap_or_default(),
+        };
+        Self { average, runs }
+    }
+}
+
+/// 检查配置超时，区别于配置错误
+#[derive(Debug, thiserror::Error)]
+#[error("config check timed out after {0:?}")]
+pub struct ConfigCheckTimeout(pub Duration);
+
+/// 核心检查配置未通过，包含解析后的错误信息
+#[derive(Debug, thiserror::Error)]
+#[error("{}", .0.join("\n"))]
+pub struct ConfigCheckFailed(pub Vec<String>);
+
+/// 检查配置时需要的数据文件，核心可能下载或改写它们，也可能与运行中的核心争用文件锁
+const CHECK_DATA_FILES: [&str; 5] = [
+    "Country.mmdb",
+    "geoip.dat",
+    "geosite.dat",
+    "geoip.metadb",
+    "ASN.mmdb",
+];
+
+/// 准备检查配置用的数据目录，数据文件复制一份，其余文件链接到原目录
+/// 使配置中以相对路径引用的提供者等文件仍然可用
+fn prepare_check_dir(from: &Path, to: &Path) -> Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let src = entry.path();
+        let dst = to.join(&name);
+        if CHECK_DATA_FILES.iter().any(|file| name == *file) {
+            if src.is_file() {
+                fs::copy(&src, &dst)?;
+            }
+            continue;
+        }
+        // 无法创建链接时（如 Windows 未开启开发者模式）只是缺少该文件
+        if let Err(err) = link_check_entry(&src, &dst) {
+            log::debug!(target: "app", "failed to link {src:?} for the config check: {err}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link_check_entry(src: &Path, dst: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(src, dst)
+}
+
+#[cfg(windows)]
+fn link_check_entry(src: &Path, dst: &Path) -> std::io::Result<()> {
+    if src.is_dir() {
+        std::os::windows::fs::symlink_dir(src, dst)
+    } else {
+        std::os::windows::fs::symlink_file(src, dst)
+    }
+}
+
+#[derive(Debug, Default)]
+struct CheckOutput {
+    success: bool,
+    stdout: String,
+}
+
+/// 核心启动被用户取消
+#[derive(Debug, thiserror::Error)]
+#[error("the core start was cancelled")]
+pub struct StartCancelled;
+
+/// 启动或停止核心时实际进行的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoreAction {
+    /// 之前没有运行中的核心
+    Started,
+    /// 停止了正在运行的核心后重新启动
+    Restarted,
+    Stopped,
+    /// 核心本来就没有运行
+    WasNotRunning,
+}
+
+/// 更新配置的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigApplyStatus {
+    /// 核心已经在使用新的配置
+    Applied,
+    /// 配置已推送，但超时前未能确认生效
+    Unconfirmed,
+    /// 核心暂时无法连接，恢复运行后再推送
+    Deferred,
+}
+
+/// 计划内停止核心期间持有的守卫，见 [`CoreManager::suppress_recovery`]
+#[must_use = "recovery is only suppressed while the guard is held"]
+pub struct RecoverySuppressed<'a>(&'a AtomicUsize);
+
+impl Drop for RecoverySuppressed<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 已安装的核心无法运行，提示用户重新下载或切换核心
+#[derive(Debug, Clone, Serialize)]
+pub struct CoreBroken {
+    pub core: ClashCore,
+    pub reason: String,
+}
+
+/// 核心反复恢复失败时建议切换到的其他已安装核心
+#[derive(Debug, Clone, Serialize)]
+pub struct CoreSwitchSuggestion {
+    pub core: ClashCore,
+    pub failures: u32,
+    pub candidates: Vec<ClashCore>,
+}
+
+/// 核心在等待时间内没有完成启动或恢复
+#[derive(Debug, thiserror::Error)]
+#[error("the core is not ready after {0:?}")]
+pub struct CoreNotReady(pub Duration);
+
+/// 核心在等待时间内没有进入目标状态
+#[derive(Debug, thiserror::Error)]
+#[error("the core did not become {target:?} in {timeout:?}, the current state is {actual:?}")]
+pub struct CoreStateTimeout {
+    pub target: CoreState,
+    pub actual: CoreState,
+    pub timeout: Duration,
+}
+
+/// TUN 模式的状态
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TunStatus {
+    /// 配置中是否开启了 TUN 模式
+    pub requested: bool,
+    /// 核心是否真正启用了 TUN
+    pub active: bool,
+    /// 核心创建的 TUN 设备名
+    pub device_name: Option<String>,
+}
+
+/// 切换核心失败的原因，供前端给出针对性的提示
+#[derive(Debug, thiserror::Error, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChangeCoreError {
+    /// 配置检查未通过，`details` 为核心输出的错误信息
+    #[error("config invalid: {}", details.join("\n"))]
+    ConfigInvalid { details: Vec<String> },
+    /// 核心启动失败
+    #[error("failed to start the core: {cause}")]
+    SpawnFailed { cause: String },
+    /// 外部控制端口被占用
+    #[error("port {port} is already in use")]
+    PortConflict { port: u16 },
+    /// 配置检查超时，通常是规则集过大
+    #[error("config check timed out after {timeout_secs}s")]
+    CheckTimedOut { timeout_secs: u64 },
+    /// 配置中使用了目标核心不支持的功能
+    #[error("unsupported features: {}", features.join(", "))]
+    UnsupportedFeatures { features: Vec<String> },
+    /// 核心可执行文件的架构与当前系统不符
+    #[error("this core binary is built for {binary} but your system is {system}")]
+    ArchMismatch { binary: String, system: String },
+}
+
+/// 切换核心的各个阶段，依次通过 `nyanpasu://change-core-progress` 通知前端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeCorePhase {
+    /// 修改核心设置的草稿
+    Draft,
+    /// 生成运行时配置
+    Generate,
+    /// 使用新核心检查配置
+    Check,
+    ClearLogs,
+    /// 启动新核心
+    Start,
+    /// 外部控制接口已就绪
+    Ready,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeCoreProgress {
+    pub core: ClashCore,
+    pub phase: ChangeCorePhase,
+}
+
+/// 只有核心实际给出的检查结果才算配置错误，找不到或无法执行核心都属于启动失败
+impl From<anyhow::Error> for ChangeCoreError {
+    fn from(err: anyhow::Error) -> Self {
+        if let Some(PortUnavailable(port)) = err.downcast_ref::<PortUnavailable>() {
+            return Self::PortConflict { port: *port };
+        }
+        if let Some(ConfigCheckTimeout(timeout)) = err.downcast_ref::<ConfigCheckTimeout>() {
+            return Self::CheckTimedOut {
+                timeout_secs: timeout.as_secs(),
+            };
+        }
+        match err.downcast::<ConfigCheckFailed>() {
+            Ok(ConfigCheckFailed(details)) => Self::ConfigInvalid { details },
+            Err(err) => Self::SpawnFailed {
+                cause: format!("{err:?}"),
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CoreManager {
-    sidecar: Arc<Mutex<Option<CommandChild>>>,
+    /// 当前使用的运行后端
+    backend: Mutex<Arc<dyn CoreBackend>>,
+
+    /// 切换核心等操作进行时持有，避免与自动重载配置等操作交错
+    op_lock: TokioMutex<()>,
+
+    /// 独占的 pid 文件，防止多个实例在同一数据目录下启动核心
+    pid_file: Arc<Mutex<Option<fs::File>>>,
+
+    /// 用于取消正在进行的启动
+    start_cancel: Arc<Notify>,
+
+    /// 是否有恢复任务正在运行
+    recovering: Arc<AtomicBool>,
+
+    /// 是否正在启动核心
+    starting: Arc<AtomicBool>,
+
+    /// 持有中的 [`RecoverySuppressed`] 数量，大于 0 时核心退出不会触发恢复
+    recovery_suppressed: AtomicUsize,
+
+    /// 核心是否因为空闲被自动停止
+    idle_stopped: AtomicBool,
+
+    /// 是否以安全模式运行，使用用户的配置成功启动或推送后退出
+    safe_mode: AtomicBool,
+
+    /// 启动或恢复结束时通知等待者
+    lifecycle_changed: Arc<Notify>,
+
+    /// 上一次成功切换前的核心与运行时配置，只保留一份用于回滚
+    previous: Mutex<Option<(ClashCore, IRuntime)>>,
+
+    /// 正在运行的核心所使用的配置
+    running_config: Mutex<Option<Mapping>>,
 
-    #[allow(unused)]
-    use_service_mode: Arc<Mutex<bool>>,
+    /// 各核心连续恢复失败的次数
+    recover_failures: Mutex<HashMap<ClashCore, u32>>,
+
+    /// 是否已安排在核心就绪后重新推送配置
+    update_pending: Arc<AtomicBool>,
+
+    /// 是否已安排恢复分组的选择，启动后紧接着推送配置时只恢复一次
+    restore_pending: AtomicBool,
+
+    /// 以子进程方式启动核心时使用的后端
+    child_backend: fn() -> Arc<dyn CoreBackend>,
+
+    /// pid 文件的路径，为空时使用数据目录下的 pid 文件
+    pid_path: Option<PathBuf>,
+
+    /// 是否修改系统 DNS、恢复节点选择等核心之外的状态，测试中关闭
+    side_effects: bool,
 }
 
 impl CoreManager {
     pub fn global() -> &'static CoreManager {
         static CORE_MANAGER: OnceCell<CoreManager> = OnceCell::new();
 
-        CORE_MANAGER.get_or_init(|| CoreManager {
-            sidecar: Arc::new(Mutex::new(None)),
-            use_service_mode: Arc::new(Mutex::new(false)),
-        })
+        CORE_MANAGER.get_or_init(|| CoreManager::with_backend(Arc::new(ChildBackend::default())))
+    }
+
+    pub fn with_backend(backend: Arc<dyn CoreBackend>) -> Self {
+        CoreManager {
+            backend: Mutex::new(backend),
+            op_lock: TokioMutex::new(()),
+            pid_file: Arc::new(Mutex::new(None)),
+            start_cancel: Arc::new(Notify::new()),
+            recovering: Arc::new(AtomicBool::new(false)),
+            starting: Arc::new(AtomicBool::new(false)),
+            recovery_suppressed: AtomicUsize::new(0),
+            idle_stopped: AtomicBool::new(false),
+            safe_mode: AtomicBool::new(false),
+            lifecycle_changed: Arc::new(Notify::new()),
+            previous: Mutex::new(None),
+            running_config: Mutex::new(None),
+            recover_failures: Mutex::new(HashMap::new()),
+            update_pending: Arc::new(AtomicBool::new(false)),
+            restore_pending: AtomicBool::new(false),
+            child_backend: || Arc::new(ChildBackend::default()),
+            pid_path: None,
+            side_effects: true,
+        }
+    }
+
+    /// 不启动任何进程的实例，用于测试核心的启动与停止
+    /// pid 文件放在临时目录中，各实例互不影响，也不会修改系统 DNS 或请求外部控制接口
+    #[cfg(test)]
+    pub fn new_for_test() -> Self {
+        use super::backend::MockBackend;
+
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNT.fetch_add(1, Ordering::SeqCst);
+        CoreManager {
+            child_backend: || Arc::new(MockBackend::default()),
+            pid_path: Some(
+                std::env::temp_dir().join(format!("nyanpasu-test-{}-{id}.pid", std::process::id())),
+            ),
+            side_effects: false,
+            ..Self::with_backend(Arc::new(MockBackend::default()))
+        }
+    }
+
+    fn backend(&self) -> Arc<dyn CoreBackend> {
+        self.backend.lock().clone()
+    }
+
+    /// 尝试获取操作锁，已有操作进行中时返回 None
+    pub fn try_lock_op(&self) -> Option<TokioMutexGuard<'_, ()>> {
+        self.op_lock.try_lock().ok()
+    }
+
+    /// 获取 pid 文件的独占锁，已被其他存活的实例持有时返回错误
+    fn lock_pid_file(&self) -> Result<()> {
+        let mut pid_file = self.pid_file.lock();
+        if pid_file.is_some() {
+            return Ok(());
+        }
+
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(match self.pid_path.as_ref() {
+                Some(path) => path.clone(),
+                None => dirs::clash_pid_path()?,
+            })
+            .context("failed to open the pid file")?;
+        if file.try_lock_exclusive().is_err() {
+            bail!("another Nyanpasu instance is managing a core here");
+        }
+        *pid_file = Some(file);
+        Ok(())
+    }
+
+    /// 读取 pid 文件中记录的 pid，需要先持有锁
+    fn read_pid(&self) -> Result<u32> {
+        let mut pid_file = self.pid_file.lock();
+        let file = pid_file.as_mut().context("the pid file is not locked")?;
+        let mut pid = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut pid)?;
+        Ok(pid.trim().parse()?)
+    }
+
+    /// 将 pid 写入持有锁的 pid 文件
+    fn write_pid(&self, pid: u32) -> Result<()> {
+        let mut pid_file = self.pid_file.lock();
+        let file = pid_file.as_mut().context("the pid file is not locked")?;
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(format!("{pid}").as_bytes())
+            .context("failed to write pid to the file")?;
+        Ok(())
+    }
+
+    /// 由应用启动并管理的核心进程的 pid，核心未运行或由服务启动时为 None
+    pub fn managed_pid(&self) -> Option<u32> {
+        self.read_pid().ok()
     }
 
     pub fn init(&self) -> Result<()> {
         // kill old clash process
-        let _ = dirs::clash_pid_path()
-            .and_then(|path| fs::read(path).map(|p| p.to_vec()).context(""))
-            .and_then(|pid| String::from_utf8_lossy(&pid).parse().context(""))
+        // pid 文件被其他实例持有时，说明核心由其管理，不能杀掉
+        let _ = self
+            .lock_pid_file()
+            .and_then(|_| self.read_pid())
             .map(|pid| {
                 let mut system = System::new();
                 system.refresh_all();
@@ -50,285 +575,1426 @@ impl CoreManager {
                 }
             });
 
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        Self::recover_stale_dns();
+
+        // 首次运行时可能还没有下载任何核心，此时提示用户下载而不是报错
+        if !dirs::has_any_core_installed() {
+            log::warn!(target: "app", "no core installed, skip launching the core");
+            Handle::no_core_installed();
+            Handle::notify(
+                t!("notification.no_core.title"),
+                t!("notification.no_core.body"),
+            );
+            return Ok(());
+        }
+
         tauri::async_runtime::spawn(async {
             // 启动clash
-            log_err!(Self::global().run_core().await);
+            log_err!(Self::global().startup().await);
         });
 
         Ok(())
     }
 
-    /// 检查配置是否正确
-    pub fn check_config(&self) -> Result<()> {
-        let config_path = Config::generate_file(ConfigType::Check)?;
-        let config_path = dirs::path_to_str(&config_path)?;
-
-        let clash_core = { Config::verge().latest().clash_core.clone() };
-        let clash_core = clash_core.unwrap_or(ClashCore::ClashPremium).to_string();
+    /// 确认核心能够运行，架构不符或文件损坏的核心只会陷入启动失败与恢复的循环
+    /// 核心能运行但版本号的格式无法识别时不影响启动
+    fn preflight(clash_core: &ClashCore) -> Result<()> {
+        let binary = manager::find_binary_path(clash_core)?;
+        if let Some(arch) = dirs::incompatible_binary_arch(&binary) {
+            bail!(
+                "the core is built for {arch}, but the system is {}",
+                std::env::consts::ARCH
+            );
+        }
+        match resolve::resolve_binary_version(clash_core, &binary) {
+            Err(err) if err.is::<resolve::VersionUnparsed>() => {
+                log::warn!(target: "app", "core `{clash_core}` runs but {err}, start it anyway");
+                Ok(())
+            }
+            res => res
+                .map(|_| ())
+                .context("the core failed to report its version"),
+        }
+    }
 
-        let app_dir = dirs::app_home_dir()?;
-        let app_dir = dirs::path_to_str(&app_dir)?;
-        log::debug!(target: "app", "check config in `{clash_core}`");
-        let output = Command::new_sidecar(clash_core)?
-            .args(["-t", "-d", app_dir, "-f", config_path])
-            .output()?;
+    /// 应用启动时首次运行核心，连续多次没能稳定运行时改用安全模式
+    /// 只统计启动阶段的失败，运行中的崩溃由 `recover_core` 处理
+    async fn startup(&'static self) -> Result<()> {
+        let failures = read_startup_failures();
+        // 先记为失败，启动过程中应用本身崩溃也会被计入
+        log_err!(write_startup_failures(failures + 1));
 
-        if !output.status.success() {
-            let error = api::parse_check_output(output.stdout.clone());
-            let error = match !error.is_empty() {
-                true => error,
-                false => output.stdout.clone(),
-            };
-            Logger::global().set_log(output.stdout);
-            bail!("{error}");
+        if failures >= SAFE_MODE_THRESHOLD {
+            return self.run_safe_mode(failures).await;
         }
+        // 接管上次运行残留在服务中的核心，之后的启动会通过服务将其停止
+        #[cfg(target_os = "windows")]
+        self.adopt_service_core().await;
+        self.preflight_and_run().await?;
+        // 启动成功后立即清零，之后正常退出应用不会被计为失败
+        log_err!(write_startup_failures(0));
+        self.watch_startup_crash(failures + 1);
+        Ok(())
+    }
+
+    /// 启动后一段时间内核心崩溃时，仍将这次启动计为失败
+    fn watch_startup_crash(&'static self, failures: u32) {
+        let crashes = CoreMetrics::global().snapshot().crashes;
+        tauri::async_runtime::spawn(async move {
+            let deadline = tokio::time::Instant::now() + STARTUP_STABLE_PERIOD;
+            loop {
+                let changed = self.lifecycle_changed.notified();
+                if CoreMetrics::global().snapshot().crashes != crashes {
+                    log::warn!(target: "app", "the core crashed shortly after startup");
+                    log_err!(write_startup_failures(failures));
+                    return;
+                }
+                if tokio::time::timeout_at(deadline, changed).await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
 
+    /// 使用只包含端口和外部控制接口的最小配置启动，并提示用户修复配置
+    /// 最小配置中关闭了 TUN，用户的 TUN 设置保持不变，退出安全模式后照常生效
+    async fn run_safe_mode(&'static self, failures: u32) -> Result<()> {
+        log::warn!(target: "app", "the core failed to start {failures} times in a row, start in safe mode");
+
+        // 与正常启动一样先确认外部控制接口的端口可用
+        let config = {
+            let clash = Config::clash();
+            let mut clash = clash.latest();
+            clash.prepare_external_controller_port()?;
+            safe_mode_config(&clash.0)
+        };
+        let config_path = dirs::safe_mode_config_path()?;
+        help::save_yaml(
+            &config_path,
+            &config,
+            Some("# Clash Nyanpasu Safe Mode Config"),
+        )?;
+        self.safe_mode.store(true, Ordering::SeqCst);
+        if let Err(err) = self.run_core_with(Some(config_path)).await {
+            self.safe_mode.store(false, Ordering::SeqCst);
+            return Err(err);
+        }
+        Handle::safe_mode(failures);
         Ok(())
     }
 
-    /// 启动核心
-    pub async fn run_core(&self) -> Result<()> {
-        #[allow(unused_mut)]
-        let mut should_kill = match self.sidecar.lock().take() {
-            Some(child) => {
-                log::debug!(target: "app", "stop the core by sidecar");
-                let _ = child.kill();
-                true
+    /// 是否以安全模式运行
+    pub fn is_safe_mode(&self) -> bool {
+        self.safe_mode.load(Ordering::SeqCst)
+    }
+
+    /// 用户的配置已成功应用，退出安全模式
+    fn leave_safe_mode(&self) {
+        if self.safe_mode.swap(false, Ordering::SeqCst) {
+            log::info!(target: "app", "the user config is applied, leave safe mode");
+            let failures = read_startup_failures();
+            log_err!(write_startup_failures(0));
+            // 只有全局实例会进入安全模式
+            CoreManager::global().watch_startup_crash(failures);
+        }
+    }
+
+    /// 启动前先检查当前核心，检查失败时通知前端，并尝试使用备用核心启动
+    /// 重新下载核心后更新器会再次启动核心
+    async fn preflight_and_run(&self) -> Result<()> {
+        let (clash_core, observe) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                verge.clash_core.clone().unwrap_or(ClashCore::ClashPremium),
+                verge.enable_observer_mode.unwrap_or(false),
+            )
+        };
+        // 观察模式不启动核心，无需检查
+        if !observe {
+            let core = clash_core.clone();
+            if let Err(err) = tokio::task::spawn_blocking(move || Self::preflight(&core)).await? {
+                log::error!(target: "app", "core `{clash_core}` is broken: {err:#}");
+                Handle::core_broken(CoreBroken {
+                    core: clash_core,
+                    reason: format!("{err:#}"),
+                });
+                self.run_fallback_core().await.ok_or(err)?;
+                return Ok(());
             }
-            None => false,
+        }
+        self.run_core().await?;
+        Ok(())
+    }
+
+    /// 获取核心当前的运行状态和运行方式
+    pub async fn status(&self) -> (CoreState, RunType) {
+        if let Some(state) = self.transient_state() {
+            return (state, self.backend().run_type());
+        }
+
+        if self.idle_stopped.load(Ordering::SeqCst) {
+            return (CoreState::IdleStopped, self.backend().run_type());
+        }
+
+        self.backend().status().await
+    }
+
+    /// 核心为何以当前的方式运行，会实际连接一次服务
+    pub async fn run_type_diagnostics(&self) -> RunTypeDiagnostics {
+        let (enable_service_mode, enable_observer_mode) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                verge.enable_service_mode.unwrap_or(false),
+                verge.enable_observer_mode.unwrap_or(false),
+            )
         };
 
         #[cfg(target_os = "windows")]
-        if *self.use_service_mode.lock() {
-            log::debug!(target: "app", "stop the core by service");
-            log_err!(win_service::stop_core_by_service().await);
-            should_kill = true;
-        }
+        let ipc_error = win_service::check_service()
+            .await
+            .err()
+            .map(|err| format!("{err:#}"));
+        #[cfg(not(target_os = "windows"))]
+        let ipc_error = Some("the service mode is only available on Windows".to_string());
 
-        // 这里得等一会儿
-        if should_kill {
-            sleep(Duration::from_millis(500)).await;
+        RunTypeDiagnostics {
+            chosen: self.status().await.1,
+            enable_service_mode,
+            enable_observer_mode,
+            ipc_connected: ipc_error.is_none(),
+            ipc_error,
         }
+    }
 
-        // 检查端口是否可用
-        Config::clash()
-            .latest()
-            .prepare_external_controller_port()?;
+    /// 查询 TUN 模式是否真正生效，而不仅仅是在配置中开启
+    pub async fn tun_status(&self) -> Result<TunStatus> {
+        let requested = { Config::verge().latest().enable_tun_mode };
+        let requested = requested.unwrap_or(false);
 
-        let config_path = Config::generate_file(ConfigType::Run)?;
+        let configs = api::get_configs().await?;
+        let tun = configs.get("tun");
+        let active = tun
+            .and_then(|tun| tun.get("enable"))
+            .and_then(|enable| enable.as_bool())
+            .unwrap_or(false);
+        let device_name = tun
+            .and_then(|tun| tun.get("device"))
+            .and_then(|device| device.as_str())
+            .filter(|device| !device.is_empty())
+            .map(String::from);
 
-        #[cfg(target_os = "macos")]
-        {
-            let enable_tun = Config::verge().latest().enable_tun_mode;
-            let enable_tun = enable_tun.unwrap_or(false);
+        Ok(TunStatus {
+            requested,
+            active,
+            device_name,
+        })
+    }
 
-            if enable_tun {
-                log::debug!(target: "app", "try to set system dns");
+    /// 正在启动或恢复时返回对应的状态
+    fn transient_state(&self) -> Option<CoreState> {
+        if self.starting.load(Ordering::SeqCst) {
+            Some(CoreState::Starting)
+        } else if self.recovering.load(Ordering::SeqCst) {
+            Some(CoreState::Recovering)
+        } else {
+            None
+        }
+    }
 
-                let tun_device_ip = Config::clash().clone().latest().get_tun_device_ip();
-                // 执行 networksetup -setdnsservers Wi-Fi $tun_device_ip
-                let (mut rx, _) = Command::new("networksetup")
-                    .args(["-setdnsservers", "Wi-Fi", tun_device_ip.as_str()])
-                    .spawn()?;
-                let event = rx.recv().await;
-                log::debug!(target: "app", "{event:?}");
+    /// 等待核心启动或恢复完成，超时返回 `CoreNotReady`
+    async fn wait_until_ready(&self) -> Result<()> {
+        let deadline = Instant::now() + CORE_READY_TIMEOUT;
+        loop {
+            // 先注册通知再检查状态，避免错过检查之后发出的通知
+            let changed = self.lifecycle_changed.notified();
+            let Some(state) = self.transient_state() else {
+                return Ok(());
+            };
+            log::debug!(target: "app", "core is {state:?}, wait for it to be ready");
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if tokio::time::timeout(remaining, changed).await.is_err() {
+                bail!(CoreNotReady(CORE_READY_TIMEOUT));
             }
         }
-        #[cfg(target_os = "windows")]
+    }
+
+    /// 等待核心进入 `target` 状态，超时返回带有当前状态的 `CoreStateTimeout`
+    /// 在启动、停止等生命周期事件与核心进程自行退出时重新检查状态
+    pub async fn wait_for_state(&self, target: CoreState, timeout: Duration) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut exits = backend::subscribe_core_exits();
+        loop {
+            // 先注册通知再检查状态，避免错过检查之后发出的通知
+            let changed = self.lifecycle_changed.notified();
+            exits.borrow_and_update();
+            let (actual, _) = self.status().await;
+            if actual == target {
+                return Ok(());
+            }
+            let woken = tokio::time::timeout_at(deadline, async {
+                tokio::select! {
+                    _ = changed => {}
+                    _ = exits.changed() => {}
+                }
+            })
+            .await;
+            if woken.is_err() {
+                bail!(CoreStateTimeout {
+                    target,
+                    actual,
+                    timeout,
+                });
+            }
+        }
+    }
+
+    /// 服务模式下与服务的连接中断后，核心可能仍由服务托管运行
+    /// 此时重新接管该核心，而不是认为核心已经停止
+    /// 只在启动与崩溃恢复时调用，查询状态不会切换运行后端
+    #[cfg(target_os = "windows")]
+    async fn adopt_service_core(&self) {
+        let enable = { Config::verge().latest().enable_service_mode };
+        let backend = self.backend();
+        if !enable.unwrap_or(false)
+            || matches!(backend.run_type(), RunType::Service | RunType::Observer)
+            || backend.state().await == CoreState::Running
         {
-            // 服务模式
-            let enable = { Config::verge().latest().enable_service_mode };
-            let enable = enable.unwrap_or(false);
+            return;
+        }
+        let service: Arc<dyn CoreBackend> = Arc::new(ServiceBackend);
+        if service.state().await == CoreState::Running {
+            log::info!(target: "app", "adopt the core still running in the service");
+            *self.backend.lock() = service;
+        }
+    }
 
-            *self.use_service_mode.lock() = enable;
+    /// 使用指定的核心执行 `-t` 检查配置
+    /// 规则集很大时检查可能很慢，超过 `config_check_timeout` 后终止检查
+    ///
+    /// 检查使用真实的数据目录，配置中相对路径的 provider 才能正确解析。
+    /// 失败且输出提到 geodata 等数据文件时，可能是与运行中的核心争用文件锁，
+    /// 此时复制数据文件到缓存目录下本次检查独占的临时目录中重试一次
+    fn run_config_check(clash_core: &ClashCore, config_path: &str) -> Result<CheckOutput> {
+        let binary = manager::find_binary_path(clash_core)?;
+        let binary = dirs::path_to_str(&binary)?;
+        let timeout = { Config::verge().latest().config_check_timeout };
+        let timeout = Duration::from_secs(timeout.unwrap_or(30));
 
-            if enable {
-                // 服务模式启动失败就直接运行 sidecar
-                log::debug!(target: "app", "try to run core in service mode");
-                let res = async {
-                    win_service::check_service().await?;
-                    win_service::run_core_by_service(&config_path).await
-                }
-                .await;
-                match res {
-                    Ok(_) => return Ok(()),
-                    Err(err) => {
-                        // 修改这个值，免得stop出错
-                        *self.use_service_mode.lock() = false;
-                        log::error!(target: "app", "{err}");
+        // 在独立的目录中检查，不改动数据目录中的文件
+        let home_dir = dirs::app_home_dir()?;
+        let cache_dir = dirs::app_cache_dir()?;
+        fs::create_dir_all(&cache_dir)?;
+        let check_dir = tempfile::Builder::new()
+            .prefix("check-")
+            .tempdir_in(&cache_dir)?;
+        prepare_check_dir(&home_dir, check_dir.path())?;
+        log::debug!(target: "app", "check config in `{clash_core}` under {:?}", check_dir.path());
+        let data_dir = check_dir
+            .path()
+            .to_str()
+            .context("failed to convert the check dir to str")?;
+        Self::spawn_config_check(
+            binary,
+            data_dir,
+            dirs::path_to_str(&home_dir)?,
+            config_path,
+            timeout,
+        )
+    }
+
+    /// `safe_path` 为数据目录，配置中以绝对路径引用数据目录中的文件时仍能通过检查
+    fn spawn_config_check(
+        binary: &str,
+        data_dir: &str,
+        safe_path: &str,
+        config_path: &str,
+        timeout: Duration,
+    ) -> Result<CheckOutput> {
+        let (mut rx, child) = backend::core_command(binary)
+            .args(["-t", "-d", data_dir, "-f", config_path])
+            .envs(HashMap::from([(
+                "SAFE_PATHS".to_string(),
+                safe_path.to_string(),
+            )]))
+            .spawn()?;
+
+        // 可能在异步任务中调用，所以在单独的线程中收集输出
+        let (tx, done) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut output = CheckOutput::default();
+            while let Some(event) = rx.blocking_recv() {
+                match event {
+                    CommandEvent::Stdout(line) => {
+                        output.stdout.push_str(&line);
+                        output.stdout.push('\n');
+                    }
+                    CommandEvent::Terminated(payload) => {
+                        output.success = payload.code == Some(0);
                     }
+                    _ => {}
                 }
             }
+            let _ = tx.send(output);
+        });
+
+        match done.recv_timeout(timeout) {
+            Ok(output) => Ok(output),
+            Err(_) => {
+                let _ = child.kill();
+                Err(ConfigCheckTimeout(timeout).into())
+            }
         }
+    }
 
-        let app_dir = dirs::app_home_dir()?;
-        let app_dir = dirs::path_to_str(&app_dir)?;
+    /// 使用指定的核心检查配置，不修改任何配置，也不影响正在运行的核心
+    /// 未指定配置路径时检查当前生成的配置，返回解析后的错误信息，检查通过时为空
+    pub fn check_config_for(
+        &self,
+        clash_core: &ClashCore,
+        config_path: Option<PathBuf>,
+    ) -> Result<Vec<String>> {
+        let config_path = match config_path {
+            Some(path) => path,
+            None => Config::generate_file(ConfigType::Check)?,
+        };
+        let output = Self::run_config_check(clash_core, dirs::path_to_str(&config_path)?)?;
+        Ok(api::parse_check_messages(&output.stdout, output.success).errors)
+    }
+
+    /// 检查配置是否正确，通过时返回核心输出的警告
+    pub fn check_config(&self) -> Result<Vec<String>> {
+        let config_path = Config::generate_file(ConfigType::Check)?;
+        let config_path = dirs::path_to_str(&config_path)?;
 
         let clash_core = { Config::verge().latest().clash_core.clone() };
         let clash_core = clash_core.unwrap_or(ClashCore::ClashPremium);
-        let is_clash = matches!(&clash_core, ClashCore::ClashPremium);
+        let output = Self::run_config_check(&clash_core, config_path)?;
+        let messages = api::parse_check_messages(&output.stdout, output.success);
 
-        let config_path = dirs::path_to_str(&config_path)?;
+        if !output.success {
+            Logger::global().set_log(output.stdout);
+            return Err(ConfigCheckFailed(messages.errors).into());
+        }
+
+        for warning in &messages.warnings {
+            log::warn!(target: "app", "config check warning: {warning}");
+        }
+        Ok(messages.warnings)
+    }
 
+    /// 启动核心的参数
+    pub(super) fn core_args<'a>(
+        clash_core: &ClashCore,
+        app_dir: &'a str,
+        config_path: &'a str,
+    ) -> Vec<&'a str> {
         // fix #212
-        let args = match &clash_core {
+        match clash_core {
             ClashCore::Mihomo | ClashCore::MihomoAlpha => {
                 vec!["-m", "-d", app_dir, "-f", config_path]
             }
             ClashCore::ClashRs => vec!["-d", app_dir, "-c", config_path],
             ClashCore::ClashPremium => vec!["-d", app_dir, "-f", config_path],
-        };
-
-        let cmd = Command::new_sidecar(clash_core)?;
-        let (mut rx, cmd_child) = cmd.args(args).spawn()?;
+        }
+    }
 
-        // 将pid写入文件中
-        crate::log_err!((|| {
-            let pid = cmd_child.pid();
-            let path = dirs::clash_pid_path()?;
-            fs::File::create(path)
-                .context("failed to create the pid file")?
-                .write(format!("{pid}").as_bytes())
-                .context("failed to write pid to the file")?;
-            <Result<()>>::Ok(())
-        })());
-
-        let mut sidecar = self.sidecar.lock();
-        *sidecar = Some(cmd_child);
-        drop(sidecar);
+    /// 使用最小配置启动指定的核心，确认其能正常运行后立即停止
+    /// 不影响当前正在运行的核心
+    pub async fn smoke_test_core(&self, clash_core: &ClashCore) -> Result<SmokeTestResult> {
+        log::debug!(target: "app", "smoke test `{clash_core}`");
+        let (mut rx, child, url) = Self::spawn_benchmark_core(clash_core)?;
 
-        tauri::async_runtime::spawn(async move {
-            while let Some(event) = rx.recv().await {
-                match event {
-                    CommandEvent::Stdout(line) => {
-                        if is_clash {
-                            let stdout = api::parse_log(line.clone());
-                            log::info!(target: "app", "[clash]: {stdout}");
-                        } else {
-                            log::info!(target: "app", "[clash]: {line}");
-                        };
-                        Logger::global().set_log(line);
-                    }
-                    CommandEvent::Stderr(err) => {
-                        // let stdout = api::parse_log(err.clone());
-                        log::error!(target: "app", "[clash]: {err}");
-                        Logger::global().set_log(err);
-                    }
-                    CommandEvent::Error(err) => {
-                        log::error!(target: "app", "[clash]: {err}");
-                        Logger::global().set_log(err);
+        let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+        let mut output = Vec::new();
+        let mut ticker = tokio::time::interval(Duration::from_millis(200));
+        let success = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                tokio::select! {
+                    event = rx.recv() => match event {
+                        Some(CommandEvent::Stdout(line))
+                        | Some(CommandEvent::Stderr(line))
+                        | Some(CommandEvent::Error(line)) => output.push(line),
+                        Some(CommandEvent::Terminated(_)) | None => return false,
+                        _ => {}
+                    },
+                    _ = ticker.tick() => {
+                        let ready = client.get(&url).send().await;
+                        if ready.is_ok_and(|res| res.status().is_success()) {
+                            return true;
+                        }
                     }
-                    CommandEvent::Terminated(_) => {
-                        log::info!(target: "app", "clash core terminated");
-                        let _ = CoreManager::global().recover_core();
-                        break;
-                    }
-                    _ => {}
                 }
             }
-        });
+        })
+        .await
+        .unwrap_or(false);
+        let _ = child.kill();
 
-        Ok(())
+        Ok(SmokeTestResult { success, output })
     }
 
-    /// 重启内核
-    pub fn recover_core(&'static self) -> Result<()> {
-        // 服务模式不管
-        #[cfg(target_os = "windows")]
-        if *self.use_service_mode.lock() {
-            return Ok(());
-        }
+    /// 使用最小配置启动核心，返回核心的输出、进程与 `/version` 的地址
+    fn spawn_benchmark_core(
+        clash_core: &ClashCore,
+    ) -> Result<(Receiver<CommandEvent>, CommandChild, String)> {
+        let config_path = Config::generate_file(ConfigType::Benchmark)?;
+        let config = help::read_yaml::<Mapping>(&config_path)?;
+        let controller = config
+            .get("external-controller")
+            .and_then(|val| val.as_str())
+            .context("external-controller is missing in the benchmark config")?
+            .to_string();
 
-        // 清空原来的sidecar值
-        if let Some(sidecar) = self.sidecar.lock().take() {
-            let _ = sidecar.kill();
+        let binary = manager::find_binary_path(clash_core)?;
+        let app_dir = dirs::app_home_dir()?;
+        let args = Self::core_args(
+            clash_core,
+            dirs::path_to_str(&app_dir)?,
+            dirs::path_to_str(&config_path)?,
+        );
+        let (rx, child) = backend::core_command(dirs::path_to_str(&binary)?)
+            .args(args)
+            .spawn()?;
+        Ok((rx, child, format!("http://{controller}/version")))
+    }
+
+    /// 测试核心的启动耗时，重复 `runs` 次取平均值
+    /// 不影响当前正在运行的核心
+    pub async fn benchmark_startup(
+        &self,
+        clash_core: &ClashCore,
+        runs: u32,
+    ) -> Result<StartupBenchmark> {
+        let mut timings = Vec::new();
+        for run in 1..=runs.clamp(1, 10) {
+            log::debug!(target: "app", "benchmark the startup of `{clash_core}`, run {run}");
+            timings.push(Self::measure_startup(clash_core).await?);
+            // 等待上一次启动的核心完全退出
+            sleep(Duration::from_millis(200)).await;
         }
+        Ok(StartupBenchmark::from_runs(timings))
+    }
 
-        tauri::async_runtime::spawn(async move {
-            // 6秒之后再查看服务是否正常 (时间随便搞的)
-            // terminated 可能是切换内核 (切换内核已经有500ms的延迟)
-            sleep(Duration::from_millis(6666)).await;
+    async fn measure_startup(clash_core: &ClashCore) -> Result<StartupTiming> {
+        let started = Instant::now();
+        let (mut rx, child, url) = Self::spawn_benchmark_core(clash_core)?;
+        let spawn = started.elapsed();
+
+        let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+        let mut first_output = None;
+        let mut ticker = tokio::time::interval(Duration::from_millis(20));
+        let ready = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                tokio::select! {
+                    event = rx.recv() => match event {
+                        Some(CommandEvent::Stdout(_)) | Some(CommandEvent::Stderr(_)) => {
+                            first_output.get_or_insert(started.elapsed());
+                        }
+                        Some(CommandEvent::Terminated(_)) | None => return None,
+                        _ => {}
+                    },
+                    _ = ticker.tick() => {
+                        let ready = client.get(&url).send().await;
+                        if ready.is_ok_and(|res| res.status().is_success()) {
+                            return Some(started.elapsed());
+                        }
+                    }
+                }
+            }
+        })
+        .await
+        .ok()
+        .flatten();
+        let _ = child.kill();
 
-            if self.sidecar.lock().is_none() {
-                log::info!(target: "app", "recover clash core");
+        let ready = ready.context("the core did not become ready in 10s")?;
+        Ok(StartupTiming {
+            spawn_ms: spawn.as_millis() as u64,
+            first_output_ms: first_output.map(|elapsed| elapsed.as_millis() as u64),
+            ready_ms: ready.as_millis() as u64,
+        })
+    }
 
-                // 重新启动app
-                if let Err(err) = self.run_core().await {
-                    log::error!(target: "app", "failed to recover clash core");
-                    log::error!(target: "app", "{err}");
+    /// 这些配置项变化后核心必须重启才能生效
+    const RESTART_KEYS: [&'static str; 8] = [
+        "external-controller",
+        "secret",
+        "port",
+        "mixed-port",
+        "socks-port",
+        "redir-port",
+        "tproxy-port",
+        "tun",
+    ];
+
+    fn restart_keys(config: &Mapping) -> Mapping {
+        Self::RESTART_KEYS
+            .iter()
+            .filter_map(|key| Some((Value::from(*key), config.get(*key)?.clone())))
+            .collect()
+    }
+
+    /// 可以通过 `PATCH /configs` 单独修改的配置项
+    const PATCHABLE_KEYS: [&'static str; 9] = [
+        "mode",
+        "log-level",
+        "allow-lan",
+        "bind-address",
+        "ipv6",
+        "sniffing",
+        "tcp-concurrent",
+        "find-process-mode",
+        "interface-name",
+    ];
+
+    /// 保存从运行配置中恢复的外部控制接口密钥，之后的请求都使用该密钥
+    pub fn apply_recovered_secret(&self, secret: String) -> Result<()> {
+        let mut patch = Mapping::new();
+        patch.insert("secret".into(), secret.into());
+        // 不经过草稿，避免提交其他尚未确认的修改
+        let clash = Config::clash();
+        let mut clash = clash.data();
+        clash.patch_config(patch);
+        clash.save_config()
+    }
+
+    /// 记录当前的运行时配置为正在运行的配置
+    fn record_running_config(&self) {
+        let config = { Config::runtime().latest().config.clone() };
+        *self.running_config.lock() = config;
+    }
+
+    /// 新配置是否修改了需要重启才能生效的配置项
+    fn needs_restart(&self, config: &Mapping) -> bool {
+        match self.running_config.lock().as_ref() {
+            Some(running) => Self::restart_keys(running) != Self::restart_keys(config),
+            // 不清楚正在运行的配置时走热重载
+            None => false,
+        }
+    }
 
-                    let _ = self.recover_core();
+    /// 计算新配置相对于正在运行的配置变化的部分
+    /// 有无法单独修改的配置项变化或被删除时返回 None
+    fn patchable_diff(running: &Mapping, config: &Mapping) -> Option<Mapping> {
+        let is_patchable = |key: &Value| {
+            key.as_str()
+                .is_some_and(|key| Self::PATCHABLE_KEYS.contains(&key))
+        };
+        if running
+            .keys()
+            .any(|key| !config.contains_key(key) && !is_patchable(key))
+        {
+            return None;
+        }
+        let mut diff = Mapping::new();
+        for (key, value) in config {
+            if running.get(key) == Some(value) {
+                continue;
+            }
+            if !is_patchable(key) {
+                return None;
+            }
+            diff.insert(key.clone(), value.clone());
+        }
+        Some(diff)
+    }
+
+    /// 取消正在进行的启动
+    pub fn cancel_start(&self) {
+        self.start_cancel.notify_waiters();
+    }
+
+    /// 启动核心，可通过 `cancel_start` 或 `stop_core` 取消
+    /// 启动失败且设置了备用核心时，改用备用核心启动
+    pub async fn run_core(&self) -> Result<CoreAction> {
+        let err = match self.run_core_with(None).await {
+            Ok(action) => return Ok(action),
+            Err(err) => err,
+        };
+        if err.is::<StartCancelled>() {
+            return Err(err);
+        }
+        match self.run_fallback_core().await {
+            Some(action) => Ok(action),
+            None => Err(err),
+        }
+    }
+
+    /// 使用备用核心启动，成功后将其保存为当前核心，可通过 `rollback_core` 切换回去
+    /// 备用核心与当前核心相同或未安装时不尝试，避免在两者间反复切换
+    async fn run_fallback_core(&self) -> Option<CoreAction> {
+        // 有尚未应用的修改时由修改方处理启动失败
+        if Config::verge().has_draft() {
+            return None;
+        }
+        let (clash_core, fallback) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                verge.clash_core.clone().unwrap_or(ClashCore::ClashPremium),
+                verge.fallback_core.clone()?,
+            )
+        };
+        if fallback == clash_core || manager::find_binary_path(&fallback).is_err() {
+            return None;
+        }
+
+        log::warn!(target: "app", "failed to start `{clash_core}`, try the fallback core `{fallback}`");
+        Config::verge().draft().clash_core = Some(fallback.clone());
+        let res = async {
+            Config::generate()?;
+            self.check_config()?;
+            self.run_core_with(None).await
+        }
+        .await;
+        match res {
+            Ok(action) => {
+                self.remember_previous(Config::verge().apply(), Config::runtime().apply());
+                log_err!(Config::verge().latest().save_file());
+                Handle::refresh_verge();
+                Handle::notify(
+                    t!("notification.fallback_core.title"),
+                    t!(
+                        "notification.fallback_core.body",
+                        core = clash_core.to_string(),
+                        fallback = fallback.to_string()
+                    ),
+                );
+                Some(action)
+            }
+            Err(err) => {
+                log::error!(target: "app", "the fallback core `{fallback}` failed to start: {err}");
+                Config::verge().discard();
+                Config::runtime().discard();
+                None
+            }
+        }
+    }
+
+    /// 使用上次写入的配置文件原样重启核心，不重新写入运行时配置
+    /// 与 `recover_core` 不同，不会带上尚未生效的配置修改
+    pub async fn restart_core_preserve_config(&self) -> Result<CoreAction> {
+        let config_path = dirs::app_home_dir()?.join(RUNTIME_CONFIG);
+        if !config_path.exists() {
+            bail!("the runtime config {config_path:?} does not exist");
+        }
+        self.run_core_with(Some(config_path)).await
+    }
+
+    /// `config_path` 为空时根据运行时配置重新生成配置文件
+    async fn run_core_with(&self, config_path: Option<PathBuf>) -> Result<CoreAction> {
+        // 重启时会先停止正在运行的核心
+        let _suppressed = self.suppress_recovery();
+        self.idle_stopped.store(false, Ordering::SeqCst);
+        self.starting.store(true, Ordering::SeqCst);
+        self.lifecycle_changed.notify_waiters();
+        let started = Instant::now();
+        let generated = config_path.is_none();
+        let res = self.run_core_inner(config_path).await;
+        CoreMetrics::global().record_start(started.elapsed(), res.is_ok());
+        if res.is_ok() && generated {
+            self.leave_safe_mode();
+        }
+        self.starting.store(false, Ordering::SeqCst);
+        self.lifecycle_changed.notify_waiters();
+        res
+    }
+
+    async fn run_core_inner(&self, config_path: Option<PathBuf>) -> Result<CoreAction> {
+        let cancelled = self.start_cancel.notified();
+        tokio::select! {
+            res = self.start_core(config_path) => {
+                if res.is_ok() {
+                    // 核心就绪后恢复用户选择的节点
+                    self.schedule_restore_selections();
+                }
+                res
+            }
+            _ = cancelled => {
+                log::warn!(target: "app", "the core start was cancelled");
+                log_err!(self.backend().stop().await);
+                Err(StartCancelled.into())
+            }
+        }
+    }
+
+    async fn start_core(&self, config_path: Option<PathBuf>) -> Result<CoreAction> {
+        let should_kill = match self.backend().stop().await {
+            Ok(stopped) => stopped,
+            Err(err) => {
+                log::error!(target: "app", "{err}");
+                true
+            }
+        };
+
+        // 观察模式下不检查端口，外部核心正占用着这些端口
+        let observe = { Config::verge().latest().enable_observer_mode };
+        let observe = observe.unwrap_or(false);
+
+        // 这里得等一会儿
+        if should_kill {
+            Self::wait_for_release(!observe).await;
+        }
+        let action = match should_kill {
+            true => CoreAction::Restarted,
+            false => CoreAction::Started,
+        };
+
+        if observe {
+            log::info!(target: "app", "observer mode, attach to the external core");
+            *self.running_config.lock() = None;
+            *self.backend.lock() = Arc::new(ObserverBackend);
+            return Ok(action);
+        }
+
+        let config_path = match config_path {
+            // 原样使用配置文件，正在运行的配置项也不变
+            Some(config_path) => config_path,
+            None => {
+                // 检查端口是否可用
+                let substituted = Config::clash()
+                    .latest()
+                    .prepare_external_controller_port()?;
+                // 运行时配置在此之前已经生成，需要同步替换后的端口
+                if let Some(server) = substituted {
+                    if let Some(config) = Config::runtime().latest().config.as_mut() {
+                        config.insert("external-controller".into(), server.into());
+                    }
+                    Handle::refresh_clash();
+                }
+
+                let config_path = Config::generate_file(ConfigType::Run)?;
+                self.record_running_config();
+                config_path
+            }
+        };
+
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        {
+            let enable_tun = Config::verge().latest().enable_tun_mode;
+            // 安全模式的配置中没有开启 TUN
+            let enable_tun = enable_tun.unwrap_or(false) && !self.safe_mode.load(Ordering::SeqCst);
+
+            if enable_tun && self.side_effects {
+                Self::set_system_dns().await?;
+            }
+        }
+
+        let clash_core = { Config::verge().latest().clash_core.clone() };
+        let clash_core = clash_core.unwrap_or(ClashCore::ClashPremium);
+
+        #[cfg(target_os = "windows")]
+        {
+            // 服务模式
+            let enable = { Config::verge().latest().enable_service_mode };
+            let enable = enable.unwrap_or(false);
+
+            if enable {
+                // 服务模式启动失败就直接运行 sidecar
+                log::debug!(target: "app", "try to run core in service mode");
+                let service: Arc<dyn CoreBackend> = Arc::new(ServiceBackend);
+                match service.start(&clash_core, &config_path).await {
+                    Ok(_) => {
+                        *self.backend.lock() = service;
+                        return Ok(action);
+                    }
+                    Err(err) => {
+                        log::error!(target: "app", "{err}");
+                        log::warn!(target: "app", "service mode is unavailable, fallback to run core as child process");
+                        // 避免服务中残留半启动的核心与 sidecar 抢占端口
+                        let _ = win_service::stop_core_by_service().await;
+                        Handle::notice_message(
+                            "set_config::error",
+                            format!("service mode is unavailable, fallback to normal mode: {err}"),
+                        );
+                    }
                 }
             }
+        }
+
+        self.lock_pid_file()?;
+
+        let child = (self.child_backend)();
+        let pid = child.start(&clash_core, &config_path).await?;
+
+        // 将pid写入文件中
+        if let Some(pid) = pid {
+            crate::log_err!(self.write_pid(pid));
+        }
+
+        *self.backend.lock() = child;
+        Ok(action)
+    }
+
+    /// 核心停止后等待系统释放资源，`check_ports` 时还会等待端口被释放
+    async fn wait_for_release(check_ports: bool) {
+        let grace = { Config::verge().latest().core_restart_grace_ms };
+        sleep(Duration::from_millis(grace.unwrap_or(300))).await;
+        if !check_ports {
+            return;
+        }
+
+        let ports = {
+            let clash = Config::clash().latest();
+            [clash.get_external_controller_port(), clash.get_mixed_port()]
+        };
+        let deadline = Instant::now() + PORT_RELEASE_TIMEOUT;
+        loop {
+            let occupied = ports
+                .iter()
+                .filter(|port| !port_scanner::local_port_available(**port))
+                .collect::<Vec<_>>();
+            if occupied.is_empty() {
+                return;
+            }
+            if Instant::now() >= deadline {
+                log::warn!(target: "app", "ports {occupied:?} are still in use after the core stopped");
+                return;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// 记录一次恢复失败，达到建议切换核心的次数时返回失败次数并重新计数
+    fn count_recover_failure(&self, clash_core: &ClashCore) -> Option<u32> {
+        let mut failures = self.recover_failures.lock();
+        let count = failures.entry(clash_core.clone()).or_insert(0);
+        *count += 1;
+        if *count < SUGGEST_SWITCH_CORE_FAILURES {
+            return None;
+        }
+        failures.remove(clash_core)
+    }
+
+    /// 问题可能出在核心本身，建议切换到其他已安装的核心，由用户确认后切换
+    async fn suggest_switch_core(clash_core: ClashCore, failures: u32) {
+        let installed = tokio::task::spawn_blocking(manager::list_installed_cores).await;
+        let mut candidates = installed
+            .unwrap_or_default()
+            .into_iter()
+            .map(|installed| installed.core_type)
+            .filter(|core| *core != clash_core)
+            .collect::<Vec<_>>();
+        candidates.dedup();
+        if candidates.is_empty() {
+            log::debug!(target: "app", "no other core is installed, skip suggesting");
+            return;
+        }
+        log::warn!(target: "app", "`{clash_core}` failed to recover {failures} times, suggest switching to {candidates:?}");
+        Handle::suggest_switch_core(&CoreSwitchSuggestion {
+            core: clash_core,
+            failures,
+            candidates,
         });
+    }
 
-        Ok(())
+    /// 在计划内停止核心的操作期间暂停自动恢复，返回的守卫被丢弃后恢复
+    ///
+    /// 守卫可以嵌套持有，全部释放后才会重新允许恢复
+    pub fn suppress_recovery(&self) -> RecoverySuppressed<'_> {
+        self.recovery_suppressed.fetch_add(1, Ordering::SeqCst);
+        RecoverySuppressed(&self.recovery_suppressed)
     }
 
-    /// 停止核心运行
-    pub fn stop_core(&self) -> Result<()> {
-        #[cfg(target_os = "windows")]
-        if *self.use_service_mode.lock() {
-            log::debug!(target: "app", "stop the core by service");
-            tauri::async_runtime::block_on(async move {
-                log_err!(win_service::stop_core_by_service().await);
-            });
+    pub fn is_recovery_suppressed(&self) -> bool {
+        self.recovery_suppressed.load(Ordering::SeqCst) > 0
+    }
+
+    /// 重启内核
+    pub fn recover_core(&'static self) -> Result<()> {
+        // 服务模式不管
+        if self.backend().run_type() == RunType::Service {
             return Ok(());
         }
 
-        #[cfg(target_os = "macos")]
-        {
-            let enable_tun = Config::verge().latest().enable_tun_mode;
-            let enable_tun = enable_tun.unwrap_or(false);
+        // 计划内的停止，由持有守卫的操作负责之后的启动
+        if self.is_recovery_suppressed() {
+            log::debug!(target: "app", "core exited during a planned operation, skip recovery");
+            return Ok(());
+        }
 
-            if enable_tun {
-                log::debug!(target: "app", "try to set system dns");
+        Handle::notify_core_crashed();
+        CoreMetrics::global().record_crash();
 
-                match Command::new("networksetup")
-                    .args(["-setdnsservers", "Wi-Fi", "Empty"])
-                    .output()
-                {
-                    Ok(_) => return Ok(()),
+        // 已有恢复任务在运行，由其负责重试
+        if self.recovering.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.lifecycle_changed.notify_waiters();
+
+        // 所有重试都在同一个任务中进行，避免递归地生成新任务
+        tauri::async_runtime::spawn(async move {
+            for attempt in 1..=MAX_RECOVER_ATTEMPTS {
+                // 6秒之后再查看服务是否正常 (时间随便搞的)
+                // terminated 可能是切换内核 (切换内核已经有500ms的延迟)
+                sleep(Duration::from_millis(6666)).await;
+
+                #[cfg(target_os = "windows")]
+                self.adopt_service_core().await;
+                if self.backend().state().await == CoreState::Running {
+                    break;
+                }
+                // 等待期间开始了计划内的操作，交由该操作处理
+                if self.is_recovery_suppressed() {
+                    log::debug!(target: "app", "recovery suppressed, stop recovering clash core");
+                    break;
+                }
+                log::info!(target: "app", "recover clash core, attempt {attempt}");
+                let observe = { Config::verge().latest().enable_observer_mode };
+                Self::wait_for_release(!observe.unwrap_or(false)).await;
+
+                // 重新启动app
+                let clash_core = { Config::verge().latest().clash_core.clone() };
+                let clash_core = clash_core.unwrap_or(ClashCore::ClashPremium);
+                let res = self.run_core().await;
+                CoreMetrics::global().record_recovery(res.is_ok());
+                match res {
+                    Ok(_) => {
+                        self.recover_failures.lock().remove(&clash_core);
+                        break;
+                    }
                     Err(err) => {
-                        // 修改这个值，免得stop出错
-                        *self.use_service_mode.lock() = false;
+                        log::error!(target: "app", "failed to recover clash core");
                         log::error!(target: "app", "{err}");
+                        if let Some(failures) = self.count_recover_failure(&clash_core) {
+                            Self::suggest_switch_core(clash_core, failures).await;
+                        }
                     }
                 }
+                if attempt == MAX_RECOVER_ATTEMPTS {
+                    log::error!(target: "app", "give up recovering clash core after {attempt} attempts");
+                }
+            }
+            self.recovering.store(false, Ordering::SeqCst);
+            self.lifecycle_changed.notify_waiters();
+
+            // 在一个通知窗口内没有再次崩溃才算恢复
+            let window = { Config::verge().latest().crash_notify_window };
+            sleep(Duration::from_secs(window.unwrap_or(30))).await;
+            if !self.recovering.load(Ordering::SeqCst)
+                && self.backend().state().await == CoreState::Running
+            {
+                Handle::notify_core_recovered();
+            }
+        });
+
+        Ok(())
+    }
+
+    /// 将系统 DNS 设置为 TUN 设备的地址
+    #[cfg(target_os = "macos")]
+    async fn set_system_dns() -> Result<()> {
+        log::debug!(target: "app", "try to set system dns");
+
+        // 已有保存的设置时说明当前已被覆盖，不能把 TUN 的地址当作原始设置
+        let recovery = dirs::dns_recovery_path()?;
+        if !recovery.exists() {
+            let output = Command::new("networksetup")
+                .args(["-getdnsservers", "Wi-Fi"])
+                .output()?;
+            let servers = parse_dns_servers(&output.stdout);
+            fs::write(&recovery, servers.join("\n"))
+                .context("failed to save the system dns settings")?;
+        }
+
+        let tun_device_ip = Config::clash().clone().latest().get_tun_device_ip();
+        // 执行 networksetup -setdnsservers Wi-Fi $tun_device_ip
+        let (mut rx, _) = Command::new("networksetup")
+            .args(["-setdnsservers", "Wi-Fi", tun_device_ip.as_str()])
+            .spawn()?;
+        let event = rx.recv().await;
+        log::debug!(target: "app", "{event:?}");
+        Ok(())
+    }
+
+    /// 恢复系统 DNS
+    #[cfg(target_os = "macos")]
+    fn restore_system_dns() -> Result<()> {
+        let recovery = dirs::dns_recovery_path()?;
+        let servers = fs::read_to_string(&recovery)
+            .map(|saved| parse_dns_servers(&saved))
+            .unwrap_or_default();
+        // 原本没有设置 DNS 服务器时使用 Empty 清除
+        let servers = match servers.is_empty() {
+            true => vec!["Empty".to_string()],
+            false => servers,
+        };
+        Command::new("networksetup")
+            .args(["-setdnsservers", "Wi-Fi"])
+            .args(servers)
+            .output()?;
+        let _ = fs::remove_file(recovery);
+        Ok(())
+    }
+
+    /// 将系统 DNS 设置为 TUN 设备的地址
+    /// 没有权限修改时只记录错误，不影响核心启动
+    #[cfg(target_os = "linux")]
+    async fn set_system_dns() -> Result<()> {
+        log::debug!(target: "app", "try to set system dns");
+
+        let tun_device_ip = Config::clash().clone().latest().get_tun_device_ip();
+        let device = {
+            Config::runtime()
+                .latest()
+                .config
+                .as_ref()
+                .and_then(|config| config.get("tun"))
+                .and_then(|tun| tun.get("device"))
+                .and_then(|device| device.as_str())
+                .filter(|device| !device.is_empty())
+                .map(String::from)
+        };
+        if let Err(err) = crate::core::linux_dns::set_system_dns(&tun_device_ip, device) {
+            log::error!(target: "app", "failed to set system dns: {err:?}");
+        }
+        Ok(())
+    }
+
+    /// 恢复系统 DNS
+    #[cfg(target_os = "linux")]
+    fn restore_system_dns() -> Result<()> {
+        crate::core::linux_dns::restore_system_dns()
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    fn restore_system_dns() -> Result<()> {
+        Ok(())
+    }
+
+    /// 上次运行崩溃时没能恢复系统 DNS，启动时使用保存的设置恢复
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    fn recover_stale_dns() {
+        let exists = dirs::dns_recovery_path().is_ok_and(|path| path.exists());
+        if exists {
+            log::warn!(target: "app", "found a stale dns override, restore the system dns");
+            log_err!(Self::restore_system_dns());
+        }
+    }
+
+    /// 停止核心运行，只用于退出应用等不在异步运行时中的同步调用
+    pub fn stop_core(&self) -> Result<CoreAction> {
+        tauri::async_runtime::block_on(self.stop_core_async())
+    }
+
+    /// 停止核心运行，在异步运行时中调用
+    pub async fn stop_core_async(&self) -> Result<CoreAction> {
+        let enable_tun = { Config::verge().latest().enable_tun_mode };
+        self.stop_core_with(enable_tun.unwrap_or(false), Self::restore_system_dns)
+            .await
+    }
+
+    /// 长时间没有流量时停止核心，之后由 [`Self::wake_from_idle`] 重新启动
+    pub async fn stop_for_idle(&self) -> Result<()> {
+        let _guard = self.op_lock.lock().await;
+        let backend = self.backend();
+        if backend.run_type() == RunType::Observer || backend.state().await != CoreState::Running {
+            return Ok(());
+        }
+        log::info!(target: "app", "no traffic for a while, stop the core to save power");
+        // 先撤下系统代理，避免流量指向已停止的核心，TUN 的 DNS 在停止时恢复
+        log_err!(crate::core::sysopt::Sysopt::global().reset_sysproxy());
+        if let Err(err) = self.stop_core_async().await {
+            log_err!(crate::core::sysopt::Sysopt::global().init_sysproxy());
+            return Err(err);
+        }
+        self.idle_stopped.store(true, Ordering::SeqCst);
+        self.lifecycle_changed.notify_waiters();
+        Handle::notify(
+            t!("notification.idle_stopped.title"),
+            t!("notification.idle_stopped.body"),
+        );
+        Ok(())
+    }
+
+    /// 核心因空闲被停止时重新启动，用于响应用户操作
+    pub fn wake_from_idle(&'static self) {
+        if !self.idle_stopped.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        tauri::async_runtime::spawn(async move {
+            log::info!(target: "app", "wake the core from idle");
+            log_err!(self.run_core().await);
+            // 未开启系统代理时不会做任何修改
+            log_err!(crate::core::sysopt::Sysopt::global().init_sysproxy());
+        });
+    }
+
+    /// 核心是否因空闲被停止，此时系统代理已被撤下
+    pub fn is_idle_stopped(&self) -> bool {
+        self.idle_stopped.load(Ordering::SeqCst)
+    }
+
+    /// 切换 TUN 模式，调用前需先更新 verge 中的 `enable_tun_mode`
+    /// 核心支持时通过外部控制接口热切换，否则重启核心
+    pub async fn set_tun_mode(&self, enabled: bool) -> Result<()> {
+        let clash_core = { Config::verge().latest().clash_core.clone() };
+        let clash_core = clash_core.unwrap_or(ClashCore::ClashPremium);
+
+        Config::generate()?;
+
+        if !clash_core.supports_tun_hot_toggle() {
+            log::debug!(target: "app", "`{clash_core}` can not toggle tun at runtime, restart the core");
+            self.run_core().await?;
+            return Ok(());
+        }
+
+        let mut tun = Mapping::new();
+        tun.insert("enable".into(), enabled.into());
+        let mut patch = Mapping::new();
+        patch.insert("tun".into(), tun.into());
+        api::patch_configs(&patch).await?;
+
+        // 核心可能因权限不足等原因未能应用，此时回退到重启
+        match self.tun_status().await {
+            Ok(status) if status.active == enabled => {}
+            _ => {
+                log::warn!(target: "app", "failed to toggle tun at runtime, restart the core");
+                self.run_core().await?;
+                return Ok(());
+            }
+        }
+        // 只有 TUN 被修改，其他配置项仍是原来的
+        let tun = {
+            Config::runtime()
+                .latest()
+                .config
+                .as_ref()
+                .and_then(|c| c.get("tun").cloned())
+        };
+        if let (Some(running), Some(tun)) = (self.running_config.lock().as_mut(), tun) {
+            running.insert("tun".into(), tun);
+        }
+
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        if enabled {
+            Self::set_system_dns().await?;
+        } else if let Err(err) = Self::restore_system_dns() {
+            log::error!(target: "app", "failed to reset system dns: {err}");
+        }
+
+        Ok(())
+    }
+
+    /// `restore_dns` 用于在 TUN 模式下恢复系统 DNS，只在 macOS 和 Linux 上有实际操作
+    /// 恢复 DNS 只是尽力而为，无论成功与否都要停止核心
+    async fn stop_core_with(
+        &self,
+        enable_tun: bool,
+        restore_dns: impl FnOnce() -> Result<()>,
+    ) -> Result<CoreAction> {
+        // 守卫只覆盖停止的过程，之后才到达的退出事件由后端按进程识别
+        let _suppressed = self.suppress_recovery();
+        self.idle_stopped.store(false, Ordering::SeqCst);
+        self.cancel_start();
+
+        if enable_tun {
+            log::debug!(target: "app", "try to reset system dns");
+            if let Err(err) = restore_dns() {
+                log::error!(target: "app", "failed to reset system dns: {err}");
+            }
+        }
+        let action = match self.backend().stop().await {
+            Ok(true) => {
+                CoreMetrics::global().record_stop();
+                CoreAction::Stopped
+            }
+            Ok(false) => CoreAction::WasNotRunning,
+            // 核心可能仍在运行，保留 pid 文件的锁
+            Err(err) => {
+                self.lifecycle_changed.notify_waiters();
+                return Err(err.context("failed to stop the core"));
+            }
+        };
+        // 释放 pid 文件的锁
+        self.pid_file.lock().take();
+        self.lifecycle_changed.notify_waiters();
+        Ok(action)
+    }
+
+    /// 将以子进程运行的核心迁移到服务中运行，并开启服务模式
+    /// 两个核心无法同时监听相同的端口，因此先确认服务可用并生成好配置，再停止子进程并立即由服务启动，尽量缩短断开的时间
+    /// 服务启动失败时重新以子进程运行核心
+    #[cfg(target_os = "windows")]
+    pub async fn migrate_to_service(&self) -> Result<()> {
+        const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+        let _guard = self.op_lock.lock().await;
+        let _suppressed = self.suppress_recovery();
+        let child = self.backend();
+        if child.run_type() != RunType::Normal || child.state().await != CoreState::Running {
+            bail!("the core is not running as a child process");
+        }
+        win_service::check_service()
+            .await
+            .context("the service is unavailable")?;
+
+        let config_path = Config::generate_file(ConfigType::Run)?;
+        let clash_core = { Config::verge().latest().clash_core.clone() };
+        let clash_core = clash_core.unwrap_or(ClashCore::ClashPremium);
+
+        log::info!(target: "app", "migrate the core to the service");
+        child.stop().await?;
+        self.pid_file.lock().take();
+        Self::wait_for_release(true).await;
+
+        let service: Arc<dyn CoreBackend> = Arc::new(ServiceBackend);
+        let started = async {
+            service.start(&clash_core, &config_path).await?;
+            tokio::time::timeout(READY_TIMEOUT, async {
+                while api::get_version().await.is_err() {
+                    sleep(Duration::from_millis(100)).await;
+                }
+            })
+            .await
+            .context("the core started by the service is not ready")
+        }
+        .await;
+
+        if let Err(err) = started {
+            log::error!(target: "app", "failed to migrate the core to the service: {err:?}");
+            let _ = win_service::stop_core_by_service().await;
+            Self::wait_for_release(true).await;
+            self.lock_pid_file()?;
+            if let Some(pid) = child.start(&clash_core, &config_path).await? {
+                log_err!(self.write_pid(pid));
             }
+            self.lifecycle_changed.notify_waiters();
+            return Err(err.context("the core keeps running as a child process"));
         }
-        let mut sidecar = self.sidecar.lock();
-        if let Some(child) = sidecar.take() {
-            log::debug!(target: "app", "stop the core by sidecar");
-            let _ = child.kill();
+
+        *self.backend.lock() = service;
+        {
+            let verge = Config::verge();
+            let mut verge = verge.data();
+            verge.enable_service_mode = Some(true);
+            log_err!(verge.save_file());
         }
+        Handle::refresh_verge();
+        self.lifecycle_changed.notify_waiters();
         Ok(())
     }
 
-    /// 切换核心
-    pub async fn change_core(&self, clash_core: Option<ClashCore>) -> Result<()> {
+    /// 切换核心，`force` 为真时忽略不支持的功能继续切换
+    /// 成功时返回检查配置时核心输出的警告
+    pub async fn change_core(
+        &self,
+        clash_core: Option<ClashCore>,
+        force: bool,
+    ) -> Result<Vec<String>, ChangeCoreError> {
         let clash_core = clash_core.ok_or(anyhow::anyhow!("clash core is null"))?;
+        let _guard = self.op_lock.lock().await;
+        let _suppressed = self.suppress_recovery();
 
         // if &clash_core != "clash" && &clash_core != "clash-meta" && &clash_core != "clash-rs" {
         //     bail!("invalid clash core name \"{clash_core}\"");
         // }
 
         log::debug!(target: "app", "change core to `{clash_core}`");
+        // 提前检查架构，避免启动时只得到难以理解的系统错误
+        if let Some(binary) = manager::find_binary_path(&clash_core)
+            .ok()
+            .and_then(|path| dirs::incompatible_binary_arch(&path))
+        {
+            return Err(ChangeCoreError::ArchMismatch {
+                binary,
+                system: std::env::consts::ARCH.to_string(),
+            });
+        }
+        let progress = |phase| {
+            Handle::change_core_progress(&ChangeCoreProgress {
+                core: clash_core.clone(),
+                phase,
+            })
+        };
 
-        Config::verge().draft().clash_core = Some(clash_core);
+        progress(ChangeCorePhase::Draft);
+        Config::verge().draft().clash_core = Some(clash_core.clone());
 
         // 更新配置
+        progress(ChangeCorePhase::Generate);
         Config::generate()?;
 
-        self.check_config()?;
+        if !force {
+            let features = {
+                let runtime = Config::runtime();
+                let runtime = runtime.latest();
+                runtime
+                    .config
+                    .as_ref()
+                    .map(|config| capability::unsupported_features(&clash_core, config))
+                    .unwrap_or_default()
+            };
+            if !features.is_empty() {
+                Config::verge().discard();
+                Config::runtime().discard();
+                return Err(ChangeCoreError::UnsupportedFeatures { features });
+            }
+        }
+
+        progress(ChangeCorePhase::Check);
+        let warnings = match self.check_config() {
+            Ok(warnings) => warnings,
+            Err(err) => {
+                Config::verge().discard();
+                Config::runtime().discard();
+                return Err(err.into());
+            }
+        };
 
         // 清掉旧日志
+        progress(ChangeCorePhase::ClearLogs);
         Logger::global().clear_log();
 
-        match self.run_core().await {
+        progress(ChangeCorePhase::Start);
+        match self.run_core_with(None).await {
             Ok(_) => {
-                Config::verge().apply();
-                Config::runtime().apply();
+                self.remember_previous(Config::verge().apply(), Config::runtime().apply());
+                log_err!(Config::verge().latest().save_file());
+                // 超时只记录日志，核心已经启动，切换仍算成功
+                self.warmup().await;
+                progress(ChangeCorePhase::Ready);
+                Ok(warnings)
+            }
+            Err(err) => {
+                Config::verge().discard();
+                Config::runtime().discard();
+                Err(err.into())
+            }
+        }
+    }
+
+    /// 记录被替换掉的核心与运行时配置
+    fn remember_previous(&self, verge: Option<IVerge>, runtime: Option<IRuntime>) {
+        if let (Some(verge), Some(runtime)) = (verge, runtime) {
+            let clash_core = verge.clash_core.unwrap_or(ClashCore::ClashPremium);
+            *self.previous.lock() = Some((clash_core, runtime));
+        }
+    }
+
+    /// 回滚到上一次切换前的核心与配置，回滚后可以再次回滚以撤销
+    pub async fn rollback_core(&self) -> Result<()> {
+        let _guard = self.op_lock.lock().await;
+        let _suppressed = self.suppress_recovery();
+        let (clash_core, runtime) = self
+            .previous
+            .lock()
+            .clone()
+            .context("there is no previous core to roll back to")?;
+
+        log::info!(target: "app", "roll back to `{clash_core}`");
+        Config::verge().draft().clash_core = Some(clash_core);
+        *Config::runtime().draft() = runtime;
+
+        // 清掉旧日志
+        Logger::global().clear_log();
+
+        match self.run_core_with(None).await {
+            Ok(_) => {
+                self.remember_previous(Config::verge().apply(), Config::runtime().apply());
                 log_err!(Config::verge().latest().save_file());
                 Ok(())
             }
@@ -340,21 +2006,194 @@ impl CoreManager {
         }
     }
 
+    /// 等待核心的外部控制接口就绪，避免刚启动时推送配置失败
+    async fn warmup(&self) {
+        let timeout = { Config::verge().latest().core_warmup_timeout };
+        let timeout = Duration::from_millis(timeout.unwrap_or(3000));
+        if timeout.is_zero() {
+            return;
+        }
+
+        let start = Instant::now();
+        let ready = tokio::time::timeout(timeout, async {
+            while api::get_version().await.is_err() {
+                sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await;
+        match ready {
+            Ok(_) => {
+                log::debug!(target: "app", "core warmup took {:?}", start.elapsed());
+            }
+            Err(_) => {
+                log::warn!(target: "app", "core is not ready after {timeout:?}, push the config anyway");
+            }
+        }
+    }
+
+    /// 开启 `partial_config_reload` 时只修改变化的配置项，返回是否已完成更新
+    async fn try_patch_config(&self) -> bool {
+        let (enabled, clash_core) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            (
+                verge.partial_config_reload.unwrap_or(false),
+                verge.clash_core.clone().unwrap_or(ClashCore::ClashPremium),
+            )
+        };
+        if !enabled || !clash_core.supports_config_patch() {
+            return false;
+        }
+
+        let diff = {
+            let running = self.running_config.lock();
+            let runtime = Config::runtime();
+            let runtime = runtime.latest();
+            match (running.as_ref(), runtime.config.as_ref()) {
+                (Some(running), Some(config)) => Self::patchable_diff(running, config),
+                _ => None,
+            }
+        };
+        let Some(diff) = diff else {
+            log::debug!(target: "app", "the changes can not be patched, reload the whole config");
+            return false;
+        };
+        if diff.is_empty() {
+            log::debug!(target: "app", "the config is not changed, skip reloading");
+            return true;
+        }
+        match api::patch_configs(&diff).await {
+            Ok(_) => {
+                // 核心可能忽略不支持的配置项，确认核心报告的配置确实已修改
+                let applied = api::get_configs().await.is_ok_and(|live| {
+                    diff.iter().all(|(key, value)| {
+                        live.get(key)
+                            .map_or(true, |live| same_config_value(value, live))
+                    })
+                });
+                if !applied {
+                    log::warn!(target: "app", "the patched config is not applied, reload the whole config");
+                    return false;
+                }
+                log::debug!(target: "app", "patched {} config keys", diff.len());
+                self.record_running_config();
+                true
+            }
+            Err(err) => {
+                log::warn!(target: "app", "failed to patch the config, reload the whole config: {err}");
+                false
+            }
+        }
+    }
+
     /// 更新proxies那些
     /// 如果涉及端口和外部控制则需要重启
-    pub async fn update_config(&self) -> Result<()> {
+    /// 因无法连接核心而失败时，在核心恢复运行后自动重试，见 `defer_config_update`
+    /// 确认生效后发送 `config://applied` 事件
+    pub async fn update_config(&self) -> Result<ConfigApplyStatus> {
+        let err = match self.timed_update_config().await {
+            Ok(status) => {
+                Handle::config_applied(status);
+                return Ok(status);
+            }
+            Err(err) => err,
+        };
+        let defer = { Config::verge().latest().defer_config_update };
+        if !defer.unwrap_or(true) || !is_transient_error(&err) {
+            return Err(err);
+        }
+
+        log::warn!(target: "app", "the core is unreachable, update the config when it is running: {err}");
+        Handle::notify(
+            t!("notification.config_pending.title"),
+            t!("notification.config_pending.body"),
+        );
+        // 已有重试任务时由其推送最新的配置
+        if self.update_pending.swap(true, Ordering::SeqCst) {
+            return Ok(ConfigApplyStatus::Deferred);
+        }
+        tauri::async_runtime::spawn(async {
+            let manager = CoreManager::global();
+            let res = match manager
+                .wait_for_state(CoreState::Running, DEFERRED_UPDATE_TIMEOUT)
+                .await
+            {
+                Ok(_) => manager.timed_update_config().await,
+                Err(err) => Err(err),
+            };
+            manager.update_pending.store(false, Ordering::SeqCst);
+            match res {
+                Ok(status) => {
+                    Handle::config_applied(status);
+                    log::info!(target: "app", "the pending config has been applied");
+                    Handle::notify(
+                        t!("notification.config_pending.title"),
+                        t!("notification.config_pending.applied"),
+                    );
+                }
+                Err(err) => {
+                    log::error!(target: "app", "failed to apply the pending config: {err}");
+                    Handle::notify(
+                        t!("notification.config_pending.title"),
+                        t!(
+                            "notification.config_pending.failed",
+                            error = err.to_string()
+                        ),
+                    );
+                }
+            }
+        });
+        Ok(ConfigApplyStatus::Deferred)
+    }
+
+    /// 记录推送配置的耗时与结果
+    async fn timed_update_config(&self) -> Result<ConfigApplyStatus> {
+        let started = Instant::now();
+        let res = self.update_config_inner().await;
+        CoreMetrics::global().record_config_push(started.elapsed(), res.is_ok());
+        if res.is_ok() {
+            self.leave_safe_mode();
+        }
+        res
+    }
+
+    async fn update_config_inner(&self) -> Result<ConfigApplyStatus> {
         log::debug!(target: "app", "try to update clash config");
 
+        // 核心启动或恢复期间推送配置必然失败，等待其完成
+        self.wait_until_ready().await?;
+
         // 更新配置
         Config::generate()?;
 
         // 检查配置是否正常
         self.check_config()?;
 
+        // 端口、外部控制或 TUN 变化时热重载不会生效，直接重启核心
+        let needs_restart = {
+            let runtime = Config::runtime();
+            let runtime = runtime.latest();
+            runtime
+                .config
+                .as_ref()
+                .is_some_and(|config| self.needs_restart(config))
+        };
+        if needs_restart {
+            log::info!(target: "app", "ports or controller changed, restart the core");
+            self.run_core().await?;
+            return Ok(ConfigApplyStatus::Applied);
+        }
+
         // 更新运行时配置
         let path = Config::generate_file(ConfigType::Run)?;
         let path = dirs::path_to_str(&path)?;
 
+        self.warmup().await;
+
+        if self.try_patch_config().await {
+            return Ok(ConfigApplyStatus::Applied);
+        }
+
         // 发送请求 发送5次
         for i in 0..5 {
             match api::put_configs(path).await {
@@ -369,7 +2208,516 @@ impl CoreManager {
             }
             sleep(Duration::from_millis(250)).await;
         }
+        let previous = self.running_config.lock().clone();
+        self.record_running_config();
 
-        Ok(())
+        // PUT 返回时核心可能还没有完成加载
+        let status = Self::confirm_config(previous).await;
+        if status == ConfigApplyStatus::Unconfirmed {
+            log::warn!(target: "app", "the config is pushed but not confirmed in {CONFIG_CONFIRM_TIMEOUT:?}");
+        }
+
+        // 重新加载配置后分组的选择可能被重置
+        self.schedule_restore_selections();
+
+        Ok(status)
+    }
+
+    /// 在核心就绪后恢复用户选择的节点，已有等待中的恢复时不重复安排
+    fn schedule_restore_selections(&self) {
+        if !self.side_effects || self.restore_pending.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tauri::async_runtime::spawn(async {
+            let manager = CoreManager::global();
+            manager.warmup().await;
+            manager.restore_pending.store(false, Ordering::SeqCst);
+            log_err!(ProxiesGuard::global().restore_selections().await);
+        });
+    }
+
+    /// 轮询外部控制接口，直到核心报告的配置与推送的一致
+    /// `previous` 为推送前正在运行的配置，没有可以用来确认的变化时不算生效
+    async fn confirm_config(previous: Option<Mapping>) -> ConfigApplyStatus {
+        let expected = { Config::runtime().latest().config.clone() };
+        let (Some(previous), Some(expected)) = (previous, expected) else {
+            return ConfigApplyStatus::Unconfirmed;
+        };
+        let confirmed = tokio::time::timeout(CONFIG_CONFIRM_TIMEOUT, async {
+            loop {
+                if let (Ok(live), Ok(proxies)) =
+                    (api::get_configs().await, api::get_proxies().await)
+                {
+                    match is_config_live(&previous, &expected, &live, &proxies.proxies) {
+                        Some(true) => return ConfigApplyStatus::Applied,
+                        Some(false) => {}
+                        None => return ConfigApplyStatus::Unconfirmed,
+                    }
+                }
+                sleep(Duration::from_millis(200)).await;
+            }
+        })
+        .await;
+        confirmed.unwrap_or(ConfigApplyStatus::Unconfirmed)
+    }
+}
+
+/// 安全模式的最小配置，只保留端口与外部控制接口，所有流量直连
+fn safe_mode_config(clash: &Mapping) -> Mapping {
+    const KEEP_KEYS: [&str; 6] = [
+        "mixed-port",
+        "port",
+        "socks-port",
+        "allow-lan",
+        "external-controller",
+        "secret",
+    ];
+
+    let mut config = Mapping::new();
+    for key in KEEP_KEYS {
+        if let Some(value) = clash.get(key) {
+            config.insert(key.into(), value.clone());
+        }
+    }
+    config.insert("mode".into(), "rule".into());
+    config.insert("log-level".into(), "info".into());
+    let mut tun = Mapping::new();
+    tun.insert("enable".into(), false.into());
+    config.insert("tun".into(), tun.into());
+    config.insert("proxies".into(), Value::Sequence(vec![]));
+    config.insert("proxy-groups".into(), Value::Sequence(vec![]));
+    config.insert("rules".into(), vec![Value::from("MATCH,DIRECT")].into());
+    config
+}
+
+fn read_startup_failures() -> u32 {
+    dirs::startup_failures_path()
+        .and_then(|path| Ok(fs::read_to_string(path)?))
+        .ok()
+        .and_then(|failures| failures.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn write_startup_failures(failures: u32) -> Result<()> {
+    fs::write(dirs::startup_failures_path()?, failures.to_string())
+        .context("failed to save the startup failures")
+}
+
+/// 核心报告的配置项与写入的是否相同，核心报告的字符串大小写可能不同
+fn same_config_value(expected: &Value, live: &Value) -> bool {
+    match (expected, live) {
+        (Value::String(expected), Value::String(live)) => expected.eq_ignore_ascii_case(live),
+        (expected, live) => expected == live,
+    }
+}
+
+/// 核心报告的配置是否与推送的一致
+/// 只比较相对于之前运行的配置发生变化、且核心会报告的基础配置项，以及增删的分组
+/// 没有可比较的变化时返回 None，此时无法区分新旧配置
+fn is_config_live<T>(
+    previous: &Mapping,
+    expected: &Mapping,
+    live: &Mapping,
+    proxies: &IndexMap<String, T>,
+) -> Option<bool> {
+    const KEYS: [&str; 4] = ["mode", "log-level", "allow-lan", "ipv6"];
+
+    fn group_names(config: &Mapping) -> HashSet<&str> {
+        config
+            .get("proxy-groups")
+            .and_then(|groups| groups.as_sequence())
+            .into_iter()
+            .flatten()
+            .filter_map(|group| group.get("name").and_then(|name| name.as_str()))
+            .collect()
+    }
+
+    let changed = KEYS
+        .iter()
+        .filter_map(|key| {
+            let expected = expected.get(*key)?;
+            if previous
+                .get(*key)
+                .is_some_and(|previous| same_config_value(previous, expected))
+            {
+                return None;
+            }
+            // 核心没有报告的配置项无法比较
+            live.get(*key).map(|live| same_config_value(expected, live))
+        })
+        .collect::<Vec<_>>();
+    let (expected_groups, previous_groups) = (group_names(expected), group_names(previous));
+    let added = expected_groups
+        .difference(&previous_groups)
+        .collect::<Vec<_>>();
+    let removed = previous_groups
+        .difference(&expected_groups)
+        .collect::<Vec<_>>();
+    if changed.is_empty() && added.is_empty() && removed.is_empty() {
+        return None;
+    }
+    Some(
+        changed.into_iter().all(|same| same)
+            && added.iter().all(|name| proxies.contains_key(**name))
+            && removed.iter().all(|name| !proxies.contains_key(**name)),
+    )
+}
+
+/// 是否为连接核心失败等可以稍后重试的错误，配置无效等错误重试也不会成功
+fn is_transient_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|err| err.is_connect() || err.is_timeout() || err.is_request())
+            || cause.downcast_ref::<CoreStateTimeout>().is_some()
+    })
+}
+
+/// 解析 `networksetup -getdnsservers` 的输出，没有设置时为空
+#[cfg(any(target_os = "macos", test))]
+fn parse_dns_servers(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.parse::<std::net::IpAddr>().is_ok())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clash::backend::MockBackend;
+
+    #[test]
+    fn test_stop_core_stops_backend() {
+        let backend = Arc::new(MockBackend::running());
+        let manager = CoreManager::with_backend(backend.clone());
+
+        let action =
+            tauri::async_runtime::block_on(manager.stop_core_with(false, || Ok(()))).unwrap();
+        assert_eq!(action, CoreAction::Stopped);
+        assert!(!backend.is_running());
+
+        let action =
+            tauri::async_runtime::block_on(manager.stop_core_with(false, || Ok(()))).unwrap();
+        assert_eq!(action, CoreAction::WasNotRunning);
+
+        let (state, run_type) = tauri::async_runtime::block_on(manager.status());
+        assert_eq!(state, CoreState::Stopped);
+        assert_eq!(run_type, RunType::Normal);
+
+        // 停止失败时不能报告为已停止
+        let manager = CoreManager::with_backend(Arc::new(MockBackend::unstoppable()));
+        assert!(tauri::async_runtime::block_on(manager.stop_core_with(false, || Ok(()))).is_err());
+        let (state, _) = tauri::async_runtime::block_on(manager.status());
+        assert_eq!(state, CoreState::Running);
+    }
+
+    #[test]
+    fn test_safe_mode_config() {
+        let clash: Mapping = serde_yaml::from_str(
+            "mixed-port: 7890\nexternal-controller: 127.0.0.1:9090\nsecret: abc\ntun: {enable: true}\nproxies: [{name: a}]",
+        )
+        .unwrap();
+        let config = safe_mode_config(&clash);
+        assert_eq!(config.get("mixed-port"), clash.get("mixed-port"));
+        assert_eq!(
+            config.get("external-controller"),
+            clash.get("external-controller")
+        );
+        assert_eq!(config.get("secret"), clash.get("secret"));
+        assert_eq!(
+            config.get("tun").and_then(|tun| tun.get("enable")),
+            Some(&Value::Bool(false))
+        );
+        assert_eq!(
+            config
+                .get("proxies")
+                .and_then(|proxies| proxies.as_sequence()),
+            Some(&vec![])
+        );
+        assert_eq!(
+            config.get("rules"),
+            Some(&serde_yaml::from_str::<Value>("['MATCH,DIRECT']").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_is_config_live() {
+        let previous: Mapping =
+            serde_yaml::from_str("mode: global\nlog-level: info\nproxy-groups: [{name: Proxy}]")
+                .unwrap();
+        let expected: Mapping = serde_yaml::from_str(
+            "mode: rule\nlog-level: info\nproxy-groups: [{name: Proxy}, {name: Auto}]",
+        )
+        .unwrap();
+        let proxies: IndexMap<String, ()> =
+            IndexMap::from([("Proxy".to_string(), ()), ("Auto".to_string(), ())]);
+
+        let live: Mapping = serde_yaml::from_str("mode: Rule\nlog-level: info").unwrap();
+        assert_eq!(
+            is_config_live(&previous, &expected, &live, &proxies),
+            Some(true)
+        );
+
+        // 还是旧的配置
+        let live: Mapping = serde_yaml::from_str("mode: global\nlog-level: info").unwrap();
+        assert_eq!(
+            is_config_live(&previous, &expected, &live, &proxies),
+            Some(false)
+        );
+
+        let live: Mapping = serde_yaml::from_str("mode: rule\nlog-level: info").unwrap();
+        let old_proxies: IndexMap<String, ()> = IndexMap::from([("Proxy".to_string(), ())]);
+        assert_eq!(
+            is_config_live(&previous, &expected, &live, &old_proxies),
+            Some(false)
+        );
+
+        // 只修改了规则等无法从外部控制接口比较的内容
+        assert_eq!(is_config_live(&expected, &expected, &live, &proxies), None);
+    }
+
+    #[test]
+    fn test_suppress_recovery() {
+        let manager: &'static CoreManager = Box::leak(Box::new(CoreManager::new_for_test()));
+        assert!(!manager.is_recovery_suppressed());
+
+        let outer = manager.suppress_recovery();
+        let inner = manager.suppress_recovery();
+        drop(inner);
+        assert!(manager.is_recovery_suppressed());
+        // 被暂停时直接跳过，不会启动恢复任务
+        manager.recover_core().unwrap();
+        assert!(!manager.recovering.load(Ordering::SeqCst));
+
+        drop(outer);
+        assert!(!manager.is_recovery_suppressed());
+    }
+
+    #[test]
+    fn test_run_and_stop_core() {
+        let manager = CoreManager::new_for_test();
+        let config_path = std::env::temp_dir().join("nyanpasu-test-config.yaml");
+        fs::write(&config_path, "mixed-port: 7890").unwrap();
+
+        let action =
+            tauri::async_runtime::block_on(manager.run_core_with(Some(config_path.clone())))
+                .unwrap();
+        assert_eq!(action, CoreAction::Started);
+        let (state, _) = tauri::async_runtime::block_on(manager.status());
+        assert_eq!(state, CoreState::Running);
+
+        let action =
+            tauri::async_runtime::block_on(manager.run_core_with(Some(config_path))).unwrap();
+        assert_eq!(action, CoreAction::Restarted);
+
+        let action =
+            tauri::async_runtime::block_on(manager.stop_core_with(false, || Ok(()))).unwrap();
+        assert_eq!(action, CoreAction::Stopped);
+        let (state, _) = tauri::async_runtime::block_on(manager.status());
+        assert_eq!(state, CoreState::Stopped);
+    }
+
+    #[test]
+    fn test_startup_benchmark_average() {
+        let timing = |spawn_ms, first_output_ms, ready_ms| StartupTiming {
+            spawn_ms,
+            first_output_ms,
+            ready_ms,
+        };
+        let benchmark =
+            StartupBenchmark::from_runs(vec![timing(10, Some(40), 100), timing(20, None, 200)]);
+        assert_eq!(benchmark.average.spawn_ms, 15);
+        assert_eq!(benchmark.average.first_output_ms, Some(40));
+        assert_eq!(benchmark.average.ready_ms, 150);
+    }
+
+    #[test]
+    fn test_wait_for_state() {
+        let manager = Arc::new(CoreManager::with_backend(Arc::new(MockBackend::running())));
+
+        let stopper = manager.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            tauri::async_runtime::block_on(stopper.stop_core_with(false, || Ok(()))).unwrap();
+        });
+        tauri::async_runtime::block_on(
+            manager.wait_for_state(CoreState::Stopped, Duration::from_secs(5)),
+        )
+        .unwrap();
+        handle.join().unwrap();
+
+        let err = tauri::async_runtime::block_on(
+            manager.wait_for_state(CoreState::Running, Duration::from_millis(50)),
+        )
+        .unwrap_err();
+        let err = err.downcast::<CoreStateTimeout>().unwrap();
+        assert_eq!(err.actual, CoreState::Stopped);
+
+        // 核心进程自行退出时同样会唤醒等待者
+        let backend = Arc::new(MockBackend::running());
+        let manager = CoreManager::with_backend(backend.clone());
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            backend.exit();
+        });
+        tauri::async_runtime::block_on(
+            manager.wait_for_state(CoreState::Stopped, Duration::from_secs(5)),
+        )
+        .unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_count_recover_failure_per_core() {
+        let manager = CoreManager::with_backend(Arc::new(MockBackend::running()));
+        for _ in 1..SUGGEST_SWITCH_CORE_FAILURES {
+            assert_eq!(manager.count_recover_failure(&ClashCore::Mihomo), None);
+        }
+        assert_eq!(manager.count_recover_failure(&ClashCore::ClashRs), None);
+        assert_eq!(
+            manager.count_recover_failure(&ClashCore::Mihomo),
+            Some(SUGGEST_SWITCH_CORE_FAILURES)
+        );
+        // 建议后重新计数
+        assert_eq!(manager.count_recover_failure(&ClashCore::Mihomo), None);
+    }
+
+    #[test]
+    fn test_needs_restart_only_on_restart_keys() {
+        let manager = CoreManager::with_backend(Arc::new(MockBackend::running()));
+        let running: Mapping = serde_yaml::from_str(
+            "mixed-port: 7890\nexternal-controller: 127.0.0.1:9090\nmode: rule",
+        )
+        .unwrap();
+        assert!(!manager.needs_restart(&running));
+
+        *manager.running_config.lock() = Some(running.clone());
+        let mut config = running.clone();
+        config.insert("mode".into(), "global".into());
+        assert!(!manager.needs_restart(&config));
+
+        config.insert("mixed-port".into(), 7891.into());
+        assert!(manager.needs_restart(&config));
+
+        let mut config = running.clone();
+        config.insert("tun".into(), serde_yaml::from_str("enable: true").unwrap());
+        assert!(manager.needs_restart(&config));
+    }
+
+    #[test]
+    fn test_patchable_diff() {
+        let running: Mapping =
+            serde_yaml::from_str("mode: rule\nlog-level: info\nproxies: []").unwrap();
+
+        let config: Mapping =
+            serde_yaml::from_str("mode: global\nlog-level: info\nproxies: []").unwrap();
+        let diff = CoreManager::patchable_diff(&running, &config).unwrap();
+        assert_eq!(
+            diff,
+            serde_yaml::from_str::<Mapping>("mode: global").unwrap()
+        );
+
+        assert!(CoreManager::patchable_diff(&running, &running)
+            .unwrap()
+            .is_empty());
+
+        let config: Mapping =
+            serde_yaml::from_str("mode: global\nlog-level: info\nproxies: [{name: a}]").unwrap();
+        assert!(CoreManager::patchable_diff(&running, &config).is_none());
+
+        // 删除的配置项无法通过 PATCH 还原
+        let config: Mapping = serde_yaml::from_str("mode: rule\nlog-level: info").unwrap();
+        assert!(CoreManager::patchable_diff(&running, &config).is_none());
+    }
+
+    #[test]
+    fn test_stop_core_kills_sidecar_in_tun_mode() {
+        // DNS 恢复成功时也必须停止核心
+        let backend = Arc::new(MockBackend::running());
+        let manager = CoreManager::with_backend(backend.clone());
+        let mut restored = false;
+        tauri::async_runtime::block_on(manager.stop_core_with(true, || {
+            restored = true;
+            Ok(())
+        }))
+        .unwrap();
+        assert!(restored);
+        assert!(!backend.is_running());
+
+        let backend = Arc::new(MockBackend::running());
+        let manager = CoreManager::with_backend(backend.clone());
+        tauri::async_runtime::block_on(
+            manager.stop_core_with(true, || bail!("networksetup failed")),
+        )
+        .unwrap();
+        assert!(!backend.is_running());
+    }
+
+    #[test]
+    fn test_parse_dns_servers() {
+        assert_eq!(
+            parse_dns_servers("1.1.1.1\n2606:4700:4700::1111\n"),
+            ["1.1.1.1", "2606:4700:4700::1111"]
+        );
+        assert!(parse_dns_servers("There aren't any DNS Servers set on Wi-Fi.").is_empty());
+    }
+
+    #[test]
+    fn test_is_transient_error() {
+        let timeout = anyhow::Error::new(CoreStateTimeout {
+            target: CoreState::Running,
+            actual: CoreState::Starting,
+            timeout: Duration::from_secs(1),
+        });
+        assert!(is_transient_error(
+            &timeout.context("failed to update config")
+        ));
+        assert!(!is_transient_error(&anyhow::anyhow!(
+            "failed to put configs with status \"400\""
+        )));
+    }
+
+    #[test]
+    fn test_prepare_check_dir() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        std::fs::write(from.path().join("geoip.dat"), "geoip").unwrap();
+        std::fs::create_dir(from.path().join("providers")).unwrap();
+        std::fs::write(from.path().join("providers/a.yaml"), "proxies: []").unwrap();
+
+        prepare_check_dir(from.path(), to.path()).unwrap();
+        // 数据文件是独立的副本，改写不会影响原文件
+        std::fs::write(to.path().join("geoip.dat"), "updated").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(from.path().join("geoip.dat")).unwrap(),
+            "geoip"
+        );
+        #[cfg(unix)]
+        assert_eq!(
+            std::fs::read_to_string(to.path().join("providers/a.yaml")).unwrap(),
+            "proxies: []"
+        );
+    }
+
+    #[test]
+    fn test_change_core_error_from_check() {
+        let err = ChangeCoreError::from(anyhow::Error::from(ConfigCheckFailed(vec![
+            "proxy group[0]: 'name' missing".to_string(),
+        ])));
+        assert!(matches!(err, ChangeCoreError::ConfigInvalid { details } if details.len() == 1));
+
+        let err = ChangeCoreError::from(anyhow::anyhow!("core binary not found `mihomo`"));
+        assert!(matches!(err, ChangeCoreError::SpawnFailed { .. }));
+
+        let err = ChangeCoreError::from(anyhow::Error::from(ConfigCheckTimeout(
+            Duration::from_secs(30),
+        )));
+        assert!(matches!(
+            err,
+            ChangeCoreError::CheckTimedOut { timeout_secs: 30 }
+        ));
     }
 }
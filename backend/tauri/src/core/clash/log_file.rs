@@ -0,0 +1,189 @@
+//! 将核心的输出持久化到日志目录，与内存中的 `Logger` 相互独立
+use crate::{config::Config, log_err, utils::dirs};
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+const CORE_LOG_FILE: &str = "core.log";
+
+/// 当前文件超过该大小后轮换，单位 MiB
+const DEFAULT_MAX_SIZE: u64 = 10;
+
+/// 默认保留的已轮换文件数量
+const DEFAULT_MAX_FILES: usize = 5;
+
+/// 轮换与保留的限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoreLogLimits {
+    pub max_size: u64,
+    pub max_files: usize,
+}
+
+impl CoreLogLimits {
+    /// 未开启 `persist_core_logs` 时返回 None
+    pub fn from_config() -> Option<Self> {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        if !verge.persist_core_logs.unwrap_or(false) {
+            return None;
+        }
+        Some(CoreLogLimits {
+            max_size: verge.core_log_file_max_size.unwrap_or(DEFAULT_MAX_SIZE) * 1024 * 1024,
+            max_files: verge.core_log_file_max_count.unwrap_or(DEFAULT_MAX_FILES),
+        })
+    }
+}
+
+struct OpenedFile {
+    file: fs::File,
+    size: u64,
+    /// 文件内容所属的日期，跨天后轮换
+    date: NaiveDate,
+}
+
+/// 按大小和日期轮换的核心日志文件，旧文件依次命名为 `core.log.1`、`core.log.2` ...
+pub struct CoreLogFile {
+    opened: Mutex<Option<OpenedFile>>,
+}
+
+impl CoreLogFile {
+    pub fn global() -> &'static CoreLogFile {
+        static LOG_FILE: OnceCell<CoreLogFile> = OnceCell::new();
+
+        LOG_FILE.get_or_init(CoreLogFile::new)
+    }
+
+    fn new() -> Self {
+        CoreLogFile {
+            opened: Mutex::new(None),
+        }
+    }
+
+    /// 写入一行核心输出，写入失败只记录日志
+    pub fn append(&self, line: &str, limits: &CoreLogLimits) {
+        let dir = match dirs::app_logs_dir() {
+            Ok(dir) => dir,
+            Err(err) => {
+                log::error!(target: "app", "failed to get the logs dir: {err:?}");
+                return;
+            }
+        };
+        log_err!(self.append_to(&dir, Local::now(), line, limits));
+    }
+
+    fn append_to(
+        &self,
+        dir: &Path,
+        now: DateTime<Local>,
+        line: &str,
+        limits: &CoreLogLimits,
+    ) -> Result<()> {
+        let line = format!("{} {line}\n", now.format("%Y-%m-%d %H:%M:%S"));
+        let today = now.date_naive();
+        let mut opened = self.opened.lock();
+
+        let rotate = match opened.as_ref() {
+            Some(file) => file.date != today || file.size + line.len() as u64 > limits.max_size,
+            None => false,
+        };
+        if rotate {
+            opened.take();
+            rotate_files(dir, limits.max_files)?;
+        }
+
+        let file = match opened.as_mut() {
+            Some(file) => file,
+            None => opened.insert(open_file(dir, today, limits)?),
+        };
+        file.file.write_all(line.as_bytes())?;
+        file.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+fn log_path(dir: &Path, index: usize) -> PathBuf {
+    match index {
+        0 => dir.join(CORE_LOG_FILE),
+        n => dir.join(format!("{CORE_LOG_FILE}.{n}")),
+    }
+}
+
+/// 打开当前的日志文件，已有的文件超出限制或不是今天写入的先轮换
+fn open_file(dir: &Path, today: NaiveDate, limits: &CoreLogLimits) -> Result<OpenedFile> {
+    fs::create_dir_all(dir)?;
+    let path = log_path(dir, 0);
+    if let Ok(metadata) = fs::metadata(&path) {
+        let modified = metadata
+            .modified()
+            .map(|time| DateTime::<Local>::from(time).date_naive())
+            .unwrap_or(today);
+        if modified != today || metadata.len() >= limits.max_size {
+            rotate_files(dir, limits.max_files)?;
+        }
+    }
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    let size = file.metadata()?.len();
+    Ok(OpenedFile {
+        file,
+        size,
+        date: today,
+    })
+}
+
+/// 依次重命名旧文件，超出 `max_files` 的文件被删除
+fn rotate_files(dir: &Path, max_files: usize) -> Result<()> {
+    let mut index = max_files;
+    while log_path(dir, index + 1).exists() {
+        index += 1;
+    }
+    for index in (max_files..=index).rev() {
+        let _ = fs::remove_file(log_path(dir, index));
+    }
+    for index in (0..max_files).rev() {
+        let from = log_path(dir, index);
+        if from.exists() {
+            fs::rename(from, log_path(dir, index + 1))?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_core_log_rotation() {
+    let dir = tempfile::tempdir().unwrap();
+    let log_file = CoreLogFile::new();
+    let limits = CoreLogLimits {
+        max_size: 32,
+        max_files: 2,
+    };
+    let now = Local::now();
+
+    // 每行加上时间戳后为 27 字节，每写一行轮换一次
+    for i in 0..4 {
+        log_file
+            .append_to(dir.path(), now, &format!("line {i}"), &limits)
+            .unwrap();
+    }
+    let read = |index| fs::read_to_string(log_path(dir.path(), index)).unwrap();
+    assert!(read(0).ends_with("line 3\n"));
+    assert!(read(1).ends_with("line 2\n"));
+    assert!(read(2).ends_with("line 1\n"));
+    assert!(!log_path(dir.path(), 3).exists());
+
+    // 跨天后即使没有超出大小也会轮换
+    let tomorrow = now + chrono::Duration::days(1);
+    log_file
+        .append_to(dir.path(), tomorrow, "next day", &limits)
+        .unwrap();
+    assert!(read(0).ends_with("next day\n"));
+    assert!(read(1).ends_with("line 3\n"));
+}
@@ -0,0 +1,185 @@
+//! 本次运行期间核心生命周期事件的计数与耗时，只保存在内存中
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// 耗时分布的桶上限，单位毫秒，超出最后一个的计入溢出桶
+const BUCKETS_MS: [u64; 7] = [100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// 固定分桶的耗时分布
+#[derive(Debug, Default)]
+pub struct Histogram {
+    buckets: [AtomicU64; BUCKETS_MS.len() + 1],
+    count: AtomicU64,
+    sum_ms: AtomicU64,
+    max_ms: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistogramBucket {
+    /// 为 None 时是溢出桶
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistogramSnapshot {
+    pub count: u64,
+    pub avg_ms: Option<u64>,
+    pub max_ms: u64,
+    pub buckets: Vec<HistogramBucket>,
+}
+
+impl Histogram {
+    pub fn observe(&self, duration: Duration) {
+        let ms = duration.as_millis() as u64;
+        let index = BUCKETS_MS
+            .iter()
+            .position(|le| ms <= *le)
+            .unwrap_or(BUCKETS_MS.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_ms.fetch_add(ms, Ordering::Relaxed);
+        self.max_ms.fetch_max(ms, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let count = self.count.load(Ordering::Relaxed);
+        let sum_ms = self.sum_ms.load(Ordering::Relaxed);
+        let buckets = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(index, bucket)| HistogramBucket {
+                le_ms: BUCKETS_MS.get(index).copied(),
+                count: bucket.load(Ordering::Relaxed),
+            })
+            .collect();
+        HistogramSnapshot {
+            count,
+            avg_ms: sum_ms.checked_div(count),
+            max_ms: self.max_ms.load(Ordering::Relaxed),
+            buckets,
+        }
+    }
+}
+
+/// 核心生命周期的统计，由 `CoreManager` 在对应的操作中更新
+#[derive(Debug, Default)]
+pub struct CoreMetrics {
+    since: i64,
+    starts: AtomicU64,
+    start_failures: AtomicU64,
+    stops: AtomicU64,
+    crashes: AtomicU64,
+    recoveries: AtomicU64,
+    recovery_failures: AtomicU64,
+    config_pushes: AtomicU64,
+    config_push_failures: AtomicU64,
+    start_duration: Histogram,
+    config_push_duration: Histogram,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CoreMetricsSnapshot {
+    /// 统计开始的时间，即核心管理初始化的时间
+    pub since: i64,
+    pub starts: u64,
+    pub start_failures: u64,
+    pub stops: u64,
+    pub crashes: u64,
+    pub recoveries: u64,
+    pub recovery_failures: u64,
+    pub config_pushes: u64,
+    pub config_push_failures: u64,
+    pub start_duration: HistogramSnapshot,
+    pub config_push_duration: HistogramSnapshot,
+}
+
+impl CoreMetrics {
+    pub fn global() -> &'static CoreMetrics {
+        static METRICS: OnceCell<CoreMetrics> = OnceCell::new();
+
+        METRICS.get_or_init(|| CoreMetrics {
+            since: chrono::Local::now().timestamp(),
+            ..Default::default()
+        })
+    }
+
+    /// 启动的耗时只统计成功的启动
+    pub fn record_start(&self, duration: Duration, success: bool) {
+        if success {
+            self.starts.fetch_add(1, Ordering::Relaxed);
+            self.start_duration.observe(duration);
+        } else {
+            self.start_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_stop(&self) {
+        self.stops.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_crash(&self) {
+        self.crashes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 每次恢复尝试的结果
+    pub fn record_recovery(&self, success: bool) {
+        match success {
+            true => self.recoveries.fetch_add(1, Ordering::Relaxed),
+            false => self.recovery_failures.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub fn record_config_push(&self, duration: Duration, success: bool) {
+        if success {
+            self.config_pushes.fetch_add(1, Ordering::Relaxed);
+            self.config_push_duration.observe(duration);
+        } else {
+            self.config_push_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> CoreMetricsSnapshot {
+        CoreMetricsSnapshot {
+            since: self.since,
+            starts: self.starts.load(Ordering::Relaxed),
+            start_failures: self.start_failures.load(Ordering::Relaxed),
+            stops: self.stops.load(Ordering::Relaxed),
+            crashes: self.crashes.load(Ordering::Relaxed),
+            recoveries: self.recoveries.load(Ordering::Relaxed),
+            recovery_failures: self.recovery_failures.load(Ordering::Relaxed),
+            config_pushes: self.config_pushes.load(Ordering::Relaxed),
+            config_push_failures: self.config_push_failures.load(Ordering::Relaxed),
+            start_duration: self.start_duration.snapshot(),
+            config_push_duration: self.config_push_duration.snapshot(),
+        }
+    }
+}
+
+#[test]
+fn test_histogram() {
+    let histogram = Histogram::default();
+    assert_eq!(histogram.snapshot().avg_ms, None);
+
+    histogram.observe(Duration::from_millis(80));
+    histogram.observe(Duration::from_millis(100));
+    histogram.observe(Duration::from_millis(700));
+    histogram.observe(Duration::from_secs(30));
+
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.count, 4);
+    assert_eq!(snapshot.avg_ms, Some(7720));
+    assert_eq!(snapshot.max_ms, 30000);
+    let counts = snapshot
+        .buckets
+        .iter()
+        .map(|bucket| bucket.count)
+        .collect::<Vec<_>>();
+    assert_eq!(counts, vec![2, 0, 0, 1, 0, 0, 0, 1]);
+    assert_eq!(snapshot.buckets.last().unwrap().le_ms, None);
+}
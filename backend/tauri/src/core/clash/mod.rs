@@ -1,8 +1,14 @@
 use backon::ExponentialBuilder;
 use once_cell::sync::Lazy;
 pub mod api;
+pub mod backend;
+pub mod capability;
 pub mod core;
+pub mod log_file;
+pub mod metrics;
 pub mod proxies;
+pub mod route;
+pub mod traffic;
 
 pub static CLASH_API_DEFAULT_BACKOFF_STRATEGY: Lazy<ExponentialBuilder> = Lazy::new(|| {
     ExponentialBuilder::default()
@@ -2,15 +2,28 @@
 /// It is used to provide the unite interface between tray and frontend.
 /// TODO: add a diff algorithm to reduce the data transfer, and the rerendering of the tray menu.
 use super::{api, CLASH_API_DEFAULT_BACKOFF_STRATEGY};
+use crate::{
+    config::{nyanpasu::ClashCore, Config},
+    log_err,
+};
 use adler::adler32;
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use backon::Retryable;
 use indexmap::IndexMap;
-use log::warn;
+use log::{debug, warn};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, OnceLock};
-use tokio::{sync::broadcast, try_join};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+};
+use tokio::{
+    sync::{broadcast, mpsc, Notify, Semaphore},
+    try_join,
+};
 use tracing_attributes::instrument;
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -55,6 +68,83 @@ impl From<api::ProxyItem> for ProxyGroupItem {
     }
 }
 
+/// 循环切换节点的方向
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CycleDirection {
+    Next,
+    Previous,
+}
+
+/// 自动选择类型的分组实际使用的节点
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedNode {
+    pub name: String,
+    /// 最近一次测速的延迟，未测速或超时为 None
+    pub delay: Option<u64>,
+}
+
+/// 能否手动选择该类型分组中的节点
+pub fn is_selectable(group_type: &str) -> bool {
+    matches!(group_type, "Selector" | "Fallback")
+}
+
+/// 分组的节点由核心自动选择，不能手动选择
+#[derive(Debug, thiserror::Error)]
+#[error("group `{group}` of type `{group_type}` can not be selected manually")]
+pub struct NotSelectable {
+    pub group: String,
+    pub group_type: String,
+}
+
+/// 分组选择节点的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupMode {
+    /// 固定使用当前节点
+    Manual,
+    /// 由核心按测速结果选择
+    Auto,
+}
+
+/// 能否在运行时切换分组的选择方式，只有 mihomo 能固定或取消固定测速类分组的节点
+pub fn supports_group_mode(core: &ClashCore, group_type: &str) -> bool {
+    matches!(core, ClashCore::Mihomo | ClashCore::MihomoAlpha)
+        && matches!(group_type, "URLTest" | "Fallback")
+}
+
+/// 当前核心或分组类型不支持切换选择方式
+#[derive(Debug, thiserror::Error)]
+#[error("switching between manual and auto is not supported for group `{group}` of type `{group_type}` on `{core}`")]
+pub struct GroupModeUnsupported {
+    pub group: String,
+    pub group_type: String,
+    pub core: ClashCore,
+}
+
+/// 代理集合的更新状态
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderInfo {
+    pub name: String,
+    pub vehicle_type: api::VehicleType,
+    /// 核心没有提供时为 None
+    pub updated_at: Option<String>,
+    pub node_count: usize,
+}
+
+impl From<&api::ProxyProviderItem> for ProviderInfo {
+    fn from(item: &api::ProxyProviderItem) -> Self {
+        ProviderInfo {
+            name: item.name.clone(),
+            vehicle_type: item.vehicle_type.clone(),
+            updated_at: item.updated_at.clone(),
+            node_count: item.proxies.len(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Proxies {
@@ -63,6 +153,8 @@ pub struct Proxies {
     pub groups: Vec<ProxyGroupItem>,
     pub records: IndexMap<String, api::ProxyItem>,
     pub proxies: Vec<api::ProxyItem>,
+    #[serde(default)]
+    pub providers: Vec<ProviderInfo>,
 }
 
 async fn fetch_proxies() -> Result<(api::ProxiesRes, api::ProvidersProxiesRes)> {
@@ -70,6 +162,154 @@ async fn fetch_proxies() -> Result<(api::ProxiesRes, api::ProvidersProxiesRes)>
 }
 
 impl Proxies {
+    fn find_group(&self, group: &str) -> Option<&ProxyGroupItem> {
+        self.groups
+            .iter()
+            .find(|g| g.name == group)
+            .or_else(|| Some(&self.global).filter(|g| g.name.eq_ignore_ascii_case(group)))
+    }
+
+    /// 获取分组内的所有节点名称
+    pub fn group_nodes(&self, group: &str) -> Option<Vec<String>> {
+        self.find_group(group)
+            .map(|g| g.all.iter().map(|p| p.name.clone()).collect())
+    }
+
+    /// 检查分组能否手动选择，缓存中没有的分组交由核心判断
+    pub fn check_selectable(&self, group: &str) -> Result<(), NotSelectable> {
+        match self.find_group(group) {
+            Some(item) if !is_selectable(&item.r#type) => Err(NotSelectable {
+                group: group.to_string(),
+                group_type: item.r#type.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// 所有可手动选择的分组当前选中的节点
+    pub fn current_selections(&self) -> HashMap<String, String> {
+        std::iter::once(&self.global)
+            .chain(self.groups.iter())
+            .filter(|group| is_selectable(&group.r#type))
+            .filter_map(|group| Some((group.name.clone(), group.now.clone()?)))
+            .collect()
+    }
+
+    /// 过滤出需要重新选择的分组，跳过已不存在的分组或节点
+    fn pending_selections<'a>(
+        &self,
+        selections: &'a HashMap<String, String>,
+    ) -> Vec<(&'a String, &'a String)> {
+        selections
+            .iter()
+            .filter(|(group, name)| {
+                let Some(item) = self.find_group(group) else {
+                    debug!(target: "clash::proxies", "skip restoring stale group `{group}`");
+                    return false;
+                };
+                if item.now.as_ref() == Some(*name) {
+                    return false;
+                }
+                if !item.all.iter().any(|p| p.name == **name) {
+                    debug!(target: "clash::proxies", "skip restoring stale node `{name}` in `{group}`");
+                    return false;
+                }
+                true
+            })
+            .collect()
+    }
+
+    /// 使用从核心获取的分组信息替换缓存中的分组，缓存中没有该分组时返回 false
+    fn replace_group(&mut self, item: api::ProxyItem) -> bool {
+        let target = match self.global.name == item.name {
+            true => Some(&mut self.global),
+            false => self.groups.iter_mut().find(|g| g.name == item.name),
+        };
+        let Some(target) = target else {
+            return false;
+        };
+        // 节点信息沿用缓存，不在缓存中的节点来自 provider
+        let all = item
+            .all
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|name| {
+                self.records
+                    .get(&name)
+                    .or_else(|| target.all.iter().find(|p| p.name == name))
+                    .cloned()
+                    .unwrap_or_else(|| api::ProxyItem {
+                        name,
+                        r#type: "Unknown".to_string(),
+                        ..Default::default()
+                    })
+            })
+            .collect();
+        let mut group: ProxyGroupItem = item.clone().into();
+        group.all = all;
+        *target = group;
+        self.records.insert(item.name.clone(), item);
+        true
+    }
+
+    /// 获取分组当前选中的节点
+    pub fn group_now(&self, group: &str) -> Option<String> {
+        self.find_group(group).and_then(|g| g.now.clone())
+    }
+
+    /// 解析自动选择类型分组（URLTest、LoadBalance 等）当前实际使用的节点
+    pub fn resolve_group(&self, group: &ProxyGroupItem) -> Option<ResolvedNode> {
+        if !matches!(
+            group.r#type.as_str(),
+            "URLTest" | "LoadBalance" | "Fallback"
+        ) {
+            return None;
+        }
+        let name = group.now.clone()?;
+        let delay = group
+            .all
+            .iter()
+            .find(|p| p.name == name)
+            .and_then(|p| p.history.last())
+            .and_then(|h| u64::try_from(h.delay).ok())
+            .filter(|delay| *delay > 0);
+        Some(ResolvedNode { name, delay })
+    }
+
+    /// 从分组开始沿着各分组当前选中的节点，得到最终使用的代理链
+    pub fn resolve_chain(&self, start: &str) -> Vec<String> {
+        let mut chain = vec![start.to_string()];
+        while let Some(now) = self
+            .find_group(chain.last().unwrap())
+            .and_then(|g| g.now.clone())
+        {
+            // 防止分组互相引用时死循环
+            if chain.contains(&now) {
+                break;
+            }
+            chain.push(now);
+        }
+        chain
+    }
+
+    /// 所有可手动选择的分组的代理链，链的第一项为分组本身
+    pub fn proxy_chains(&self) -> IndexMap<String, Vec<String>> {
+        std::iter::once(&self.global)
+            .chain(self.groups.iter())
+            .filter(|group| is_selectable(&group.r#type))
+            .map(|group| (group.name.clone(), self.resolve_chain(&group.name)))
+            .collect()
+    }
+
+    /// 所有自动选择类型分组当前实际使用的节点
+    pub fn resolved_nodes(&self) -> IndexMap<String, ResolvedNode> {
+        self.groups
+            .iter()
+            .filter_map(|group| Some((group.name.clone(), self.resolve_group(group)?)))
+            .collect()
+    }
+
     #[instrument]
     pub async fn fetch() -> Result<Self> {
         let (inner_proxies, providers_proxies) = fetch_proxies
@@ -188,15 +428,57 @@ impl Proxies {
             groups,
             records: inner_proxies,
             proxies,
+            providers: providers_proxies.values().map(ProviderInfo::from).collect(),
         })
     }
 }
 
+/// 节点选择变化的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionOrigin {
+    /// 主窗口
+    Window,
+    Tray,
+    /// 启动或重载配置后恢复保存的选择
+    Restore,
+    /// 通过外部控制接口等其他方式修改
+    External,
+}
+
+/// 分组选中的节点发生了变化
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SelectionChange {
+    pub group: String,
+    pub from: String,
+    pub to: String,
+    pub origin: SelectionOrigin,
+}
+
+/// 找出两次刷新之间选中节点发生变化的分组，新增或移除的分组不算作变化
+fn diff_selections(
+    old: &HashMap<String, String>,
+    new: &HashMap<String, String>,
+) -> Vec<(String, String, String)> {
+    let mut changes = new
+        .iter()
+        .filter_map(|(group, to)| {
+            let from = old.get(group)?;
+            (from != to).then(|| (group.clone(), from.clone(), to.clone()))
+        })
+        .collect::<Vec<_>>();
+    changes.sort();
+    changes
+}
+
 pub struct ProxiesGuard {
     inner: Proxies,
+    resolved: IndexMap<String, ResolvedNode>,
     checksum: Option<u32>,
     updated_at: u64,
     sender: broadcast::Sender<()>,
+    /// 由应用发起、尚未在刷新中观察到的选择及其来源
+    expected: HashMap<String, (String, SelectionOrigin)>,
 }
 
 impl ProxiesGuard {
@@ -208,7 +490,9 @@ impl ProxiesGuard {
                 checksum: None,
                 sender: tx,
                 inner: Proxies::default(),
+                resolved: IndexMap::new(),
                 updated_at: 0,
+                expected: HashMap::new(),
             }))
         })
     }
@@ -218,7 +502,27 @@ impl ProxiesGuard {
     }
 
     pub fn replace(&mut self, proxies: Proxies, checksum: u32) {
+        let changes = diff_selections(
+            &self.inner.current_selections(),
+            &proxies.current_selections(),
+        );
+        for (group, from, to) in changes {
+            let origin = match self.expected.remove(&group) {
+                Some((name, origin)) if name == to => origin,
+                _ => SelectionOrigin::External,
+            };
+            let change = SelectionChange {
+                group,
+                from,
+                to,
+                origin,
+            };
+            debug!(target: "clash::proxies", "selection changed: {change:?}");
+            crate::core::handle::Handle::selection_changed(&change);
+        }
+
         let now = chrono::Utc::now().timestamp() as u64;
+        self.resolved = proxies.resolved_nodes();
         self.inner = proxies;
         self.checksum = Some(checksum);
         self.updated_at = now;
@@ -237,10 +541,20 @@ impl ProxiesGuard {
     //     Ok(())
     // }
 
+    /// 记录即将由应用发起的选择，刷新观察到该变化时使用此来源
+    fn expect_selection(&mut self, group: &str, name: &str, origin: SelectionOrigin) {
+        self.expected
+            .insert(group.to_string(), (name.to_string(), origin));
+    }
+
     pub fn inner(&self) -> &Proxies {
         &self.inner
     }
 
+    pub fn resolved(&self) -> &IndexMap<String, ResolvedNode> {
+        &self.resolved
+    }
+
     pub fn updated_at(&self) -> u64 {
         self.updated_at
     }
@@ -251,9 +565,168 @@ impl ProxiesGuard {
     }
 }
 
+/// 同时进行的延迟测试数量的默认上限
+const DEFAULT_DELAY_TEST_CONCURRENCY: usize = 5;
+
+/// 所有延迟测试共用的信号量，超出上限的测试排队等待
+/// 上限修改后创建新的信号量，进行中的测试不受影响
+fn delay_test_semaphore() -> Arc<Semaphore> {
+    static SEMAPHORE: parking_lot::Mutex<Option<(usize, Arc<Semaphore>)>> =
+        parking_lot::Mutex::new(None);
+
+    let limit = { Config::verge().latest().delay_test_concurrency };
+    let limit = limit.unwrap_or(DEFAULT_DELAY_TEST_CONCURRENCY).max(1);
+    let mut semaphore = SEMAPHORE.lock();
+    match semaphore.as_ref() {
+        Some((current, semaphore)) if *current == limit => semaphore.clone(),
+        _ => {
+            let new = Arc::new(Semaphore::new(limit));
+            *semaphore = Some((limit, new.clone()));
+            new
+        }
+    }
+}
+
+/// 延迟测试被取消
+#[derive(Debug, thiserror::Error)]
+#[error("latency tests cancelled")]
+pub struct DelayTestCancelled;
+
+/// 延迟测试的取消信号，取消时递增代数，之前开始的批次中排队的测试全部中止
+struct DelayTestControl {
+    generation: AtomicU64,
+    running: AtomicUsize,
+    notify: Notify,
+}
+
+impl DelayTestControl {
+    fn global() -> &'static DelayTestControl {
+        static CONTROL: OnceLock<DelayTestControl> = OnceLock::new();
+
+        CONTROL.get_or_init(|| DelayTestControl {
+            generation: AtomicU64::new(0),
+            running: AtomicUsize::new(0),
+            notify: Notify::new(),
+        })
+    }
+
+    fn is_cancelled(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) != generation
+    }
+
+    /// 在批次被取消时完成
+    async fn cancelled(&self, generation: u64) {
+        loop {
+            let notified = self.notify.notified();
+            if self.is_cancelled(generation) {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// 一批延迟测试，存在期间托盘显示测试中
+struct DelayTestBatch {
+    generation: u64,
+}
+
+impl DelayTestBatch {
+    fn start() -> Self {
+        let control = DelayTestControl::global();
+        if control.running.fetch_add(1, Ordering::SeqCst) == 0 {
+            crate::core::handle::Handle::delay_tests_changed(true);
+        }
+        DelayTestBatch {
+            generation: control.generation.load(Ordering::SeqCst),
+        }
+    }
+}
+
+impl Drop for DelayTestBatch {
+    fn drop(&mut self) {
+        if DelayTestControl::global()
+            .running
+            .fetch_sub(1, Ordering::SeqCst)
+            == 1
+        {
+            crate::core::handle::Handle::delay_tests_changed(false);
+        }
+    }
+}
+
+/// 是否有进行中的延迟测试批次
+pub fn is_delay_testing() -> bool {
+    DelayTestControl::global().running.load(Ordering::SeqCst) > 0
+}
+
+/// 取消所有进行中的延迟测试批次
+/// 排队中的测试立即中止，已经发出的请求会继续完成
+pub fn cancel_delay_tests() {
+    let control = DelayTestControl::global();
+    control.generation.fetch_add(1, Ordering::SeqCst);
+    control.notify.notify_waiters();
+}
+
+/// 受并发上限约束的延迟测试，所属批次取消后不再发出请求
+async fn test_delay(
+    name: String,
+    test_url: Option<String>,
+    generation: u64,
+) -> Result<api::DelayRes> {
+    let control = DelayTestControl::global();
+    let _permit = tokio::select! {
+        permit = delay_test_semaphore().acquire_owned() => permit?,
+        _ = control.cancelled(generation) => bail!(DelayTestCancelled),
+    };
+    if control.is_cancelled(generation) {
+        bail!(DelayTestCancelled);
+    }
+    api::get_proxy_delay(name, test_url).await
+}
+
+/// 单个节点的延迟测试结果，超时或失败时 `delay` 为 None
+#[derive(Debug, Clone, Serialize)]
+pub struct DelayTestResult {
+    pub name: String,
+    pub delay: Option<u64>,
+}
+
 pub trait ProxiesGuardExt {
     async fn update(&self) -> Result<()>;
-    async fn select_proxy(&self, group: &str, name: &str) -> Result<()>;
+    /// 只刷新单个分组，缓存中没有该分组时完整刷新
+    async fn update_group(&self, group: &str) -> Result<()>;
+    /// 立即刷新，并发调用会合并为同一次更新
+    async fn refresh_now(&self) -> Result<()>;
+    async fn select_proxy(&self, group: &str, name: &str, origin: SelectionOrigin) -> Result<()>;
+    /// 重新应用保存的节点选择，跳过已不存在的分组或节点
+    async fn restore_selections(&self) -> Result<()>;
+    /// 将所有分组当前选中的节点保存为指定名称的快照，同名快照会被覆盖
+    async fn save_selection_snapshot(&self, name: &str) -> Result<()>;
+    /// 应用快照中的节点选择，跳过已不存在的分组或节点
+    async fn apply_selection_snapshot(&self, name: &str, origin: SelectionOrigin) -> Result<()>;
+    /// 按序号选择分组内的节点
+    async fn select_proxy_by_index(
+        &self,
+        group: &str,
+        index: usize,
+        origin: SelectionOrigin,
+    ) -> Result<String>;
+    /// 选择当前节点的下一个或上一个节点，到达两端时循环
+    async fn cycle_proxy(
+        &self,
+        group: &str,
+        direction: CycleDirection,
+        origin: SelectionOrigin,
+    ) -> Result<String>;
+    /// 测试分组内所有节点的延迟，并选择延迟最低的节点
+    /// 所有节点都超时则不改变选择，返回 None，测试被取消时返回 `DelayTestCancelled`
+    async fn select_fastest(&self, group: &str, origin: SelectionOrigin) -> Result<Option<String>>;
+    /// 测试所有分组中节点的延迟，每个节点只测试一次
+    /// 结果在测试完成后立即发送，全部完成或被取消后 channel 关闭
+    fn test_all_groups(&self) -> mpsc::UnboundedReceiver<DelayTestResult>;
+    /// 将分组固定到当前节点或恢复自动选择，完成后刷新代理
+    async fn set_group_mode(&self, group: &str, mode: GroupMode) -> Result<()>;
 }
 
 type ProxiesGuardSingleton = &'static Arc<RwLock<ProxiesGuard>>;
@@ -273,9 +746,480 @@ impl ProxiesGuardExt for ProxiesGuardSingleton {
         Ok(())
     }
 
-    async fn select_proxy(&self, group: &str, name: &str) -> Result<()> {
-        api::update_proxy(group, name).await?;
+    async fn update_group(&self, group: &str) -> Result<()> {
+        let item = api::get_proxy(group.to_string()).await?;
+        let proxies = {
+            let mut proxies = self.read().inner().clone();
+            proxies.replace_group(item).then_some(proxies)
+        };
+        let Some(proxies) = proxies else {
+            debug!(target: "clash::proxies", "group `{group}` is not cached, update all");
+            return self.update().await;
+        };
+
+        let buf = simd_json::to_string(&proxies)?;
+        let checksum = adler32(buf.as_bytes())?;
+        let mut writer = self.write();
+        if writer.checksum != Some(checksum) {
+            writer.replace(proxies, checksum);
+        }
+        Ok(())
+    }
+
+    async fn refresh_now(&self) -> Result<()> {
+        /// 正在进行的刷新，完成时向等待者广播结果
+        static IN_FLIGHT: parking_lot::Mutex<Option<broadcast::Sender<Result<(), String>>>> =
+            parking_lot::Mutex::new(None);
+
+        let receiver = {
+            let mut in_flight = IN_FLIGHT.lock();
+            match in_flight.as_ref() {
+                Some(sender) => Some(sender.subscribe()),
+                None => {
+                    *in_flight = Some(broadcast::channel(1).0);
+                    None
+                }
+            }
+        };
+        if let Some(mut receiver) = receiver {
+            // 已有刷新在进行，等待其完成并使用其结果
+            return match receiver.recv().await {
+                Ok(res) => res.map_err(|err| anyhow::anyhow!(err)),
+                Err(_) => bail!("the proxies refresh is interrupted"),
+            };
+        }
+
+        /// 刷新被取消时移除发送端，等待者随之结束等待
+        struct InFlightGuard;
+        impl Drop for InFlightGuard {
+            fn drop(&mut self) {
+                IN_FLIGHT.lock().take();
+            }
+        }
+        let _guard = InFlightGuard;
+
+        let res = self.update().await;
+        if let Some(sender) = IN_FLIGHT.lock().take() {
+            let _ = sender.send(res.as_ref().map(|_| ()).map_err(|err| format!("{err:#}")));
+        }
+        res
+    }
+
+    async fn select_proxy(&self, group: &str, name: &str, origin: SelectionOrigin) -> Result<()> {
+        self.read().inner().check_selectable(group)?;
+        self.write().expect_selection(group, name, origin);
+        if let Err(e) = api::update_proxy(group, name).await {
+            self.write().expected.remove(group);
+            return Err(e);
+        }
+        {
+            let verge = Config::verge();
+            let mut verge = verge.data();
+            verge
+                .proxy_selections
+                .get_or_insert_with(Default::default)
+                .insert(group.to_string(), name.to_string());
+            log_err!(verge.save_file());
+        }
+        self.update_group(group).await?;
+        Ok(())
+    }
+
+    async fn restore_selections(&self) -> Result<()> {
+        let selections = { Config::verge().latest().proxy_selections.clone() };
+        let selections = match selections {
+            Some(selections) if !selections.is_empty() => selections,
+            _ => return Ok(()),
+        };
+
+        let proxies = Proxies::fetch().await?;
+        for (group, name) in proxies.pending_selections(&selections) {
+            self.write()
+                .expect_selection(group, name, SelectionOrigin::Restore);
+            if let Err(e) = api::update_proxy(group, name).await {
+                warn!(target: "clash::proxies", "failed to restore `{name}` in `{group}`: {e:?}");
+            }
+        }
+        self.update().await
+    }
+
+    async fn save_selection_snapshot(&self, name: &str) -> Result<()> {
         self.update().await?;
+        let selections = self.read().inner().current_selections();
+        let verge = Config::verge();
+        let mut verge = verge.data();
+        verge
+            .selection_snapshots
+            .get_or_insert_with(Default::default)
+            .insert(name.to_string(), selections);
+        verge.save_file()
+    }
+
+    async fn apply_selection_snapshot(&self, name: &str, origin: SelectionOrigin) -> Result<()> {
+        let snapshot = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            verge
+                .selection_snapshots
+                .as_ref()
+                .and_then(|snapshots| snapshots.get(name).cloned())
+        };
+        let snapshot = snapshot.ok_or(anyhow::anyhow!("selection snapshot `{name}` not found"))?;
+
+        self.update().await?;
+        let pending = {
+            let reader = self.read();
+            reader
+                .inner()
+                .pending_selections(&snapshot)
+                .into_iter()
+                .map(|(group, name)| (group.clone(), name.clone()))
+                .collect::<Vec<_>>()
+        };
+        for (group, name) in pending {
+            if let Err(e) = self.select_proxy(&group, &name, origin).await {
+                warn!(target: "clash::proxies", "failed to apply `{name}` in `{group}`: {e:?}");
+            }
+        }
         Ok(())
     }
+
+    async fn select_proxy_by_index(
+        &self,
+        group: &str,
+        index: usize,
+        origin: SelectionOrigin,
+    ) -> Result<String> {
+        let name = {
+            let reader = self.read();
+            let nodes = reader
+                .inner()
+                .group_nodes(group)
+                .ok_or(anyhow::anyhow!("group `{group}` not found"))?;
+            nodes.get(index).cloned().ok_or(anyhow::anyhow!(
+                "index {index} is out of range, group `{group}` has {} nodes",
+                nodes.len()
+            ))?
+        };
+        self.select_proxy(group, &name, origin).await?;
+        Ok(name)
+    }
+
+    async fn cycle_proxy(
+        &self,
+        group: &str,
+        direction: CycleDirection,
+        origin: SelectionOrigin,
+    ) -> Result<String> {
+        let index = {
+            let reader = self.read();
+            let proxies = reader.inner();
+            let nodes = proxies
+                .group_nodes(group)
+                .ok_or(anyhow::anyhow!("group `{group}` not found"))?;
+            if nodes.is_empty() {
+                anyhow::bail!("group `{group}` has no nodes");
+            }
+            let current = proxies
+                .group_now(group)
+                .and_then(|now| nodes.iter().position(|name| *name == now))
+                .unwrap_or(0);
+            match direction {
+                CycleDirection::Next => (current + 1) % nodes.len(),
+                CycleDirection::Previous => (current + nodes.len() - 1) % nodes.len(),
+            }
+        };
+        self.select_proxy_by_index(group, index, origin).await
+    }
+
+    async fn select_fastest(&self, group: &str, origin: SelectionOrigin) -> Result<Option<String>> {
+        let nodes = {
+            let reader = self.read();
+            reader
+                .inner()
+                .group_nodes(group)
+                .ok_or(anyhow::anyhow!("group `{group}` not found"))?
+        };
+        let test_url = { Config::verge().latest().default_latency_test.clone() };
+        let batch = DelayTestBatch::start();
+        let results = futures::future::join_all(nodes.into_iter().map(|name| {
+            let test_url = test_url.clone();
+            let generation = batch.generation;
+            async move {
+                let res = test_delay(name.clone(), test_url, generation).await;
+                (name, res)
+            }
+        }))
+        .await;
+        if DelayTestControl::global().is_cancelled(batch.generation) {
+            bail!(DelayTestCancelled);
+        }
+        drop(batch);
+        let fastest = results
+            .into_iter()
+            .filter_map(|(name, res)| match res {
+                Ok(res) if res.delay > 0 => Some((name, res.delay)),
+                _ => None, // 超时或测试失败的节点直接跳过
+            })
+            .min_by_key(|(_, delay)| *delay);
+
+        match fastest {
+            Some((name, _)) => {
+                self.select_proxy(group, &name, origin).await?;
+                Ok(Some(name))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn test_all_groups(&self) -> mpsc::UnboundedReceiver<DelayTestResult> {
+        let nodes = {
+            let reader = self.read();
+            let proxies = reader.inner();
+            let mut seen = HashSet::new();
+            proxies
+                .groups
+                .iter()
+                .flat_map(|group| group.all.iter())
+                .filter(|node| seen.insert(node.name.clone()))
+                .map(|node| node.name.clone())
+                .collect::<Vec<_>>()
+        };
+        let test_url = { Config::verge().latest().default_latency_test.clone() };
+
+        // 所有测试结束后批次才结束
+        let batch = Arc::new(DelayTestBatch::start());
+        let (tx, rx) = mpsc::unbounded_channel();
+        for name in nodes {
+            let tx = tx.clone();
+            let test_url = test_url.clone();
+            let batch = batch.clone();
+            tauri::async_runtime::spawn(async move {
+                let delay = match test_delay(name.clone(), test_url, batch.generation).await {
+                    Ok(res) if res.delay > 0 => Some(res.delay),
+                    Err(e) if e.is::<DelayTestCancelled>() => return,
+                    _ => None,
+                };
+                let _ = tx.send(DelayTestResult { name, delay });
+            });
+        }
+        rx
+    }
+
+    async fn set_group_mode(&self, group: &str, mode: GroupMode) -> Result<()> {
+        let (group_type, now) = {
+            let reader = self.read();
+            let item = reader
+                .inner()
+                .find_group(group)
+                .with_context(|| format!("group `{group}` not found"))?;
+            (item.r#type.clone(), item.now.clone())
+        };
+        let core = { Config::verge().latest().clash_core.clone() };
+        let core = core.unwrap_or(ClashCore::ClashPremium);
+        if !supports_group_mode(&core, &group_type) {
+            bail!(GroupModeUnsupported {
+                group: group.to_string(),
+                group_type,
+                core,
+            });
+        }
+
+        match mode {
+            GroupMode::Manual => {
+                let now = now.context("the group has no selected node")?;
+                api::update_proxy(group, &now).await?;
+            }
+            GroupMode::Auto => api::unfix_proxy(group).await?,
+        }
+        self.refresh_now().await
+    }
+}
+
+#[test]
+fn test_check_selectable() {
+    let group = |name: &str, r#type: &str| ProxyGroupItem {
+        name: name.to_string(),
+        r#type: r#type.to_string(),
+        ..Default::default()
+    };
+    let proxies = Proxies {
+        groups: vec![group("select", "Selector"), group("auto", "URLTest")],
+        ..Default::default()
+    };
+
+    assert!(proxies.check_selectable("select").is_ok());
+    assert!(proxies.check_selectable("unknown").is_ok());
+    let err = proxies.check_selectable("auto").unwrap_err();
+    assert_eq!(err.group_type, "URLTest");
+}
+
+#[test]
+fn test_pending_selections() {
+    let node = |name: &str| api::ProxyItem {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    let proxies = Proxies {
+        groups: vec![
+            ProxyGroupItem {
+                name: "select".to_string(),
+                r#type: "Selector".to_string(),
+                all: vec![node("a"), node("b")],
+                now: Some("a".to_string()),
+                ..Default::default()
+            },
+            ProxyGroupItem {
+                name: "auto".to_string(),
+                r#type: "URLTest".to_string(),
+                all: vec![node("a")],
+                now: Some("a".to_string()),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    assert_eq!(
+        proxies.current_selections(),
+        HashMap::from([("select".to_string(), "a".to_string())])
+    );
+
+    let snapshot = HashMap::from([
+        ("select".to_string(), "b".to_string()),
+        ("removed".to_string(), "a".to_string()),
+    ]);
+    let pending = proxies.pending_selections(&snapshot);
+    assert_eq!(pending, [(&"select".to_string(), &"b".to_string())]);
+
+    let snapshot = HashMap::from([("select".to_string(), "c".to_string())]);
+    assert!(proxies.pending_selections(&snapshot).is_empty());
+}
+
+#[test]
+fn test_replace_group() {
+    let node = |name: &str| api::ProxyItem {
+        name: name.to_string(),
+        r#type: "Shadowsocks".to_string(),
+        ..Default::default()
+    };
+    let mut proxies = Proxies {
+        groups: vec![ProxyGroupItem {
+            name: "select".to_string(),
+            r#type: "Selector".to_string(),
+            all: vec![node("a"), node("b")],
+            now: Some("a".to_string()),
+            ..Default::default()
+        }],
+        records: IndexMap::from([("a".to_string(), node("a"))]),
+        ..Default::default()
+    };
+
+    let item = api::ProxyItem {
+        name: "select".to_string(),
+        r#type: "Selector".to_string(),
+        all: Some(vec!["a".to_string(), "b".to_string()]),
+        now: Some("b".to_string()),
+        ..Default::default()
+    };
+    assert!(proxies.replace_group(item));
+    assert_eq!(proxies.group_now("select").as_deref(), Some("b"));
+    // 不在 records 中的节点沿用缓存
+    assert_eq!(proxies.groups[0].all[1].r#type, "Shadowsocks");
+
+    assert!(!proxies.replace_group(api::ProxyItem {
+        name: "unknown".to_string(),
+        ..Default::default()
+    }));
+}
+
+#[test]
+fn test_provider_info_from_item() {
+    let item = api::ProxyProviderItem {
+        name: "sub".to_string(),
+        r#type: api::ProviderType::Proxy,
+        proxies: vec![Default::default(), Default::default()],
+        vehicle_type: api::VehicleType::Http,
+        test_url: None,
+        expected_status: None,
+        updated_at: Some("2024-01-01T00:00:00Z".to_string()),
+    };
+    let info = ProviderInfo::from(&item);
+    assert_eq!(info.name, "sub");
+    assert_eq!(info.node_count, 2);
+    assert_eq!(info.updated_at.as_deref(), Some("2024-01-01T00:00:00Z"));
+    assert!(matches!(info.vehicle_type, api::VehicleType::Http));
+}
+
+#[test]
+fn test_diff_selections() {
+    let old = HashMap::from([
+        ("GLOBAL".to_string(), "a".to_string()),
+        ("Proxy".to_string(), "b".to_string()),
+        ("Removed".to_string(), "c".to_string()),
+    ]);
+    let new = HashMap::from([
+        ("GLOBAL".to_string(), "a".to_string()),
+        ("Proxy".to_string(), "d".to_string()),
+        ("Added".to_string(), "e".to_string()),
+    ]);
+    assert_eq!(
+        diff_selections(&old, &new),
+        vec![("Proxy".to_string(), "b".to_string(), "d".to_string())]
+    );
+}
+
+#[test]
+fn test_proxy_chains() {
+    let group = |name: &str, r#type: &str, now: &str| ProxyGroupItem {
+        name: name.to_string(),
+        r#type: r#type.to_string(),
+        now: Some(now.to_string()),
+        ..Default::default()
+    };
+    let proxies = Proxies {
+        global: group("GLOBAL", "Selector", "select"),
+        groups: vec![
+            group("select", "Selector", "auto"),
+            group("auto", "URLTest", "node"),
+            group("loop-a", "Selector", "loop-b"),
+            group("loop-b", "Selector", "loop-a"),
+        ],
+        ..Default::default()
+    };
+
+    let chains = proxies.proxy_chains();
+    assert_eq!(
+        chains.keys().collect::<Vec<_>>(),
+        ["GLOBAL", "select", "loop-a", "loop-b"]
+    );
+    assert_eq!(chains["GLOBAL"], ["GLOBAL", "select", "auto", "node"]);
+    assert_eq!(chains["loop-a"], ["loop-a", "loop-b"]);
+}
+
+#[test]
+fn test_supports_group_mode() {
+    assert!(supports_group_mode(&ClashCore::Mihomo, "URLTest"));
+    assert!(supports_group_mode(&ClashCore::MihomoAlpha, "Fallback"));
+    assert!(!supports_group_mode(&ClashCore::Mihomo, "Selector"));
+    assert!(!supports_group_mode(&ClashCore::Mihomo, "LoadBalance"));
+    assert!(!supports_group_mode(&ClashCore::ClashPremium, "URLTest"));
+    assert!(!supports_group_mode(&ClashCore::ClashRs, "Fallback"));
+}
+
+#[tokio::test]
+async fn test_cancel_delay_tests() {
+    let control = DelayTestControl::global();
+    let generation = control.generation.load(Ordering::SeqCst);
+    let waiting = tokio::spawn(control.cancelled(generation));
+    tokio::task::yield_now().await;
+    assert!(!waiting.is_finished());
+
+    cancel_delay_tests();
+    tokio::time::timeout(std::time::Duration::from_secs(1), waiting)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(control.is_cancelled(generation));
+    // 取消之后开始的批次不受影响
+    assert!(!control.is_cancelled(control.generation.load(Ordering::SeqCst)));
 }
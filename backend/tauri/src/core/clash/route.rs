@@ -0,0 +1,160 @@
+use super::{
+    api::{self, RuleItem},
+    proxies::{ProxiesGuard, ProxiesGuardExt},
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::net::IpAddr;
+
+/// 访问某个域名或 IP 时匹配到的规则和代理链
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteResolution {
+    pub host: String,
+    /// 用于匹配 IP 规则的地址
+    pub ip: Option<IpAddr>,
+    /// 没有规则匹配时为 None
+    pub rule: Option<RuleItem>,
+    /// 从规则指定的代理或分组开始，依次为各分组当前选中的节点
+    pub chain: Vec<String>,
+    /// 排在匹配规则之前、无法在本地判断的规则（如 GEOSITE、RULE-SET），实际结果可能由它们决定
+    pub undetermined: Vec<RuleItem>,
+}
+
+/// 规则能否在本地判断，能判断时返回是否匹配
+fn match_rule(rule: &RuleItem, host: &str, ip: Option<IpAddr>) -> Option<bool> {
+    let host = host.to_ascii_lowercase();
+    let payload = rule.payload.to_ascii_lowercase();
+    match rule.r#type.as_str() {
+        "Domain" | "DOMAIN" => Some(host == payload),
+        "DomainSuffix" | "DOMAIN-SUFFIX" => {
+            Some(host == payload || host.ends_with(&format!(".{payload}")))
+        }
+        "DomainKeyword" | "DOMAIN-KEYWORD" => Some(host.contains(&payload)),
+        "IPCIDR" | "IPCIDR6" | "IP-CIDR" | "IP-CIDR6" => {
+            // 没有解析出地址时视为不匹配，与核心的 no-resolve 行为一致
+            Some(ip.is_some_and(|ip| cidr_contains(&payload, ip)))
+        }
+        "Match" | "MATCH" => Some(true),
+        _ => None,
+    }
+}
+
+fn cidr_contains(cidr: &str, ip: IpAddr) -> bool {
+    let Some((network, prefix)) = cidr.split_once('/') else {
+        return false;
+    };
+    let (Ok(network), Ok(prefix)) = (network.parse::<IpAddr>(), prefix.parse::<u32>()) else {
+        return false;
+    };
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// 按顺序找到第一条匹配的规则，并记录之前无法判断的规则
+fn match_rules(
+    rules: Vec<RuleItem>,
+    host: &str,
+    ip: Option<IpAddr>,
+) -> (Option<RuleItem>, Vec<RuleItem>) {
+    let mut undetermined = Vec::new();
+    for rule in rules {
+        match match_rule(&rule, host, ip) {
+            Some(true) => return (Some(rule), undetermined),
+            Some(false) => {}
+            None => undetermined.push(rule),
+        }
+    }
+    (None, undetermined)
+}
+
+/// 从 URL 中取出域名，直接传入域名或 IP 时原样返回
+fn parse_host(input: &str) -> String {
+    let input = input.trim();
+    let url = url::Url::parse(input).ok();
+    match url.as_ref().and_then(|url| url.host_str()) {
+        Some(host) => host.trim_matches(|c| c == '[' || c == ']').to_string(),
+        None => input.to_string(),
+    }
+}
+
+/// 使用核心当前的规则，推断访问 `host` 时使用的规则和代理链
+///
+/// 只在本地判断域名和 IP 类规则，无法判断的规则记录在 `undetermined` 中
+pub async fn resolve_route(host: &str) -> Result<RouteResolution> {
+    let host = parse_host(host);
+    let ip = match host.parse::<IpAddr>() {
+        Ok(ip) => Some(ip),
+        // 只有 Mihomo 支持通过外部控制接口解析域名，失败时不匹配 IP 规则
+        Err(_) => api::dns_query(&host).await.ok().and_then(|res| {
+            res.answer
+                .iter()
+                .find_map(|answer| answer.data.parse::<IpAddr>().ok())
+        }),
+    };
+
+    let rules = api::get_rules().await?.rules;
+    let (rule, undetermined) = match_rules(rules, &host, ip);
+
+    let chain = match &rule {
+        Some(rule) => {
+            let guard = ProxiesGuard::global();
+            if !guard.read().is_updated() {
+                guard.update().await?;
+            }
+            let proxies = guard.read();
+            proxies.inner().resolve_chain(&rule.proxy)
+        }
+        None => vec!["DIRECT".to_string()],
+    };
+
+    Ok(RouteResolution {
+        host,
+        ip,
+        rule,
+        chain,
+        undetermined,
+    })
+}
+
+#[test]
+fn test_match_rules() {
+    let rule = |r#type: &str, payload: &str, proxy: &str| RuleItem {
+        r#type: r#type.to_string(),
+        payload: payload.to_string(),
+        proxy: proxy.to_string(),
+    };
+    let rules = vec![
+        rule("DomainSuffix", "google.com", "Proxy"),
+        rule("GeoSite", "cn", "DIRECT"),
+        rule("IPCIDR", "10.0.0.0/8", "DIRECT"),
+        rule("Match", "", "Final"),
+    ];
+
+    let (matched, undetermined) = match_rules(rules.clone(), "www.google.com", None);
+    assert_eq!(matched.unwrap().proxy, "Proxy");
+    assert!(undetermined.is_empty());
+
+    let (matched, undetermined) = match_rules(rules.clone(), "10.1.2.3", "10.1.2.3".parse().ok());
+    assert_eq!(matched.unwrap().proxy, "DIRECT");
+    assert_eq!(undetermined.len(), 1);
+
+    let (matched, _) = match_rules(rules, "example.com", None);
+    assert_eq!(matched.unwrap().proxy, "Final");
+
+    assert!(cidr_contains("0.0.0.0/0", "1.2.3.4".parse().unwrap()));
+    assert!(!cidr_contains("fd00::/8", "1.2.3.4".parse().unwrap()));
+    assert_eq!(
+        parse_host("https://www.google.com/search"),
+        "www.google.com"
+    );
+}
@@ -0,0 +1,194 @@
+//! 订阅控制器的 `/traffic` 流，统计实时速率与累计流量
+use super::{
+    api::ControllerEndpoint,
+    core::{CoreManager, CoreState},
+};
+use crate::{config::Config, core::handle::Handle, log_err};
+use anyhow::Result;
+use futures::StreamExt;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+/// 连接断开后重连的最短与最长间隔
+const RECONNECT_MIN_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// 控制器每秒推送一次的速率，单位为字节每秒
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+struct TrafficRate {
+    up: u64,
+    down: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TrafficStats {
+    /// 当前上传速率
+    pub up: u64,
+    /// 当前下载速率
+    pub down: u64,
+    /// 订阅以来的累计上传量，由每秒速率累加得到
+    pub up_total: u64,
+    /// 订阅以来的累计下载量
+    pub down_total: u64,
+    /// 是否已连接到流量流
+    pub connected: bool,
+}
+
+impl TrafficStats {
+    fn record(&mut self, rate: TrafficRate) {
+        self.up = rate.up;
+        self.down = rate.down;
+        self.up_total = self.up_total.saturating_add(rate.up);
+        self.down_total = self.down_total.saturating_add(rate.down);
+        self.connected = true;
+    }
+}
+
+pub struct TrafficMonitor {
+    stats: Mutex<TrafficStats>,
+    started: AtomicBool,
+    /// 开始持续没有流量的时间
+    idle_since: Mutex<Option<Instant>>,
+}
+
+impl TrafficMonitor {
+    pub fn global() -> &'static TrafficMonitor {
+        static MONITOR: OnceCell<TrafficMonitor> = OnceCell::new();
+
+        MONITOR.get_or_init(|| TrafficMonitor {
+            stats: Mutex::new(TrafficStats::default()),
+            started: AtomicBool::new(false),
+            idle_since: Mutex::new(None),
+        })
+    }
+
+    pub fn stats(&self) -> TrafficStats {
+        self.stats.lock().clone()
+    }
+
+    /// 启动订阅任务，重复调用不会启动多个任务
+    pub fn init(&'static self) {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tauri::async_runtime::spawn(async move {
+            let mut delay = RECONNECT_MIN_DELAY;
+            loop {
+                // 核心未运行时不去连接，等待核心启动
+                if CoreManager::global()
+                    .wait_for_state(CoreState::Running, RECONNECT_MAX_DELAY)
+                    .await
+                    .is_err()
+                {
+                    continue;
+                }
+                match self.subscribe().await {
+                    Ok(()) => {
+                        log::debug!(target: "app", "traffic stream closed");
+                        delay = RECONNECT_MIN_DELAY;
+                    }
+                    Err(err) => {
+                        log::debug!(target: "app", "traffic stream failed: {err:?}");
+                    }
+                }
+                self.disconnected();
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+            }
+        });
+    }
+
+    /// 连接流量流并持续读取，直到流结束或出错
+    async fn subscribe(&self) -> Result<()> {
+        let endpoint = ControllerEndpoint::resolve();
+        let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+        let response = client
+            .get(format!("{}/traffic", endpoint.base_url()))
+            .headers(endpoint.headers()?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        // 重新连接时核心可能刚启动，重新计算空闲时间
+        *self.idle_since.lock() = None;
+        let mut stream = response.bytes_stream();
+        let mut buf = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            buf.extend_from_slice(&chunk?);
+            while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+                let line = buf.drain(..=pos).collect::<Vec<_>>();
+                if let Some(rate) = parse_traffic_line(&line) {
+                    let stats = {
+                        let mut stats = self.stats.lock();
+                        stats.record(rate);
+                        stats.clone()
+                    };
+                    Handle::traffic_update(&stats);
+                    self.check_idle(rate);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 持续没有流量达到 `idle_auto_stop_minutes` 时停止核心
+    fn check_idle(&self, rate: TrafficRate) {
+        let minutes = { Config::verge().latest().idle_auto_stop_minutes };
+        let limit = Duration::from_secs(minutes.unwrap_or(0) * 60);
+        let mut idle_since = self.idle_since.lock();
+        if limit.is_zero() || rate != TrafficRate::default() {
+            *idle_since = None;
+            return;
+        }
+        let since = *idle_since.get_or_insert_with(Instant::now);
+        if since.elapsed() >= limit {
+            *idle_since = None;
+            tauri::async_runtime::spawn(async {
+                log_err!(CoreManager::global().stop_for_idle().await);
+            });
+        }
+    }
+
+    fn disconnected(&self) {
+        let stats = {
+            let mut stats = self.stats.lock();
+            if !stats.connected {
+                return;
+            }
+            stats.up = 0;
+            stats.down = 0;
+            stats.connected = false;
+            stats.clone()
+        };
+        Handle::traffic_update(&stats);
+    }
+}
+
+fn parse_traffic_line(line: &[u8]) -> Option<TrafficRate> {
+    let line = std::str::from_utf8(line).ok()?.trim();
+    if line.is_empty() {
+        return None;
+    }
+    serde_json::from_str(line).ok()
+}
+
+#[test]
+fn test_traffic_stats_record() {
+    assert_eq!(
+        parse_traffic_line(b"{\"up\":10,\"down\":20}\n"),
+        Some(TrafficRate { up: 10, down: 20 })
+    );
+    assert_eq!(parse_traffic_line(b"\n"), None);
+
+    let mut stats = TrafficStats::default();
+    stats.record(TrafficRate { up: 10, down: 20 });
+    stats.record(TrafficRate { up: 5, down: 0 });
+    assert_eq!((stats.up, stats.down), (5, 0));
+    assert_eq!((stats.up_total, stats.down_total), (15, 20));
+    assert!(stats.connected);
+}
@@ -1,10 +1,36 @@
-use super::tray::Tray;
-use crate::log_err;
+use super::{
+    clash::{
+        core::{ChangeCoreProgress, ConfigApplyStatus, CoreBroken, CoreSwitchSuggestion},
+        proxies::{DelayTestResult, SelectionChange},
+        traffic::TrafficStats,
+    },
+    tray::{proxies::ProxiesNeverUpdated, Tray},
+};
+use crate::{config::Config, log_err, utils::resolve};
 use anyhow::{bail, Result};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
-use std::sync::Arc;
-use tauri::{AppHandle, Manager, Window};
+use rust_i18n::t;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tauri::{api::notification::Notification, AppHandle, Manager, Window};
+
+/// 一次连续崩溃期间的通知状态
+#[derive(Debug)]
+struct CrashEpisode {
+    crashes: u32,
+    last_notified: Option<Instant>,
+}
+
+static CRASH_EPISODE: Mutex<Option<CrashEpisode>> = Mutex::new(None);
+
+/// 主窗口已关闭，应用在后台运行
+static IN_BACKGROUND: AtomicBool = AtomicBool::new(false);
 
 #[derive(Debug, Default, Clone)]
 pub struct Handle {
@@ -62,6 +88,180 @@ impl Handle {
         }
     }
 
+    /// 启动后一直无法连接外部控制接口
+    pub fn proxies_never_updated(payload: &ProxiesNeverUpdated) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("proxies://never-updated", payload));
+        }
+    }
+
+    /// 核心反复恢复失败，建议切换到其他核心
+    pub fn suggest_switch_core(payload: &CoreSwitchSuggestion) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("nyanpasu://suggest-switch-core", payload));
+        }
+    }
+
+    /// 切换核心进入了新的阶段
+    pub fn change_core_progress(progress: &ChangeCoreProgress) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("nyanpasu://change-core-progress", progress));
+        }
+    }
+
+    /// 推送的配置已生效，或超时前未能确认
+    pub fn config_applied(status: ConfigApplyStatus) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("config://applied", status));
+        }
+    }
+
+    /// 分组选中的节点发生了变化，无论变化来自何处
+    pub fn selection_changed(change: &SelectionChange) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("nyanpasu://selection-changed", change));
+        }
+    }
+
+    /// 流量统计更新
+    pub fn traffic_update(stats: &TrafficStats) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("nyanpasu://traffic-update", stats));
+        }
+    }
+
+    /// 延迟测试批次开始或全部结束，包括被取消
+    pub fn delay_tests_changed(testing: bool) {
+        super::tray::proxies::update_delay_test_item();
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("nyanpasu://delay-tests-changed", testing));
+        }
+    }
+
+    /// 单个节点的延迟测试完成
+    pub fn delay_test_result(result: &DelayTestResult) {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("nyanpasu://delay-test-result", result));
+        }
+    }
+
+    pub fn is_in_background() -> bool {
+        IN_BACKGROUND.load(Ordering::SeqCst)
+    }
+
+    /// 窗口关闭或重新打开时更新后台运行的状态，状态变化时刷新托盘提示
+    /// 调用方可能持有 app_handle 的锁，所以由调用方传入
+    pub fn set_in_background(app_handle: &AppHandle, in_background: bool) {
+        if IN_BACKGROUND.swap(in_background, Ordering::SeqCst) != in_background {
+            log_err!(Tray::update_part(app_handle));
+        }
+    }
+
+    /// 打开主窗口并跳转到分组的代理页面
+    pub fn open_proxies_group(group: String) {
+        Self::open_window_with("nyanpasu://open-proxies-group", group);
+    }
+
+    /// 打开主窗口并跳转到托盘隐藏分组的设置
+    pub fn open_tray_groups_setting() {
+        Self::open_window_with("nyanpasu://manage-tray-groups", ());
+    }
+
+    /// 打开主窗口后向前端发送事件
+    fn open_window_with<S: serde::Serialize + Clone + Send + 'static>(
+        event: &'static str,
+        payload: S,
+    ) {
+        let Some(app_handle) = Self::global().app_handle.lock().clone() else {
+            return;
+        };
+        let exists = app_handle.get_window("main").is_some();
+        resolve::create_window(&app_handle);
+        if exists {
+            log_err!(app_handle.emit_all(event, payload));
+        } else {
+            // 新建的窗口需要等前端初始化完成才能收到事件
+            let handle = app_handle.clone();
+            app_handle.once_global("init-complete", move |_| {
+                log_err!(handle.emit_all(event, payload));
+            });
+        }
+    }
+
+    /// 已安装的核心无法运行，打开窗口让用户选择重新下载或切换核心
+    pub fn core_broken(payload: CoreBroken) {
+        Self::notify(
+            t!("notification.core_broken.title"),
+            t!("notification.core_broken.body", core = payload.core),
+        );
+        Self::open_window_with("nyanpasu://core-broken", payload);
+    }
+
+    /// 连续多次启动失败后以安全模式运行，引导用户修复配置
+    pub fn safe_mode(failures: u32) {
+        Self::notify(
+            t!("notification.safe_mode.title"),
+            t!("notification.safe_mode.body", failures = failures),
+        );
+        Self::open_window_with("nyanpasu://safe-mode", failures);
+    }
+
+    /// 没有安装任何核心，提示前端引导用户下载
+    pub fn no_core_installed() {
+        if let Some(window) = Self::global().get_window() {
+            log_err!(window.emit("verge://no-core-installed", "yes"));
+        }
+    }
+
+    /// 核心异常退出，窗口期内的重复通知会被合并，下次通知时附带崩溃次数
+    pub fn notify_core_crashed() {
+        let window = { Config::verge().latest().crash_notify_window };
+        let window = Duration::from_secs(window.unwrap_or(30));
+
+        let crashes = {
+            let mut episode = CRASH_EPISODE.lock();
+            let episode = episode.get_or_insert(CrashEpisode {
+                crashes: 0,
+                last_notified: None,
+            });
+            episode.crashes += 1;
+            if episode
+                .last_notified
+                .is_some_and(|last| last.elapsed() < window)
+            {
+                return;
+            }
+            episode.last_notified = Some(Instant::now());
+            episode.crashes
+        };
+
+        let body = match crashes {
+            1 => t!("notification.core_crashed.body"),
+            n => t!("notification.core_crashed.body_repeated", count = n),
+        };
+        Self::notify(t!("notification.core_crashed.title"), body);
+    }
+
+    /// 核心在崩溃后稳定运行，结束本次崩溃的通知合并
+    pub fn notify_core_recovered() {
+        let Some(episode) = CRASH_EPISODE.lock().take() else {
+            return;
+        };
+        Self::notify(
+            t!("notification.core_recovered.title"),
+            t!("notification.core_recovered.body", count = episode.crashes),
+        );
+    }
+
+    /// 发送系统通知，窗口未打开时也能提示用户
+    pub fn notify<T: Into<String>, B: Into<String>>(title: T, body: B) {
+        let app_handle = Self::global().app_handle.lock();
+        if let Some(app_handle) = app_handle.as_ref() {
+            let identifier = app_handle.config().tauri.bundle.identifier.clone();
+            log_err!(Notification::new(identifier).title(title).body(body).show());
+        }
+    }
+
     pub fn update_systray() -> Result<()> {
         let app_handle = Self::global().app_handle.lock();
         if app_handle.is_none() {
@@ -0,0 +1,222 @@
+#![cfg(target_os = "linux")]
+//! Linux 下 TUN 模式的系统 DNS 接管，与 macOS 的 networksetup 逻辑对应
+//! systemd-resolved 通过 resolvectl 设置 TUN 设备的 DNS，否则改写 `/etc/resolv.conf`
+use crate::utils::dirs;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// 写入 `resolv.conf` 的标记，恢复前据此确认文件没有被其他程序改写
+const MANAGED_HEADER: &str = "# Generated by Clash Nyanpasu, restored when the core stops";
+
+/// 等待 TUN 设备出现的最长时间
+const DEVICE_WAIT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 系统使用的 DNS 管理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsBackend {
+    SystemdResolved,
+    ResolvConf,
+}
+
+impl DnsBackend {
+    /// `resolv.conf` 指向 systemd-resolved 的 stub 且有 resolvectl 时使用 systemd-resolved
+    pub fn detect() -> Self {
+        let target = fs::read_link(RESOLV_CONF).ok();
+        let content = fs::read_to_string(RESOLV_CONF).unwrap_or_default();
+        if is_systemd_resolved(target.as_deref(), &content) && which::which("resolvectl").is_ok() {
+            DnsBackend::SystemdResolved
+        } else {
+            DnsBackend::ResolvConf
+        }
+    }
+}
+
+fn is_systemd_resolved(link_target: Option<&Path>, content: &str) -> bool {
+    link_target.is_some_and(|target| target.starts_with("/run/systemd/resolve"))
+        || content
+            .lines()
+            .any(|line| line.split_whitespace().eq(["nameserver", "127.0.0.53"]))
+}
+
+/// 修改前保存的状态，写入 `dirs::dns_recovery_path`，恢复后删除
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+enum DnsRecovery {
+    /// 设置了该设备的 DNS，设备不存在时无需恢复
+    SystemdResolved {
+        device: Option<String>,
+    },
+    ResolvConf {
+        original: String,
+    },
+}
+
+/// 将系统 DNS 指向 TUN 设备的地址
+/// `device` 为配置中指定的 TUN 设备名，未指定时使用最先出现的 TUN 设备
+pub fn set_system_dns(tun_device_ip: &str, device: Option<String>) -> Result<()> {
+    let recovery = dirs::dns_recovery_path()?;
+    // 已有保存的设置时说明当前已被覆盖，不能把 TUN 的地址当作原始设置
+    if recovery.exists() {
+        log::debug!(target: "app", "system dns is already overridden");
+        return Ok(());
+    }
+
+    match DnsBackend::detect() {
+        DnsBackend::SystemdResolved => {
+            save_recovery(&DnsRecovery::SystemdResolved {
+                device: device.clone(),
+            })?;
+            // 此时核心还没有创建 TUN 设备，设备出现后再设置
+            let tun_device_ip = tun_device_ip.to_string();
+            std::thread::spawn(move || {
+                let Some(device) = wait_for_tun_device(device.as_deref()) else {
+                    log::error!(target: "app", "tun device not found, skip setting the system dns");
+                    return;
+                };
+                if let Err(err) = resolvectl_set(&device, &tun_device_ip) {
+                    log::error!(target: "app", "failed to set system dns: {err:?}");
+                }
+                // 等待期间核心已经停止并恢复过时不再记录
+                if dirs::dns_recovery_path().is_ok_and(|path| path.exists()) {
+                    crate::log_err!(save_recovery(&DnsRecovery::SystemdResolved {
+                        device: Some(device)
+                    }));
+                }
+            });
+        }
+        DnsBackend::ResolvConf => {
+            let original =
+                fs::read_to_string(RESOLV_CONF).context("failed to read the resolv.conf")?;
+            save_recovery(&DnsRecovery::ResolvConf { original })?;
+            if let Err(err) = fs::write(RESOLV_CONF, managed_resolv_conf(tun_device_ip)) {
+                let _ = fs::remove_file(recovery);
+                return Err(err).context("failed to write the resolv.conf");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 恢复系统 DNS，没有保存的设置时什么都不做
+pub fn restore_system_dns() -> Result<()> {
+    let recovery = dirs::dns_recovery_path()?;
+    let saved = match fs::read_to_string(&recovery) {
+        Ok(saved) => saved,
+        Err(_) => return Ok(()),
+    };
+    match serde_json::from_str::<DnsRecovery>(&saved) {
+        Ok(DnsRecovery::SystemdResolved {
+            device: Some(device),
+        }) => {
+            // 核心崩溃后设备已被移除，resolved 中的设置随之消失
+            if Path::new("/sys/class/net").join(&device).exists() {
+                run("resolvectl", &["revert", &device])?;
+            }
+        }
+        Ok(DnsRecovery::SystemdResolved { device: None }) => {}
+        Ok(DnsRecovery::ResolvConf { original }) => {
+            let current = fs::read_to_string(RESOLV_CONF).unwrap_or_default();
+            // 期间被其他程序改写时保留其设置
+            if current.starts_with(MANAGED_HEADER) {
+                fs::write(RESOLV_CONF, original).context("failed to restore the resolv.conf")?;
+            } else {
+                log::warn!(target: "app", "resolv.conf is changed by others, skip restoring");
+            }
+        }
+        Err(err) => log::error!(target: "app", "invalid dns recovery file: {err}"),
+    }
+    let _ = fs::remove_file(recovery);
+    Ok(())
+}
+
+fn save_recovery(recovery: &DnsRecovery) -> Result<()> {
+    fs::write(dirs::dns_recovery_path()?, serde_json::to_string(recovery)?)
+        .context("failed to save the system dns settings")
+}
+
+fn managed_resolv_conf(tun_device_ip: &str) -> String {
+    format!("{MANAGED_HEADER}\nnameserver {tun_device_ip}\n")
+}
+
+/// 设置设备的 DNS，并让所有域名都通过该设备解析
+fn resolvectl_set(device: &str, tun_device_ip: &str) -> Result<()> {
+    run("resolvectl", &["dns", device, tun_device_ip])?;
+    run("resolvectl", &["domain", device, "~."])?;
+    run("resolvectl", &["default-route", device, "true"])?;
+    Ok(())
+}
+
+fn run(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program).args(args).output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`{program} {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// 等待 TUN 设备出现，超时返回 None
+fn wait_for_tun_device(device: Option<&str>) -> Option<String> {
+    let started = Instant::now();
+    while started.elapsed() < DEVICE_WAIT_TIMEOUT {
+        let found = match device {
+            Some(device) => Path::new("/sys/class/net")
+                .join(device)
+                .exists()
+                .then(|| device.to_string()),
+            None => find_tun_device(Path::new("/sys/class/net")),
+        };
+        if found.is_some() {
+            return found;
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    None
+}
+
+/// TUN 设备在 sysfs 中有 `tun_flags` 文件
+fn find_tun_device(net_dir: &Path) -> Option<String> {
+    fs::read_dir(net_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| entry.path().join("tun_flags").exists())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+}
+
+#[test]
+fn test_detect_systemd_resolved() {
+    assert!(is_systemd_resolved(
+        Some(Path::new("/run/systemd/resolve/stub-resolv.conf")),
+        ""
+    ));
+    assert!(is_systemd_resolved(
+        None,
+        "# comment\nnameserver 127.0.0.53\noptions edns0\n"
+    ));
+    assert!(!is_systemd_resolved(None, "nameserver 1.1.1.1\n"));
+    assert!(!is_systemd_resolved(
+        Some(Path::new("/run/NetworkManager/resolv.conf")),
+        "nameserver 192.168.1.1\n"
+    ));
+
+    let recovery = DnsRecovery::ResolvConf {
+        original: "nameserver 1.1.1.1\n".to_string(),
+    };
+    let saved = serde_json::to_string(&recovery).unwrap();
+    assert_eq!(
+        serde_json::from_str::<DnsRecovery>(&saved).unwrap(),
+        recovery
+    );
+    assert!(managed_resolv_conf("198.18.0.2").starts_with(MANAGED_HEADER));
+}
@@ -1,36 +1,143 @@
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
-use std::{collections::VecDeque, sync::Arc};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc,
+    },
+};
+use tokio::sync::oneshot;
 
 const LOGS_QUEUE_LEN: usize = 100;
 
+/// 等待写入的日志行数上限，超出后丢弃新的日志
+const LOGS_CHANNEL_LEN: usize = 1024;
+
+enum LogMessage {
+    /// 提交时的清空次数与日志内容
+    Line(u64, String),
+    /// 之前的消息都写入后回复
+    Flush(oneshot::Sender<()>),
+}
+
+/// 日志由单独的线程写入，所有方法都不会阻塞调用的线程
 pub struct Logger {
     log_data: Arc<Mutex<VecDeque<String>>>,
+    sender: SyncSender<LogMessage>,
+    /// 通道已满时丢弃的日志行数
+    dropped: Arc<AtomicUsize>,
+    /// 清空的次数，写入时丢弃清空前提交的日志
+    generation: Arc<AtomicU64>,
 }
 
 impl Logger {
     pub fn global() -> &'static Logger {
         static LOGGER: OnceCell<Logger> = OnceCell::new();
 
-        LOGGER.get_or_init(|| Logger {
-            log_data: Arc::new(Mutex::new(VecDeque::with_capacity(LOGS_QUEUE_LEN + 10))),
-        })
+        LOGGER.get_or_init(Logger::new)
+    }
+
+    fn new() -> Logger {
+        let log_data = Arc::new(Mutex::new(VecDeque::with_capacity(LOGS_QUEUE_LEN + 10)));
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let generation = Arc::new(AtomicU64::new(0));
+        let (sender, receiver) = mpsc::sync_channel(LOGS_CHANNEL_LEN);
+        {
+            let log_data = log_data.clone();
+            let dropped = dropped.clone();
+            let generation = generation.clone();
+            std::thread::Builder::new()
+                .name("logger".into())
+                .spawn(move || Self::write(receiver, log_data, dropped, generation))
+                .expect("failed to spawn the logger thread");
+        }
+        Logger {
+            log_data,
+            sender,
+            dropped,
+            generation,
+        }
+    }
+
+    fn write(
+        receiver: Receiver<LogMessage>,
+        log_data: Arc<Mutex<VecDeque<String>>>,
+        dropped: Arc<AtomicUsize>,
+        generation: Arc<AtomicU64>,
+    ) {
+        let push = |logs: &mut VecDeque<String>, text: String| {
+            if logs.len() > LOGS_QUEUE_LEN {
+                logs.pop_front();
+            }
+            logs.push_back(text);
+        };
+        for message in receiver {
+            match message {
+                LogMessage::Line(line_generation, text) => {
+                    let mut logs = log_data.lock();
+                    // 持有锁时检查，避免与 `clear_log` 交错
+                    if line_generation != generation.load(Ordering::SeqCst) {
+                        continue;
+                    }
+                    // 丢弃的日志合并为一行提示
+                    let count = dropped.swap(0, Ordering::Relaxed);
+                    if count > 0 {
+                        push(
+                            &mut logs,
+                            format!("[nyanpasu] {count} log lines are dropped"),
+                        );
+                    }
+                    push(&mut logs, text);
+                }
+                LogMessage::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+
+    /// 等待已提交的日志写入完成，通道已满时不等待
+    async fn flush(&self) {
+        let (done, wait) = oneshot::channel();
+        if self.sender.try_send(LogMessage::Flush(done)).is_ok() {
+            let _ = wait.await;
+        }
     }
 
-    pub fn get_log(&self) -> VecDeque<String> {
+    pub async fn get_log(&self) -> VecDeque<String> {
+        self.flush().await;
         self.log_data.lock().clone()
     }
 
+    /// 写入跟不上时丢弃日志
     pub fn set_log(&self, text: String) {
-        let mut logs = self.log_data.lock();
-        if logs.len() > LOGS_QUEUE_LEN {
-            logs.pop_front();
+        let generation = self.generation.load(Ordering::SeqCst);
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(LogMessage::Line(generation, text))
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
         }
-        logs.push_back(text);
     }
 
+    /// 立即清空，尚未写入的日志也会被丢弃
     pub fn clear_log(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
         let mut logs = self.log_data.lock();
+        self.dropped.store(0, Ordering::Relaxed);
         logs.clear();
     }
 }
+
+#[test]
+fn test_logger_keeps_order() {
+    let logger = Logger::new();
+    logger.set_log("first".into());
+    logger.clear_log();
+    logger.set_log("second".into());
+    logger.set_log("third".into());
+    assert_eq!(
+        tauri::async_runtime::block_on(logger.get_log()),
+        ["second", "third"]
+    );
+}
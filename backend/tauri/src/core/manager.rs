@@ -1,4 +1,204 @@
-use std::borrow::Cow;
+use crate::{
+    config::{nyanpasu::ClashCore, Config},
+    utils::{
+        dirs::{self, CoreSource},
+        resolve,
+    },
+};
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// 随应用分发的核心校验清单
+/// 格式: { "mihomo": { "v1.18.0": "<sha256>" } }
+const CORE_CHECKSUMS_MANIFEST: &str = "core-checksums.json";
+
+/// 启动前检查核心文件是否完整
+/// 文件为空或不可执行时直接拒绝；开启 `verify_core_checksum` 后再与清单中该版本的 SHA256 比对
+pub fn check_core_binary(core: &ClashCore, path: &Path) -> Result<()> {
+    let metadata = fs::metadata(path)
+        .with_context(|| format!("failed to read core binary \"{}\"", path.display()))?;
+    if metadata.len() == 0 {
+        bail!("core binary corrupted, please re-download `{core}`");
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            bail!("core binary \"{}\" is not executable", path.display());
+        }
+    }
+
+    let verify_checksum = { Config::verge().latest().verify_core_checksum };
+    if !verify_checksum.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let manifest = match dirs::app_resources_dir().map(|dir| dir.join(CORE_CHECKSUMS_MANIFEST)) {
+        Ok(path) if path.exists() => path,
+        _ => {
+            log::debug!(target: "app", "core checksums manifest not found, skip verification");
+            return Ok(());
+        }
+    };
+    let manifest: HashMap<String, HashMap<String, String>> =
+        serde_json::from_str(&fs::read_to_string(manifest)?)
+            .context("failed to parse the core checksums manifest")?;
+    let known_sums = match manifest.get(&core.to_string()) {
+        Some(sums) if !sums.is_empty() => sums,
+        _ => return Ok(()),
+    };
+
+    // 核心无法报告版本时按固定的版本比对
+    let version = match binary_version(core, path) {
+        Ok(version) => version,
+        Err(err) => pinned_version(core).ok_or(err)?,
+    };
+    let Some(expected) = version_checksum(known_sums, &version) else {
+        log::debug!(target: "app", "no checksum for `{core}` {version}, skip verification");
+        return Ok(());
+    };
+
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    let checksum = hex::encode(hasher.finalize());
+
+    if !expected.eq_ignore_ascii_case(&checksum) {
+        bail!("core binary corrupted, please re-download `{core}`");
+    }
+    log::debug!(target: "app", "core `{core}` matches the checksum of {version}");
+    Ok(())
+}
+
+/// 清单中指定版本的 SHA256，忽略版本号前的 v
+fn version_checksum<'a>(sums: &'a HashMap<String, String>, version: &str) -> Option<&'a String> {
+    sums.iter()
+        .find(|(v, _)| v.trim_start_matches('v') == version.trim_start_matches('v'))
+        .map(|(_, sum)| sum)
+}
+
+/// 核心可执行文件的版本，以路径、大小与修改时间为键，文件被替换后重新获取
+type VersionKey = (PathBuf, u64, Option<SystemTime>);
+
+static VERSION_CACHE: Lazy<Mutex<HashMap<VersionKey, String>>> = Lazy::new(Default::default);
+
+/// 获取核心可执行文件的版本，同一文件只会运行一次
+pub fn binary_version(core: &ClashCore, path: &Path) -> Result<String> {
+    let metadata = fs::metadata(path)?;
+    let key = (path.to_path_buf(), metadata.len(), metadata.modified().ok());
+    if let Some(version) = VERSION_CACHE.lock().get(&key) {
+        return Ok(version.clone());
+    }
+    let version = resolve::resolve_binary_version(core, path)?;
+    VERSION_CACHE.lock().insert(key, version.clone());
+    Ok(version)
+}
+
+/// 查找启动核心时使用的可执行文件，存在多份时优先使用固定的版本
+pub fn find_binary_path(core: &ClashCore) -> Result<PathBuf> {
+    let path = dirs::find_binary_path(core)?;
+    let Some(version) = pinned_version(core) else {
+        return Ok(path);
+    };
+    let candidates = dirs::find_binary_paths(core);
+    if candidates.len() < 2 {
+        return Ok(path);
+    }
+    let pinned = candidates.into_iter().find(|(_, path)| {
+        binary_version(core, path)
+            .is_ok_and(|v| v.trim_start_matches('v') == version.trim_start_matches('v'))
+    });
+    Ok(match pinned {
+        Some((source, path)) => {
+            log::debug!(target: "app", "use the pinned `{core}` {version} from {source:?}: {path:?}");
+            path
+        }
+        None => path,
+    })
+}
+
+/// 当前实际使用的核心的版本
+pub fn resolve_core_version(core: &ClashCore) -> Result<String> {
+    let binary = find_binary_path(core)?;
+    binary_version(core, &binary)
+}
+
+/// 已安装的核心
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledCore {
+    pub core_type: ClashCore,
+    pub path: PathBuf,
+    /// 无法获取版本时为 None
+    pub version: Option<String>,
+    pub source: CoreSource,
+    /// 是否为当前配置实际使用的核心
+    pub active: bool,
+}
+
+/// 列出所有目录中已安装的核心及其版本
+pub fn list_installed_cores() -> Vec<InstalledCore> {
+    let current = { Config::verge().latest().clash_core.clone() };
+    let current = current.unwrap_or(ClashCore::ClashPremium);
+
+    ClashCore::ALL
+        .iter()
+        .flat_map(|core| {
+            // 同一核心存在多份时只会使用其中一份
+            let used = find_binary_path(core).ok();
+            dirs::find_binary_paths(core)
+                .into_iter()
+                .map(|(source, path)| InstalledCore {
+                    core_type: core.clone(),
+                    version: binary_version(core, &path).ok(),
+                    active: *core == current && used.as_ref() == Some(&path),
+                    path,
+                    source,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// 固定核心的版本，阻止更新并优先使用该版本
+pub fn pin_core(core: ClashCore, version: String) -> Result<()> {
+    let verge = Config::verge();
+    let mut verge = verge.data();
+    verge
+        .pinned_core_versions
+        .get_or_insert_with(Default::default)
+        .insert(core, version);
+    verge.save_file()
+}
+
+pub fn unpin_core(core: &ClashCore) -> Result<()> {
+    let verge = Config::verge();
+    let mut verge = verge.data();
+    if let Some(pinned) = verge.pinned_core_versions.as_mut() {
+        pinned.remove(core);
+    }
+    verge.save_file()
+}
+
+/// 核心被固定的版本
+pub fn pinned_version(core: &ClashCore) -> Option<String> {
+    let verge = Config::verge();
+    let verge = verge.latest();
+    verge
+        .pinned_core_versions
+        .as_ref()
+        .and_then(|pinned| pinned.get(core).cloned())
+}
 
 /// 给clash内核的tun模式授权
 #[cfg(any(target_os = "macos", target_os = "linux"))]
@@ -80,3 +280,15 @@ pub fn escape(text: &str) -> Cow<'_, str> {
         Cow::Borrowed(std::str::from_utf8(bytes).unwrap())
     }
 }
+
+#[test]
+fn test_version_checksum() {
+    let sums = HashMap::from([
+        ("v1.18.5".to_string(), "aaa".to_string()),
+        ("v1.18.6".to_string(), "bbb".to_string()),
+    ]);
+    // 只使用该版本的校验值，其他版本的不算匹配
+    assert_eq!(version_checksum(&sums, "1.18.6").unwrap(), "bbb");
+    assert_eq!(version_checksum(&sums, "v1.18.5").unwrap(), "aaa");
+    assert!(version_checksum(&sums, "v1.18.7").is_none());
+}
@@ -2,6 +2,7 @@ pub mod clash;
 pub mod commands;
 pub mod handle;
 pub mod hotkey;
+pub mod linux_dns;
 pub mod logger;
 pub mod manager;
 pub mod storage;
@@ -9,6 +10,7 @@ pub mod sysopt;
 pub mod tasks;
 pub mod tray;
 pub mod updater;
+pub mod watcher;
 pub mod win_service;
 pub mod win_uwp;
 pub use self::clash::core::*;
@@ -286,6 +286,11 @@ impl Sysopt {
                 // update duration
                 wait_secs = guard_duration;
 
+                // 核心因空闲停止时系统代理被有意撤下
+                if crate::core::CoreManager::global().is_idle_stopped() {
+                    continue;
+                }
+
                 log::debug!(target: "app", "try to guard the system proxy");
 
                 let port = {
@@ -1,4 +1,9 @@
-use crate::{cmds, config::Config, feat, utils, utils::resolve};
+use crate::{
+    cmds,
+    config::{profile::item_type::ProfileItemType, Config},
+    feat, utils,
+    utils::resolve,
+};
 use anyhow::Result;
 use rust_i18n::t;
 use tauri::{
@@ -12,6 +17,34 @@ pub mod proxies;
 pub use self::icon::on_scale_factor_changed;
 use self::proxies::SystemTrayMenuProxiesExt;
 
+/// 托盘中切换配置菜单项的 id 前缀
+const PROFILE_ITEM_PREFIX: &str = "select_profile_";
+
+/// 可以被选为当前配置的订阅与本地配置
+fn selectable_profiles() -> Vec<(String, String)> {
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    profiles
+        .get_items()
+        .map(|items| {
+            items
+                .iter()
+                .filter(|item| {
+                    matches!(
+                        item.r#type,
+                        Some(ProfileItemType::Remote | ProfileItemType::Local)
+                    )
+                })
+                .filter_map(|item| {
+                    let uid = item.uid.clone()?;
+                    let name = item.name.clone().unwrap_or_else(|| uid.clone());
+                    Some((uid, name))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 pub struct Tray {}
 
 impl Tray {
@@ -22,17 +55,22 @@ impl Tray {
         SystemTrayMenu::new()
             .add_item(CustomMenuItem::new("open_window", t!("tray.dashboard")))
             .setup_proxies() // Setup the proxies menu
+            .setup_profiles()
             .add_native_item(SystemTrayMenuItem::Separator)
-            .add_item(CustomMenuItem::new("rule_mode", t!("tray.rule_mode")))
-            .add_item(CustomMenuItem::new("global_mode", t!("tray.global_mode")))
-            .add_item(CustomMenuItem::new("direct_mode", t!("tray.direct_mode")))
-            .add_item(CustomMenuItem::new("script_mode", t!("tray.script_mode")))
+            .add_submenu(SystemTraySubmenu::new(
+                t!("tray.mode"),
+                SystemTrayMenu::new()
+                    .add_item(CustomMenuItem::new("rule_mode", t!("tray.rule_mode")))
+                    .add_item(CustomMenuItem::new("global_mode", t!("tray.global_mode")))
+                    .add_item(CustomMenuItem::new("direct_mode", t!("tray.direct_mode")))
+                    .add_item(CustomMenuItem::new("script_mode", t!("tray.script_mode"))),
+            ))
             .add_native_item(SystemTrayMenuItem::Separator)
             .add_item(CustomMenuItem::new("system_proxy", t!("tray.system_proxy")))
             .add_item(CustomMenuItem::new("tun_mode", t!("tray.tun_mode")))
-            .add_item(CustomMenuItem::new("copy_env_sh", t!("tray.copy_env.sh")))
-            .add_item(CustomMenuItem::new("copy_env_cmd", t!("tray.copy_env.cmd")))
-            .add_item(CustomMenuItem::new("copy_env_ps", t!("tray.copy_env.ps")))
+            .add_item(CustomMenuItem::new("copy_env", t!("tray.copy_env")))
+            .add_item(CustomMenuItem::new("flush_dns", t!("tray.flush_dns")))
+            .add_item(CustomMenuItem::new("restart_core", t!("tray.restart_core")))
             .add_submenu(SystemTraySubmenu::new(
                 t!("tray.open_dir.menu"),
                 SystemTrayMenu::new()
@@ -88,6 +126,14 @@ impl Tray {
         let _ = tray.get_item("direct_mode").set_selected(mode == "direct");
         let _ = tray.get_item("script_mode").set_selected(mode == "script");
 
+        let current = { Config::profiles().latest().get_current() };
+        for (uid, _) in selectable_profiles() {
+            let selected = current.as_ref() == Some(&uid);
+            let _ = tray
+                .get_item(&format!("{PROFILE_ITEM_PREFIX}{uid}"))
+                .set_selected(selected);
+        }
+
         let verge = Config::verge();
         let verge = verge.latest();
         let system_proxy = verge.enable_system_proxy.as_ref().unwrap_or(&false);
@@ -111,6 +157,15 @@ impl Tray {
         let _ = tray.get_item("system_proxy").set_selected(*system_proxy);
         let _ = tray.get_item("tun_mode").set_selected(*tun_mode);
 
+        // 已有核心操作进行中时禁用重启
+        let busy = crate::core::CoreManager::global().try_lock_op().is_none();
+        let restart_core = tray.get_item("restart_core");
+        let _ = restart_core.set_enabled(!busy);
+        let _ = restart_core.set_title(match busy {
+            true => t!("tray.restarting_core"),
+            false => t!("tray.restart_core"),
+        });
+
         #[cfg(not(target_os = "linux"))]
         {
             let switch_map = {
@@ -120,13 +175,18 @@ impl Tray {
                 map
             };
 
-            let _ = tray.set_tooltip(&format!(
+            let mut tooltip = format!(
                 "{}: {}\n{}: {}",
                 t!("tray.system_proxy"),
                 switch_map[system_proxy],
                 t!("tray.tun_mode"),
                 switch_map[tun_mode]
-            ));
+            );
+            if crate::core::handle::Handle::is_in_background() {
+                tooltip.push('\n');
+                tooltip.push_str(&t!("tray.running_in_background"));
+            }
+            let _ = tray.set_tooltip(&tooltip);
         }
 
         Ok(())
@@ -144,19 +204,20 @@ impl Tray {
                 "open_window" => resolve::create_window(app_handle),
                 "system_proxy" => feat::toggle_system_proxy(),
                 "tun_mode" => feat::toggle_tun_mode(),
-                "copy_env_sh" => feat::copy_clash_env("sh"),
-                #[cfg(target_os = "windows")]
-                "copy_env_cmd" => feat::copy_clash_env("cmd"),
-                #[cfg(target_os = "windows")]
-                "copy_env_ps" => feat::copy_clash_env("ps"),
+                "copy_env" => feat::copy_clash_env(),
+                "flush_dns" => feat::flush_dns_cache_from_tray(),
                 "open_app_dir" => crate::log_err!(cmds::open_app_dir()),
                 "open_core_dir" => crate::log_err!(cmds::open_core_dir()),
                 "open_logs_dir" => crate::log_err!(cmds::open_logs_dir()),
                 "restart_clash" => feat::restart_clash_core(),
                 "restart_app" => utils::help::restart_application(app_handle),
+                "restart_core" => feat::restart_core_from_tray(),
                 "quit" => {
                     utils::help::quit_application(app_handle);
                 }
+                id if id.starts_with(PROFILE_ITEM_PREFIX) => {
+                    feat::switch_profile(id[PROFILE_ITEM_PREFIX.len()..].to_string());
+                }
                 _ => {
                     proxies::on_system_tray_event(&id);
                 }
@@ -169,3 +230,26 @@ impl Tray {
         }
     }
 }
+
+trait SystemTrayMenuProfilesExt {
+    fn setup_profiles(self) -> Self;
+}
+
+impl SystemTrayMenuProfilesExt for SystemTrayMenu {
+    fn setup_profiles(self) -> Self {
+        let profiles = selectable_profiles();
+        if profiles.is_empty() {
+            return self;
+        }
+        let menu = profiles
+            .into_iter()
+            .fold(SystemTrayMenu::new(), |menu, (uid, name)| {
+                menu.add_item(CustomMenuItem::new(
+                    format!("{PROFILE_ITEM_PREFIX}{uid}"),
+                    name,
+                ))
+            });
+        self.add_native_item(SystemTrayMenuItem::Separator)
+            .add_submenu(SystemTraySubmenu::new(t!("tray.profiles"), menu))
+    }
+}
@@ -1,19 +1,78 @@
 use crate::{
     config::Config,
     core::{
-        clash::proxies::{Proxies, ProxiesGuard, ProxiesGuardExt},
+        clash::{
+            api,
+            proxies::{
+                cancel_delay_tests, is_delay_testing, DelayTestCancelled, Proxies, ProxiesGuard,
+                ProxiesGuardExt, ResolvedNode, SelectionOrigin,
+            },
+        },
         handle::Handle,
     },
+    feat, log_err,
 };
 use anyhow::Context;
 use base64::{engine::general_purpose::STANDARD as base64_standard, Engine as _};
 use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 use tauri::SystemTrayMenu;
+use tokio::sync::broadcast;
 use tracing::{debug, error, warn};
 use tracing_attributes::instrument;
 
+/// 连接外部控制接口失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControllerErrorKind {
+    /// 密钥错误
+    Unauthorized,
+    ConnectionRefused,
+    Timeout,
+    Other,
+}
+
+impl From<&anyhow::Error> for ControllerErrorKind {
+    /// 错误可能被添加了上下文，需要查找整个错误链
+    fn from(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if cause.downcast_ref::<api::ControllerAuthError>().is_some() {
+                return Self::Unauthorized;
+            }
+            match cause.downcast_ref::<reqwest::Error>() {
+                Some(err) if err.is_timeout() => return Self::Timeout,
+                Some(err) if err.is_connect() => return Self::ConnectionRefused,
+                Some(err) if err.status() == Some(reqwest::StatusCode::UNAUTHORIZED) => {
+                    return Self::Unauthorized
+                }
+                _ => {}
+            }
+        }
+        Self::Other
+    }
+}
+
+/// 启动后一直无法获取代理信息时发送给前端
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxiesNeverUpdated {
+    pub kind: ControllerErrorKind,
+    /// 最近一次更新失败的错误信息
+    pub error: String,
+    pub elapsed_secs: u64,
+}
+
 #[instrument]
 async fn loop_task() {
+    let started_at = Instant::now();
+    let mut last_error = None;
+    let mut reported = false;
     loop {
         match ProxiesGuard::global().update().await {
             Ok(_) => {
@@ -21,13 +80,30 @@ async fn loop_task() {
             }
             Err(e) => {
                 warn!("update proxies failed: {:?}", e);
+                last_error = Some(e);
             }
         }
+        if let Err(e) = feat::sync_clash_mode().await {
+            warn!("sync clash mode failed: {:?}", e);
+        }
         {
             let guard = ProxiesGuard::global().read();
-            if guard.updated_at() == 0 {
-                error!("proxies not updated yet!!!!");
-                // TODO: add a error dialog or notification, and panic?
+            let grace_period = { Config::verge().latest().proxies_grace_period };
+            let grace_period = Duration::from_secs(grace_period.unwrap_or(30));
+            if guard.updated_at() == 0 && !reported && started_at.elapsed() >= grace_period {
+                if let Some(err) = last_error.as_ref() {
+                    error!(
+                        "proxies not updated after {:?}: {:?}",
+                        started_at.elapsed(),
+                        err
+                    );
+                    Handle::proxies_never_updated(&ProxiesNeverUpdated {
+                        kind: err.into(),
+                        error: format!("{err:?}"),
+                        elapsed_secs: started_at.elapsed().as_secs(),
+                    });
+                    reported = true;
+                }
             }
 
             // else {
@@ -36,30 +112,137 @@ async fn loop_task() {
             //     debug!(target: "tray", "proxies info: {:?}", str);
             // }
         }
-        tokio::time::sleep(tokio::time::Duration::from_secs(10)).await; // TODO: add a config to control the interval
+        let interval = { Config::verge().latest().proxies_update_interval };
+        tokio::time::sleep(Duration::from_secs(interval.unwrap_or(10).max(1))).await;
     }
 }
 
 type GroupName = String;
 type FromProxy = String;
 type ToProxy = String;
-type ProxySelectAction = (GroupName, FromProxy, ToProxy);
-#[derive(PartialEq)]
-enum TrayUpdateType {
+pub type ProxySelectAction = (GroupName, FromProxy, ToProxy);
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "actions", rename_all = "snake_case")]
+pub enum TrayUpdateType {
     None,
     Full,
     Part(Vec<ProxySelectAction>),
 }
 
-struct TrayProxyItem {
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TrayProxyItem {
     current: Option<String>,
     all: Vec<String>,
-    r#type: String, // TODO: 转成枚举
+    r#type: String,                 // TODO: 转成枚举
+    resolved: Option<ResolvedNode>, // 自动选择类型分组实际使用的节点
+}
+pub type TrayProxies = IndexMap<String, TrayProxyItem>;
+
+/// 与托盘使用同一套 diff 逻辑的结果，供前端复用
+#[derive(Debug, Serialize)]
+pub struct ProxiesDelta {
+    pub update: TrayUpdateType,
+    /// 当前的快照，下次调用时作为 previous 传入
+    pub snapshot: TrayProxies,
+}
+
+/// 计算当前代理相对于 `previous` 快照的变化
+pub fn proxies_delta(previous: &TrayProxies) -> ProxiesDelta {
+    let mode = crate::utils::config::get_current_clash_mode();
+    let order = TrayOrder::from_runtime();
+    let snapshot = to_tray_proxies(mode.as_str(), ProxiesGuard::global().read().inner(), &order);
+    ProxiesDelta {
+        update: diff_proxies(previous, &snapshot),
+        snapshot,
+    }
+}
+
+/// 配置中定义的分组与节点顺序
+/// 部分核心每次返回的顺序不同，按配置排序后才能避免托盘因顺序变化而全量更新
+#[derive(Debug, Default)]
+struct TrayOrder {
+    groups: Vec<String>,
+    /// 各分组在配置中列出的节点
+    nodes: HashMap<String, Vec<String>>,
+    proxies: Vec<String>,
+}
+
+impl TrayOrder {
+    fn from_config(config: &Mapping) -> Self {
+        let names = |key: &str| -> Vec<&Mapping> {
+            config
+                .get(key)
+                .and_then(|v| v.as_sequence())
+                .map(|seq| seq.iter().filter_map(|v| v.as_mapping()).collect())
+                .unwrap_or_default()
+        };
+        let name = |item: &Mapping| item.get("name")?.as_str().map(String::from);
+
+        let groups = names("proxy-groups");
+        TrayOrder {
+            groups: groups.iter().filter_map(|&group| name(group)).collect(),
+            nodes: groups
+                .iter()
+                .filter_map(|&group| {
+                    let nodes = group
+                        .get("proxies")?
+                        .as_sequence()?
+                        .iter()
+                        .filter_map(|v| v.as_str().map(String::from))
+                        .collect();
+                    Some((name(group)?, nodes))
+                })
+                .collect(),
+            proxies: names("proxies").iter().filter_map(|&p| name(p)).collect(),
+        }
+    }
+
+    fn from_runtime() -> Self {
+        let runtime = Config::runtime();
+        let runtime = runtime.latest();
+        runtime
+            .config
+            .as_ref()
+            .map(Self::from_config)
+            .unwrap_or_default()
+    }
+
+    /// 节点在分组中的顺序，托盘中的 global 对应配置中的 GLOBAL
+    fn node_order(&self, group: &str) -> Vec<&String> {
+        let group = match group {
+            "global" => "GLOBAL",
+            group => group,
+        };
+        match self.nodes.get(group) {
+            Some(nodes) => nodes.iter().collect(),
+            // 核心生成的 GLOBAL 先列出分组，再列出节点
+            None if group == "GLOBAL" => self.groups.iter().chain(&self.proxies).collect(),
+            None => vec![],
+        }
+    }
+
+    /// global 始终在最前，其余按配置排序，配置中没有的分组与节点保持核心返回的顺序排在后面
+    fn apply(&self, tray_proxies: &mut TrayProxies) {
+        let position = |order: &[&String], name: &str| {
+            order.iter().position(|n| *n == name).unwrap_or(usize::MAX)
+        };
+        let groups = self.groups.iter().collect::<Vec<_>>();
+        let group_key = |name: &str| match name {
+            "global" => 0,
+            name => position(&groups, name).saturating_add(1),
+        };
+        // 排序是稳定的，位置相同的分组与节点保持原有顺序
+        tray_proxies.sort_by(|a, _, b, _| group_key(a).cmp(&group_key(b)));
+
+        for (group, item) in tray_proxies.iter_mut() {
+            let order = self.node_order(group);
+            item.all.sort_by_cached_key(|node| position(&order, node));
+        }
+    }
 }
-type TrayProxies = IndexMap<String, TrayProxyItem>;
 
 /// Convert raw proxies to tray proxies
-fn to_tray_proxies(mode: &str, raw_proxies: &Proxies) -> TrayProxies {
+fn to_tray_proxies(mode: &str, raw_proxies: &Proxies, order: &TrayOrder) -> TrayProxies {
     let mut tray_proxies = TrayProxies::new();
     if matches!(mode, "global" | "rule" | "script") {
         if mode == "global" || raw_proxies.proxies.is_empty() {
@@ -72,6 +255,7 @@ fn to_tray_proxies(mode: &str, raw_proxies: &Proxies) -> TrayProxies {
                     .map(|x| x.name.to_owned())
                     .collect(),
                 r#type: "Selector".to_string(),
+                resolved: None,
             };
             tray_proxies.insert("global".to_owned(), global);
         }
@@ -80,10 +264,29 @@ fn to_tray_proxies(mode: &str, raw_proxies: &Proxies) -> TrayProxies {
                 current: raw_group.now.clone(),
                 all: raw_group.all.iter().map(|x| x.name.to_owned()).collect(),
                 r#type: raw_group.r#type.clone(),
+                resolved: raw_proxies.resolve_group(raw_group),
             };
             tray_proxies.insert(raw_group.name.to_owned(), group);
         }
     }
+    order.apply(&mut tray_proxies);
+    tray_proxies
+}
+
+/// 分组是否在托盘中隐藏，GLOBAL 在托盘中的名称为 global
+fn is_hidden_group(name: &str, hidden: &[String]) -> bool {
+    hidden
+        .iter()
+        .any(|hidden| hidden == name || (name == "global" && hidden == "GLOBAL"))
+}
+
+/// 托盘中显示的分组，隐藏的分组仍会在主窗口中显示
+fn to_tray_visible_proxies(mode: &str, raw_proxies: &Proxies) -> TrayProxies {
+    let mut tray_proxies = to_tray_proxies(mode, raw_proxies, &TrayOrder::from_runtime());
+    let hidden = { Config::verge().latest().tray_hidden_groups.clone() };
+    if let Some(hidden) = hidden.filter(|hidden| !hidden.is_empty()) {
+        tray_proxies.retain(|name, _| !is_hidden_group(name, &hidden));
+    }
     tray_proxies
 }
 
@@ -124,13 +327,17 @@ fn diff_proxies(old_proxies: &TrayProxies, new_proxies: &TrayProxies) -> TrayUpd
         if all_matching != old_item.all.len() {
             return TrayUpdateType::Full;
         }
+        // 实际使用的节点显示在分组标题上，只能全量更新；延迟变化不触发更新，避免频繁重建菜单
+        let resolved_name = |item: &TrayProxyItem| item.resolved.as_ref().map(|r| r.name.clone());
+        if resolved_name(item) != resolved_name(old_item) {
+            return TrayUpdateType::Full;
+        }
         // then diff the current
         if item.current != old_item.current {
-            actions.push((
-                group.clone(),
-                old_item.current.clone().unwrap(),
-                item.current.clone().unwrap(),
-            ));
+            match (&old_item.current, &item.current) {
+                (Some(from), Some(to)) => actions.push((group.clone(), from.clone(), to.clone())),
+                _ => return TrayUpdateType::Full,
+            }
         }
     }
     if actions.is_empty() {
@@ -140,66 +347,119 @@ fn diff_proxies(old_proxies: &TrayProxies, new_proxies: &TrayProxies) -> TrayUpd
     }
 }
 
+static TRAY_UPDATE: Lazy<broadcast::Sender<TrayUpdateType>> =
+    Lazy::new(|| broadcast::channel(16).0);
+
+/// 订阅每次代理更新后计算出的托盘更新类型
+pub fn subscribe_tray_updates() -> broadcast::Receiver<TrayUpdateType> {
+    TRAY_UPDATE.subscribe()
+}
+
+/// 保存上一次的托盘快照，计算并广播每次更新的类型
+struct TrayUpdater {
+    holder: TrayProxies,
+}
+
+impl TrayUpdater {
+    fn new(holder: TrayProxies) -> Self {
+        Self { holder }
+    }
+
+    fn update(&mut self, current: TrayProxies) -> TrayUpdateType {
+        let update = diff_proxies(&self.holder, &current);
+        if update != TrayUpdateType::None {
+            self.holder = current;
+        }
+        // 没有订阅者时发送失败，忽略即可
+        let _ = TRAY_UPDATE.send(update.clone());
+        update
+    }
+}
+
+/// 连续的更新在这段时间内合并为一次
+const PROXIES_UPDATE_DEBOUNCE: Duration = Duration::from_millis(100);
+/// 合并的最长等待时间，避免持续的更新一直推迟托盘刷新
+const PROXIES_UPDATE_MAX_DELAY: Duration = Duration::from_millis(500);
+
+/// 等待下一次更新，并吞掉随后短时间内的连续更新
+///
+/// 返回 false 表示通道已关闭。处理时总是读取最新的状态，所以合并不会漏掉最后一次更新
+async fn recv_coalesced(rx: &mut broadcast::Receiver<()>) -> bool {
+    match rx.recv().await {
+        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+        Err(broadcast::error::RecvError::Closed) => return false,
+    }
+    let deadline = tokio::time::Instant::now() + PROXIES_UPDATE_MAX_DELAY;
+    let mut coalesced = 0usize;
+    loop {
+        let wait = PROXIES_UPDATE_DEBOUNCE
+            .min(deadline.saturating_duration_since(tokio::time::Instant::now()));
+        match tokio::time::timeout(wait, rx.recv()).await {
+            Ok(Ok(_)) | Ok(Err(broadcast::error::RecvError::Lagged(_))) => coalesced += 1,
+            Ok(Err(broadcast::error::RecvError::Closed)) | Err(_) => break,
+        }
+        if tokio::time::Instant::now() >= deadline {
+            break;
+        }
+    }
+    if coalesced > 0 {
+        debug!("coalesced {} proxies updates", coalesced);
+    }
+    true
+}
+
 #[instrument]
 pub async fn proxies_updated_receiver() {
-    let (mut rx, mut tray_proxies_holder) = {
+    let (mut rx, mut updater) = {
         let guard = ProxiesGuard::global().read();
         let proxies = guard.inner().to_owned();
         let mode = crate::utils::config::get_current_clash_mode();
         (
             guard.get_receiver(),
-            to_tray_proxies(mode.as_str(), &proxies),
+            TrayUpdater::new(to_tray_visible_proxies(mode.as_str(), &proxies)),
         )
     };
 
-    loop {
-        match rx.recv().await {
-            Ok(_) => {
-                debug!("proxies updated");
-                if Handle::global().app_handle.lock().is_none() {
-                    warn!("app handle not found");
-                    continue;
-                }
-                Handle::mutate_proxies();
-                {
-                    let is_tray_selector_enabled =
-                        Config::verge().latest().clash_tray_selector.unwrap_or(true);
-                    if !is_tray_selector_enabled {
-                        continue;
-                    }
-                }
-                // Do diff check
-                let mode = crate::utils::config::get_current_clash_mode();
-                let current_tray_proxies =
-                    to_tray_proxies(mode.as_str(), ProxiesGuard::global().read().inner());
-
-                match diff_proxies(&tray_proxies_holder, &current_tray_proxies) {
-                    TrayUpdateType::Full => {
-                        debug!("should do full update");
-                        tray_proxies_holder = current_tray_proxies;
-                        match Handle::update_systray() {
-                            Ok(_) => {
-                                debug!("update systray success");
-                            }
-                            Err(e) => {
-                                warn!("update systray failed: {:?}", e);
-                            }
-                        }
+    while recv_coalesced(&mut rx).await {
+        debug!("proxies updated");
+        if Handle::global().app_handle.lock().is_none() {
+            warn!("app handle not found");
+            continue;
+        }
+        Handle::mutate_proxies();
+        {
+            let is_tray_selector_enabled =
+                Config::verge().latest().clash_tray_selector.unwrap_or(true);
+            if !is_tray_selector_enabled {
+                continue;
+            }
+        }
+        // Do diff check
+        let mode = crate::utils::config::get_current_clash_mode();
+        let current_tray_proxies =
+            to_tray_visible_proxies(mode.as_str(), ProxiesGuard::global().read().inner());
+
+        match updater.update(current_tray_proxies) {
+            TrayUpdateType::Full => {
+                debug!("should do full update");
+                match Handle::update_systray() {
+                    Ok(_) => {
+                        debug!("update systray success");
                     }
-                    TrayUpdateType::Part(action_list) => {
-                        debug!("should do partial update, op list: {:?}", action_list);
-                        tray_proxies_holder = current_tray_proxies;
-                        platform_impl::update_selected_proxies(&action_list);
-                        debug!("update selected proxies success");
+                    Err(e) => {
+                        warn!("update systray failed: {:?}", e);
                     }
-                    _ => {}
                 }
             }
-            Err(e) => {
-                warn!("proxies updated receiver failed: {:?}", e);
+            TrayUpdateType::Part(action_list) => {
+                debug!("should do partial update, op list: {:?}", action_list);
+                platform_impl::update_selected_proxies(&action_list);
+                debug!("update selected proxies success");
             }
+            _ => {}
         }
     }
+    warn!("proxies updated receiver closed");
 }
 
 pub fn setup_proxies() {
@@ -208,15 +468,34 @@ pub fn setup_proxies() {
 }
 
 mod platform_impl {
-    use super::{ProxySelectAction, TrayProxyItem};
-    use crate::core::{clash::proxies::ProxiesGuard, handle::Handle};
+    use super::{ProxySelectAction, ResolvedNode, TrayProxyItem};
+    use crate::core::{
+        clash::proxies::{is_selectable, ProxiesGuard},
+        handle::Handle,
+    };
     use base64::{engine::general_purpose::STANDARD as base64_standard, Engine as _};
+    use rust_i18n::t;
     use tauri::{CustomMenuItem, SystemTrayMenu, SystemTrayMenuItem, SystemTraySubmenu};
     use tracing::warn;
 
-    pub fn generate_group_selector(group_name: &str, group: &TrayProxyItem) -> SystemTraySubmenu {
+    /// 每个分组默认最多显示的节点数，节点过多时部分系统的托盘菜单会卡顿甚至无法显示
+    const DEFAULT_MAX_NODES_PER_GROUP: usize = 100;
+
+    pub fn generate_group_selector(
+        group_name: &str,
+        group: &TrayProxyItem,
+        max_nodes: usize,
+    ) -> SystemTraySubmenu {
         let mut group_menu = SystemTrayMenu::new();
-        for item in group.all.iter() {
+        if group.r#type == "Selector" {
+            group_menu = group_menu
+                .add_item(CustomMenuItem::new(
+                    format!("select_fastest_{}", base64_standard.encode(group_name)),
+                    t!("tray.select_fastest"),
+                ))
+                .add_native_item(SystemTrayMenuItem::Separator);
+        }
+        for item in group.all.iter().take(max_nodes) {
             let mut sub_item = CustomMenuItem::new(
                 format!(
                     "select_proxy_{}_{}",
@@ -231,13 +510,32 @@ mod platform_impl {
                 }
             }
 
-            if !matches!(group.r#type.as_str(), "Selector" | "Fallback") {
+            if !is_selectable(&group.r#type) {
                 sub_item = sub_item.disabled();
             }
 
             group_menu = group_menu.add_item(sub_item);
         }
-        SystemTraySubmenu::new(group_name.to_string(), group_menu)
+        if group.all.len() > max_nodes {
+            group_menu = group_menu
+                .add_native_item(SystemTrayMenuItem::Separator)
+                .add_item(CustomMenuItem::new(
+                    format!("open_full_selector_{}", base64_standard.encode(group_name)),
+                    t!(
+                        "tray.open_full_selector",
+                        count = group.all.len() - max_nodes
+                    ),
+                ));
+        }
+        let title = match &group.resolved {
+            Some(ResolvedNode {
+                name,
+                delay: Some(delay),
+            }) => format!("{group_name} → {name} ({delay}ms)"),
+            Some(ResolvedNode { name, delay: None }) => format!("{group_name} → {name}"),
+            None => group_name.to_string(),
+        };
+        SystemTraySubmenu::new(title, group_menu)
     }
 
     pub fn generate_selectors(
@@ -248,8 +546,40 @@ mod platform_impl {
         if proxies.is_empty() {
             return menu.add_item(CustomMenuItem::new("no_proxies", "No Proxies"));
         }
+        menu = menu
+            .add_item(CustomMenuItem::new(
+                "test_all_delay",
+                super::test_all_delay_title(),
+            ))
+            .add_item(CustomMenuItem::new(
+                "manage_hidden_groups",
+                t!("tray.manage_hidden_groups"),
+            ));
+        let snapshots = crate::config::Config::verge()
+            .latest()
+            .selection_snapshots
+            .as_ref()
+            .map(|snapshots| snapshots.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        if !snapshots.is_empty() {
+            let mut snapshots_menu = SystemTrayMenu::new();
+            for name in snapshots.iter() {
+                snapshots_menu = snapshots_menu.add_item(CustomMenuItem::new(
+                    format!("apply_snapshot_{}", base64_standard.encode(name)),
+                    name,
+                ));
+            }
+            menu = menu.add_submenu(SystemTraySubmenu::new(
+                t!("tray.selection_snapshots"),
+                snapshots_menu,
+            ));
+        }
+        let max_nodes = crate::config::Config::verge()
+            .latest()
+            .tray_max_nodes_per_group
+            .unwrap_or(DEFAULT_MAX_NODES_PER_GROUP);
         for (group, item) in proxies.iter() {
-            let group_menu = generate_group_selector(group, item);
+            let group_menu = generate_group_selector(group, item, max_nodes);
             menu = menu.add_submenu(group_menu);
         }
         menu
@@ -268,7 +598,7 @@ mod platform_impl {
         menu = menu.add_native_item(SystemTrayMenuItem::Separator);
         let proxies = ProxiesGuard::global().read().inner().to_owned();
         let mode = crate::utils::config::get_current_clash_mode();
-        let tray_proxies = super::to_tray_proxies(mode.as_str(), &proxies);
+        let tray_proxies = super::to_tray_visible_proxies(mode.as_str(), &proxies);
         generate_selectors(&menu, &tray_proxies)
     }
 
@@ -321,8 +651,134 @@ impl SystemTrayMenuProxiesExt for SystemTrayMenu {
     }
 }
 
+/// 为分组选择延迟最低的节点
+fn select_fastest(encoded_group: &str) {
+    let group = match base64_standard
+        .decode(encoded_group)
+        .map_err(anyhow::Error::from)
+        .and_then(|group| Ok(String::from_utf8(group)?))
+    {
+        Ok(group) => group,
+        Err(e) => {
+            error!("invalid select fastest event: {:?}", e);
+            return;
+        }
+    };
+
+    tauri::async_runtime::spawn(async move {
+        match ProxiesGuard::global()
+            .select_fastest(&group, SelectionOrigin::Tray)
+            .await
+        {
+            Ok(Some(name)) => {
+                debug!("select fastest proxy success: {} {}", group, name);
+            }
+            Ok(None) => {
+                Handle::notify(
+                    t!("tray.select_fastest"),
+                    t!("tray.select_fastest_timeout", group = group),
+                );
+            }
+            Err(e) if e.is::<DelayTestCancelled>() => {
+                debug!("select fastest proxy cancelled: {}", group);
+            }
+            Err(e) => {
+                error!("select fastest proxy failed, {}, cause: {:?}", group, e);
+            }
+        }
+    });
+}
+
+/// 应用托盘菜单中选择的节点快照，名称经过 base64 编码
+fn apply_snapshot(encoded_name: &str) {
+    let name = match base64_standard
+        .decode(encoded_name)
+        .map_err(anyhow::Error::from)
+        .and_then(|name| Ok(String::from_utf8(name)?))
+    {
+        Ok(name) => name,
+        Err(e) => {
+            error!("invalid apply snapshot event: {:?}", e);
+            return;
+        }
+    };
+
+    tauri::async_runtime::spawn(async move {
+        match ProxiesGuard::global()
+            .apply_selection_snapshot(&name, SelectionOrigin::Tray)
+            .await
+        {
+            Ok(_) => debug!("apply selection snapshot success: {}", name),
+            Err(e) => error!("apply selection snapshot failed: {:?}", e),
+        }
+    });
+}
+
+/// 测试进行中时该菜单项用于取消测试
+fn test_all_delay_title() -> String {
+    if is_delay_testing() {
+        t!("tray.cancel_delay_tests").to_string()
+    } else {
+        t!("tray.test_all_delay").to_string()
+    }
+}
+
+/// 延迟测试开始或结束后更新菜单项的标题
+pub fn update_delay_test_item() {
+    if let Some(app_handle) = Handle::global().app_handle.lock().as_ref() {
+        if let Some(item) = app_handle.tray_handle().try_get_item("test_all_delay") {
+            log_err!(item.set_title(test_all_delay_title()));
+        }
+    }
+}
+
+/// 测试所有节点的延迟，完成后刷新托盘中显示的延迟
+fn test_all_delay() {
+    tauri::async_runtime::spawn(async move {
+        let mut rx = ProxiesGuard::global().test_all_groups();
+        while let Some(result) = rx.recv().await {
+            Handle::delay_test_result(&result);
+        }
+        if let Err(e) = ProxiesGuard::global().refresh_now().await {
+            error!("refresh proxies after delay test failed: {:?}", e);
+        }
+        Handle::mutate_proxies();
+    });
+}
+
 #[instrument]
 pub fn on_system_tray_event(event: &str) {
+    if event == "test_all_delay" {
+        if is_delay_testing() {
+            cancel_delay_tests();
+        } else {
+            test_all_delay();
+        }
+        return;
+    }
+    if event == "manage_hidden_groups" {
+        Handle::open_tray_groups_setting();
+        return;
+    }
+    if let Some(name) = event.strip_prefix("apply_snapshot_") {
+        apply_snapshot(name);
+        return;
+    }
+    if let Some(group) = event.strip_prefix("select_fastest_") {
+        select_fastest(group);
+        return;
+    }
+    if let Some(group) = event.strip_prefix("open_full_selector_") {
+        match base64_standard
+            .decode(group)
+            .map_err(anyhow::Error::from)
+            .and_then(|group| Ok(String::from_utf8(group)?))
+        {
+            Ok(group) => Handle::open_proxies_group(group),
+            Err(e) => error!("invalid open full selector event: {:?}", e),
+        }
+        return;
+    }
     if !event.starts_with("select_proxy_") {
         return; // bypass non-select event
     }
@@ -336,7 +792,7 @@ pub fn on_system_tray_event(event: &str) {
         let name = String::from_utf8(base64_standard.decode(parts[3])?)?;
         tauri::async_runtime::block_on(async move {
             ProxiesGuard::global()
-                .select_proxy(&group, &name)
+                .select_proxy(&group, &name, SelectionOrigin::Tray)
                 .await
                 .with_context(|| format!("select proxy failed, {} {}, cause: ", group, name))?;
 
@@ -351,3 +807,188 @@ pub fn on_system_tray_event(event: &str) {
         error!("on_system_tray_event failed: {:?}", e);
     }
 }
+
+#[test]
+fn test_diff_proxies() {
+    let item = |current: &str| TrayProxyItem {
+        current: Some(current.to_string()),
+        all: vec!["a".to_string(), "b".to_string()],
+        r#type: "Selector".to_string(),
+        resolved: None,
+    };
+    let old = TrayProxies::from([("group".to_string(), item("a"))]);
+
+    let new = TrayProxies::from([("group".to_string(), item("a"))]);
+    assert_eq!(diff_proxies(&old, &new), TrayUpdateType::None);
+
+    let new = TrayProxies::from([("group".to_string(), item("b"))]);
+    assert_eq!(
+        diff_proxies(&old, &new),
+        TrayUpdateType::Part(vec![(
+            "group".to_string(),
+            "a".to_string(),
+            "b".to_string()
+        )])
+    );
+
+    let new = TrayProxies::from([("other".to_string(), item("a"))]);
+    assert_eq!(diff_proxies(&old, &new), TrayUpdateType::Full);
+}
+
+#[test]
+fn test_tray_updater_publishes_updates() {
+    use crate::core::clash::{api::ProxyItem, proxies::ProxyGroupItem};
+
+    let snapshot = |now: &str, nodes: &[&str]| {
+        let group = ProxyGroupItem {
+            name: "group".to_string(),
+            r#type: "Selector".to_string(),
+            now: Some(now.to_string()),
+            all: nodes
+                .iter()
+                .map(|name| ProxyItem {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let proxies = Proxies {
+            groups: vec![group],
+            proxies: vec![Default::default()],
+            ..Default::default()
+        };
+        to_tray_proxies("rule", &proxies, &TrayOrder::default())
+    };
+
+    let mut rx = subscribe_tray_updates();
+    let mut updater = TrayUpdater::new(snapshot("a", &["a", "b"]));
+    updater.update(snapshot("a", &["a", "b"]));
+    updater.update(snapshot("b", &["a", "b"]));
+    updater.update(snapshot("b", &["a", "b", "c"]));
+    updater.update(snapshot("b", &["a", "b", "c"]));
+
+    let received = std::iter::from_fn(|| rx.try_recv().ok()).collect::<Vec<_>>();
+    assert_eq!(
+        received,
+        vec![
+            TrayUpdateType::None,
+            TrayUpdateType::Part(vec![(
+                "group".to_string(),
+                "a".to_string(),
+                "b".to_string()
+            )]),
+            TrayUpdateType::Full,
+            TrayUpdateType::None,
+        ]
+    );
+}
+
+#[test]
+fn test_is_hidden_group() {
+    let hidden = vec!["GLOBAL".to_string(), "ad-block".to_string()];
+    assert!(is_hidden_group("global", &hidden));
+    assert!(is_hidden_group("ad-block", &hidden));
+    assert!(!is_hidden_group("Proxy", &hidden));
+}
+
+#[tokio::test]
+async fn test_recv_coalesced() {
+    let (tx, mut rx) = broadcast::channel(4);
+    for _ in 0..10 {
+        tx.send(()).unwrap();
+    }
+    assert!(recv_coalesced(&mut rx).await);
+    assert!(rx.try_recv().is_err());
+
+    tx.send(()).unwrap();
+    drop(tx);
+    assert!(recv_coalesced(&mut rx).await);
+    assert!(!recv_coalesced(&mut rx).await);
+}
+
+#[test]
+fn test_tray_order_is_stable() {
+    use crate::core::clash::{api::ProxyItem, proxies::ProxyGroupItem};
+
+    let config: Mapping = serde_yaml::from_str(
+        r#"
+proxies:
+  - name: hk
+  - name: jp
+  - name: us
+proxy-groups:
+  - name: Proxy
+    proxies: [us, hk, jp]
+  - name: Auto
+    use: [provider]
+"#,
+    )
+    .unwrap();
+    let order = TrayOrder::from_config(&config);
+
+    let group = |name: &str, nodes: &[&str]| ProxyGroupItem {
+        name: name.to_string(),
+        r#type: "Selector".to_string(),
+        all: nodes
+            .iter()
+            .map(|name| ProxyItem {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let proxies = |groups: Vec<ProxyGroupItem>| Proxies {
+        groups,
+        proxies: vec![Default::default()],
+        ..Default::default()
+    };
+
+    let first = to_tray_proxies(
+        "rule",
+        &proxies(vec![
+            group("Auto", &["b", "a"]),
+            group("Extra", &["x"]),
+            group("Proxy", &["hk", "jp", "us"]),
+            group("Direct", &["d"]),
+        ]),
+        &order,
+    );
+    let second = to_tray_proxies(
+        "rule",
+        &proxies(vec![
+            group("Proxy", &["jp", "us", "hk"]),
+            group("Extra", &["x"]),
+            group("Auto", &["b", "a"]),
+            group("Direct", &["d"]),
+        ]),
+        &order,
+    );
+
+    // 配置中没有的分组与提供者的节点保持原有顺序
+    assert_eq!(
+        first.keys().collect::<Vec<_>>(),
+        ["Proxy", "Auto", "Extra", "Direct"]
+    );
+    assert_eq!(first["Proxy"].all, ["us", "hk", "jp"]);
+    assert_eq!(first["Auto"].all, ["b", "a"]);
+    assert_eq!(diff_proxies(&first, &second), TrayUpdateType::None);
+}
+
+#[test]
+fn test_controller_error_kind() {
+    // 与 `api::send` 中的构造方式一致，调用方还可能添加上下文
+    fn send() -> anyhow::Result<()> {
+        anyhow::bail!(api::ControllerAuthError(401))
+    }
+    let err = send().context("failed to get proxies").unwrap_err();
+    assert_eq!(
+        ControllerErrorKind::from(&err),
+        ControllerErrorKind::Unauthorized
+    );
+    assert_eq!(
+        ControllerErrorKind::from(&anyhow::anyhow!("unexpected response")),
+        ControllerErrorKind::Other
+    );
+}
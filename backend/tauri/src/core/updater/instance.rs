@@ -205,13 +205,15 @@ impl Updater {
 
     async fn replace_core(&self) -> anyhow::Result<()> {
         self.dispatch_state(UpdaterState::Replacing);
+        // 替换文件期间核心处于停止状态，不应被当作崩溃恢复
+        let _suppressed = CoreManager::global().suppress_recovery();
         let current_core = crate::config::Config::verge()
             .latest()
             .clash_core
             .clone()
             .unwrap_or_default();
         if current_core == self.core_type {
-            tokio::task::spawn_blocking(move || CoreManager::global().stop_core()).await??;
+            CoreManager::global().stop_core_async().await?;
             return Ok(());
         }
         #[cfg(target_os = "windows")]
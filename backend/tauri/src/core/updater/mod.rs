@@ -215,6 +215,9 @@ impl UpdaterManager {
     }
 
     pub async fn update_core(&mut self, core_type: &ClashCore) -> Result<usize> {
+        if let Some(version) = crate::core::manager::pinned_version(core_type) {
+            anyhow::bail!("`{core_type}` is pinned to {version}, unpin it before updating");
+        }
         self.mirror_speed_test().await?;
         let (artifact, tag) = self
             .manifest_version
@@ -0,0 +1,164 @@
+use super::{handle::Handle, CoreManager};
+use crate::{config::Config, utils::dirs};
+use anyhow::Result;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use rust_i18n::t;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+/// 编辑器保存文件时常常会连续写入多次
+/// 等待这段时间内没有新的变更后再重新加载
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(500);
+
+/// 监听当前配置的源文件，外部修改后自动重新加载配置
+pub struct ProfileWatcher {
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    /// 应用自身写入的文件内容的哈希，文件名为键
+    self_writes: Mutex<HashMap<String, u64>>,
+}
+
+fn content_hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl ProfileWatcher {
+    pub fn global() -> &'static ProfileWatcher {
+        static WATCHER: OnceCell<ProfileWatcher> = OnceCell::new();
+
+        WATCHER.get_or_init(|| ProfileWatcher {
+            watcher: Mutex::new(None),
+            self_writes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 记录应用自身写入的配置文件，写入的一方会负责重新加载配置
+    /// 文件内容与记录一致时，之后监听到的变更不会再次触发重新加载
+    pub fn record_write(&self, file: &str, data: &[u8]) {
+        self.self_writes
+            .lock()
+            .insert(file.to_string(), content_hash(data));
+    }
+
+    /// 文件是否被外部修改，内容与应用自身最后一次写入的相同时不算
+    fn changed_externally(&self, path: &Path, file: &str) -> bool {
+        let Some(written) = self.self_writes.lock().get(file).copied() else {
+            return true;
+        };
+        std::fs::read(path).map_or(true, |data| content_hash(&data) != written)
+    }
+
+    /// 根据 `watch_profile_changes` 启动或停止监听
+    pub fn refresh(&self) -> Result<()> {
+        let enable = { Config::verge().latest().watch_profile_changes };
+        let enable = enable.unwrap_or(false);
+
+        let mut watcher = self.watcher.lock();
+        if !enable {
+            if watcher.take().is_some() {
+                log::debug!(target: "app", "stop watching profile changes");
+            }
+            return Ok(());
+        }
+        if watcher.is_some() {
+            return Ok(());
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        // 编辑器通常以替换文件的方式保存，所以监听整个目录而非单个文件
+        let mut inner = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                let _ = tx.send(event.paths);
+            }
+            Ok(_) => {}
+            Err(err) => log::error!(target: "app", "profile watcher error: {err}"),
+        })?;
+        inner.watch(&dirs::app_profiles_dir()?, RecursiveMode::NonRecursive)?;
+
+        // watcher 被丢弃时 sender 一并释放，防抖任务随之退出
+        tauri::async_runtime::spawn(Self::debounce(rx));
+        *watcher = Some(inner);
+        log::debug!(target: "app", "start watching profile changes");
+        Ok(())
+    }
+
+    /// 当前配置及其链式配置所对应的文件名
+    fn active_files() -> HashSet<String> {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+
+        let mut uids = profiles.chain.clone().unwrap_or_default();
+        if let Some(current) = profiles.get_current() {
+            if let Ok(item) = profiles.get_item(&current) {
+                uids.extend(item.chains.clone().unwrap_or_default());
+            }
+            uids.push(current);
+        }
+
+        uids.iter()
+            .filter_map(|uid| profiles.get_item(uid).ok())
+            .filter_map(|item| item.file.clone())
+            .collect()
+    }
+
+    async fn debounce(mut rx: mpsc::UnboundedReceiver<Vec<PathBuf>>) {
+        while let Some(mut changed) = rx.recv().await {
+            loop {
+                match tokio::time::timeout(DEBOUNCE_DURATION, rx.recv()).await {
+                    Ok(Some(paths)) => changed.extend(paths),
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+
+            let files = Self::active_files();
+            let hit = changed.iter().any(|path| {
+                let Some(name) = path.file_name().map(|name| name.to_string_lossy()) else {
+                    return false;
+                };
+                files.contains(name.as_ref())
+                    && Self::global().changed_externally(path, name.as_ref())
+            });
+            if hit {
+                Self::reload().await;
+            }
+        }
+    }
+
+    async fn reload() {
+        // 正在切换核心时跳过，切换完成后会使用最新的配置
+        let Some(_guard) = CoreManager::global().try_lock_op() else {
+            log::debug!(target: "app", "core is switching, skip reloading the profile");
+            return;
+        };
+
+        log::info!(target: "app", "profile changed, reload the config");
+        match CoreManager::global().update_config().await {
+            Ok(_) => {
+                Handle::refresh_clash();
+                Handle::notify(
+                    t!("notification.profile_reload"),
+                    t!("notification.profile_reload_ok"),
+                );
+            }
+            Err(err) => {
+                log::error!(target: "app", "failed to reload the profile: {err}");
+                Handle::notify(
+                    t!("notification.profile_reload"),
+                    t!(
+                        "notification.profile_reload_failed",
+                        error = err.to_string()
+                    ),
+                );
+            }
+        }
+    }
+}
@@ -2,9 +2,11 @@
 
 use crate::{
     config::{nyanpasu::ClashCore, Config},
+    core::clash::core::{ServiceModeCheck, ServiceModeFailure},
     utils::dirs,
 };
 use anyhow::{bail, Context, Result};
+use backon::{ExponentialBuilder, Retryable};
 use deelevate::{PrivilegeLevel, Token};
 use runas::Command as RunasCommand;
 use serde::{Deserialize, Serialize};
@@ -15,6 +17,9 @@ use std::{
 use tokio::time::sleep;
 
 const SERVICE_URL: &str = "http://127.0.0.1:33211";
+const SERVICE_NAME: &str = "clash_verge_service";
+/// `sc query` 查询未注册的服务时返回的错误码
+const SERVICE_DOES_NOT_EXIST: i32 = 1060;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ResponseBody {
@@ -108,6 +113,65 @@ pub async fn check_service() -> Result<JsonResponse> {
     Ok(response)
 }
 
+/// 依次检查服务是否安装、是否运行、能否连接以及能否报告核心状态
+pub async fn check_service_mode() -> ServiceModeCheck {
+    let installed = dirs::service_path().is_ok_and(|path| path.exists());
+    let output = StdCommand::new("sc")
+        .args(["query", SERVICE_NAME])
+        .creation_flags(0x08000000)
+        .output();
+    match output {
+        Ok(output) if output.status.code() == Some(SERVICE_DOES_NOT_EXIST) || !installed => {
+            return ServiceModeCheck::failed(
+                ServiceModeFailure::NotInstalled,
+                "the Clash Nyanpasu Service is not installed",
+            );
+        }
+        Ok(output) if !String::from_utf8_lossy(&output.stdout).contains("RUNNING") => {
+            return ServiceModeCheck::failed(
+                ServiceModeFailure::NotRunning,
+                "the Clash Nyanpasu Service is not running",
+            );
+        }
+        Ok(_) => {}
+        Err(err) => {
+            log::warn!(target: "app", "failed to query the service state: {err}");
+        }
+    }
+
+    let url = format!("{SERVICE_URL}/get_clash");
+    let response = match reqwest::ClientBuilder::new().no_proxy().build() {
+        Ok(client) => client.get(url).send().await,
+        Err(err) => return ServiceModeCheck::failed(ServiceModeFailure::IpcUnreachable, err),
+    };
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => return ServiceModeCheck::failed(ServiceModeFailure::IpcUnreachable, err),
+    };
+    match response.json::<JsonResponse>().await {
+        Ok(_) => ServiceModeCheck {
+            failure: None,
+            error: None,
+        },
+        Err(err) => ServiceModeCheck::failed(ServiceModeFailure::VersionMismatch, err),
+    }
+}
+
+/// check the service status, retry a few times before giving up
+/// 服务重启或连接短暂断开时不应直接认为核心已停止
+pub async fn check_service_with_retry() -> Result<JsonResponse> {
+    let backoff = ExponentialBuilder::default()
+        .with_min_delay(Duration::from_millis(200))
+        .with_max_delay(Duration::from_secs(1))
+        .with_max_times(3);
+    check_service
+        .retry(&backoff)
+        .notify(|err, dur| {
+            log::warn!(target: "app", "failed to connect to the service, retry after {dur:?}: {err}");
+        })
+        .await
+}
+
 /// start the clash by service
 pub(super) async fn run_core_by_service(config_file: &PathBuf) -> Result<()> {
     let status = check_service().await?;
@@ -16,6 +16,12 @@ type ResultLog = Vec<(String, String)>;
 /// Enhance mode
 /// 返回最终配置、该配置包含的键、和script执行的结果
 pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
+    let current = { Config::profiles().latest().get_current() };
+    enhance_profile(current)
+}
+
+/// 使用指定的配置代替当前配置生成最终配置，不修改任何配置
+pub fn enhance_profile(uid: Option<String>) -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
     // config.yaml 的配置
     let clash_config = { Config::clash().latest().0.clone() };
 
@@ -36,9 +42,7 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
         let profiles = profiles.latest();
 
         let mut profile_spec_chains = {
-            let profile = profiles
-                .get_current()
-                .and_then(|uid| profiles.get_item(&uid).ok());
+            let profile = uid.as_ref().and_then(|uid| profiles.get_item(uid).ok());
             match profile {
                 Some(profile) => match &profile.chains {
                     Some(chains) => utils::convert_uids_to_scripts(&profiles, chains),
@@ -48,7 +52,10 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
             }
         };
 
-        let current_mapping = profiles.current_mapping().unwrap_or_default();
+        let current_mapping = uid
+            .as_ref()
+            .and_then(|uid| profiles.profile_mapping(uid).ok())
+            .unwrap_or_default();
 
         let chain = match profiles.chain.as_ref() {
             Some(chain) => utils::convert_uids_to_scripts(&profiles, chain),
@@ -10,8 +10,11 @@ use crate::{
     log_err,
     utils::{self, help::get_clash_external_port, resolve},
 };
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use rust_i18n::t;
 use serde_yaml::{Mapping, Value};
+use std::{collections::HashMap, fs, path::PathBuf};
 use wry::application::clipboard::Clipboard;
 
 // 打开面板
@@ -62,6 +65,57 @@ pub fn restart_clash_core() {
     });
 }
 
+/// 从托盘重启核心，重启期间托盘项显示为正在重启并禁用
+pub fn restart_core_from_tray() {
+    tauri::async_runtime::spawn(async {
+        let manager = CoreManager::global();
+        let Some(guard) = manager.try_lock_op() else {
+            log::debug!(target: "app", "another core operation is in progress, skip restarting");
+            return;
+        };
+        log_err!(handle::Handle::update_systray_part());
+        let res = manager.restart_core_preserve_config().await;
+        drop(guard);
+        log_err!(handle::Handle::update_systray_part());
+
+        match res {
+            Ok(_) => {
+                handle::Handle::refresh_clash();
+                handle::Handle::notify(
+                    t!("notification.core_restart.title"),
+                    t!("notification.core_restart.ok"),
+                );
+            }
+            Err(err) => {
+                log::error!(target: "app", "failed to restart the core: {err}");
+                handle::Handle::notify(
+                    t!("notification.core_restart.title"),
+                    t!("notification.core_restart.failed", error = err.to_string()),
+                );
+            }
+        }
+    });
+}
+
+/// 从托盘清空核心的 DNS 缓存
+pub fn flush_dns_cache_from_tray() {
+    tauri::async_runtime::spawn(async {
+        match clash::api::flush_dns_cache().await {
+            Ok(_) => handle::Handle::notify(
+                t!("notification.flush_dns.title"),
+                t!("notification.flush_dns.ok"),
+            ),
+            Err(err) => {
+                log::error!(target: "app", "failed to flush the dns cache: {err}");
+                handle::Handle::notify(
+                    t!("notification.flush_dns.title"),
+                    t!("notification.flush_dns.failed", error = err.to_string()),
+                );
+            }
+        }
+    });
+}
+
 // 切换模式 rule/global/direct/script mode
 pub fn change_clash_mode(mode: String) {
     let mut mapping = Mapping::new();
@@ -91,6 +145,72 @@ pub fn change_clash_mode(mode: String) {
     update_proxies_buff(Some(rx));
 }
 
+/// 同步在外部（如面板）修改的代理模式
+pub async fn sync_clash_mode() -> Result<()> {
+    let configs = clash::api::get_configs().await?;
+    let mode = match configs.get("mode").and_then(|val| val.as_str()) {
+        Some(mode) => mode.to_lowercase(),
+        None => return Ok(()),
+    };
+    if mode.eq_ignore_ascii_case(&utils::config::get_current_clash_mode()) {
+        return Ok(());
+    }
+
+    log::debug!(target: "app", "clash mode changed externally to {mode}");
+    let mut mapping = Mapping::new();
+    mapping.insert(Value::from("mode"), mode.into());
+    Config::clash().data().patch_config(mapping);
+    Config::clash().data().save_config()?;
+    handle::Handle::refresh_clash();
+    handle::Handle::update_systray_part()?;
+    Ok(())
+}
+
+/// 获取核心当前实际使用的日志级别
+pub async fn get_core_log_level() -> Result<nyanpasu::LoggingLevel> {
+    let configs = clash::api::get_configs().await?;
+    let level = configs
+        .get("log-level")
+        .context("the core does not report its log level")?;
+    Ok(serde_yaml::from_value(level.clone())?)
+}
+
+/// 修改核心的日志级别，立即生效并写入配置
+/// 客户端过滤的级别比核心更严格时一并放宽，避免新的日志被过滤掉
+pub async fn set_core_log_level(level: nyanpasu::LoggingLevel) -> Result<()> {
+    use nyanpasu::LoggingLevel;
+    use tracing::level_filters::LevelFilter;
+
+    // 核心没有 trace 级别，且只接受 warning
+    let name = match level {
+        LoggingLevel::Silent => "silent",
+        LoggingLevel::Trace | LoggingLevel::Debug => "debug",
+        LoggingLevel::Info => "info",
+        LoggingLevel::Warn => "warning",
+        LoggingLevel::Error => "error",
+    };
+    let mut mapping = Mapping::new();
+    mapping.insert("log-level".into(), name.into());
+    clash::api::patch_configs(&mapping).await?;
+
+    Config::clash().data().patch_config(mapping);
+    Config::clash().data().save_config()?;
+    handle::Handle::refresh_clash();
+
+    let min_level = { Config::verge().latest().core_log_min_level.clone() };
+    if let Some(min_level) = min_level {
+        if LevelFilter::from(min_level) < LevelFilter::from(level.clone()) {
+            log::info!(target: "app", "relax the core log filter to {name}");
+            clash::backend::set_core_log_min_level(Some(level.clone().into()));
+            let verge = Config::verge();
+            let mut verge = verge.data();
+            verge.core_log_min_level = Some(level);
+            verge.save_file()?;
+        }
+    }
+    Ok(())
+}
+
 // 切换系统代理
 pub fn toggle_system_proxy() {
     let enable = Config::verge().draft().enable_system_proxy;
@@ -109,6 +229,41 @@ pub fn toggle_system_proxy() {
     });
 }
 
+/// 修改当前配置、链等，成功后重新生成并应用配置
+pub async fn patch_profiles_config(profiles: IProfiles) -> Result<()> {
+    Config::profiles().draft().patch_config(profiles)?;
+
+    match CoreManager::global().update_config().await {
+        Ok(_) => {
+            handle::Handle::refresh_clash();
+            Config::profiles().apply();
+            Config::profiles().data().save_file()?;
+            log_err!(handle::Handle::update_systray_part());
+            Ok(())
+        }
+        Err(err) => {
+            Config::profiles().discard();
+            log::error!(target: "app", "{err}");
+            Err(err)
+        }
+    }
+}
+
+/// 从托盘切换当前配置
+pub fn switch_profile(uid: String) {
+    tauri::async_runtime::spawn(async move {
+        match patch_profiles_config(IProfiles {
+            current: Some(uid),
+            ..IProfiles::default()
+        })
+        .await
+        {
+            Ok(_) => handle::Handle::refresh_profiles(),
+            Err(err) => log::error!(target: "app", "{err}"),
+        }
+    });
+}
+
 // 打开系统代理
 pub fn enable_system_proxy() {
     tauri::async_runtime::spawn(async {
@@ -273,8 +428,21 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
     let log_level = patch.app_log_level;
     let log_max_files = patch.max_log_files;
     let enable_tray_selector = patch.clash_tray_selector;
+    let tray_max_nodes = patch.tray_max_nodes_per_group;
+    let tray_hidden_groups = patch.tray_hidden_groups;
+    let selection_snapshots = patch.selection_snapshots;
+    let core_log_min_level = patch.core_log_min_level;
+    let watch_profile_changes = patch.watch_profile_changes;
+    let controller_override = patch.controller_override;
 
     let res = || async move {
+        if let Some(overrides) = controller_override {
+            clash::api::ControllerEndpoint::resolve_with(&overrides)
+                .check_reachable()
+                .await
+                .context("the custom external controller is unreachable")?;
+        }
+
         #[cfg(target_os = "windows")]
         {
             let service_mode = patch.enable_service_mode;
@@ -284,14 +452,14 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
 
                 Config::generate()?;
                 CoreManager::global().run_core().await?;
-            } else if tun_mode.is_some() {
-                update_core_config().await?;
+            } else if let Some(enabled) = tun_mode {
+                update_tun_mode(enabled).await?;
             }
         }
 
         #[cfg(not(target_os = "windows"))]
-        if tun_mode.is_some() {
-            update_core_config().await?;
+        if let Some(enabled) = tun_mode {
+            update_tun_mode(enabled).await?;
         }
 
         if auto_launch.is_some() {
@@ -321,10 +489,22 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
             utils::init::refresh_logger((log_level, log_max_files))?;
         }
 
-        if enable_tray_selector.is_some() {
+        if enable_tray_selector.is_some()
+            || tray_max_nodes.is_some()
+            || tray_hidden_groups.is_some()
+            || selection_snapshots.is_some()
+        {
             handle::Handle::update_systray()?;
         }
 
+        if watch_profile_changes.is_some() {
+            watcher::ProfileWatcher::global().refresh()?;
+        }
+
+        if let Some(level) = core_log_min_level {
+            clash::backend::set_core_log_min_level(Some(level.into()));
+        }
+
         <Result<()>>::Ok(())
     };
 
@@ -385,6 +565,26 @@ pub async fn update_profile(uid: String, option: Option<PrfOption>) -> Result<()
 /// 更新配置
 async fn update_core_config() -> Result<()> {
     match CoreManager::global().update_config().await {
+        Ok(ConfigApplyStatus::Unconfirmed) => {
+            handle::Handle::refresh_clash();
+            handle::Handle::notice_message("set_config::unconfirmed", "unconfirmed");
+            Ok(())
+        }
+        Ok(_) => {
+            handle::Handle::refresh_clash();
+            handle::Handle::notice_message("set_config::ok", "ok");
+            Ok(())
+        }
+        Err(err) => {
+            handle::Handle::notice_message("set_config::error", format!("{err}"));
+            Err(err)
+        }
+    }
+}
+
+/// 切换 TUN 模式并通知前端
+async fn update_tun_mode(enabled: bool) -> Result<()> {
+    match CoreManager::global().set_tun_mode(enabled).await {
         Ok(_) => {
             handle::Handle::refresh_clash();
             handle::Handle::notice_message("set_config::ok", "ok");
@@ -398,24 +598,344 @@ async fn update_core_config() -> Result<()> {
 }
 
 /// copy env variable
-pub fn copy_clash_env(option: &str) {
-    let port = { Config::verge().latest().verge_mixed_port.unwrap_or(7890) };
-    let http_proxy = format!("http://127.0.0.1:{}", port);
-    let socks5_proxy = format!("socks5://127.0.0.1:{}", port);
-
-    let sh =
-        format!("export https_proxy={http_proxy} http_proxy={http_proxy} all_proxy={socks5_proxy}");
-    let cmd: String = format!("set http_proxy={http_proxy} \n set https_proxy={http_proxy}");
-    let ps: String = format!("$env:HTTP_PROXY=\"{http_proxy}\"; $env:HTTPS_PROXY=\"{http_proxy}\"");
-
-    let mut clipboard = Clipboard::new();
-
-    match option {
-        "sh" => clipboard.write_text(sh),
-        "cmd" => clipboard.write_text(cmd),
-        "ps" => clipboard.write_text(ps),
-        _ => log::error!(target: "app", "copy_clash_env: Invalid option! {option}"),
+pub fn copy_clash_env() {
+    let env_type = { Config::verge().latest().env_type.clone() };
+    let env_type = env_type.unwrap_or_else(|| default_env_type().to_string());
+    let config = { Config::runtime().latest().config.clone() };
+    let config = config.unwrap_or_else(|| Config::clash().latest().0.clone());
+
+    match clash_env_script(&env_type, &config) {
+        Some(script) => Clipboard::new().write_text(script),
+        None => log::error!(target: "app", "copy_clash_env: Invalid option! {env_type}"),
+    }
+}
+
+fn default_env_type() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "ps"
+    } else {
+        "sh"
+    }
+}
+
+/// 根据正在运行的配置生成设置代理环境变量的脚本，优先使用 mixed-port
+fn clash_env_script(env_type: &str, config: &Mapping) -> Option<String> {
+    let port = |key: &str| {
+        config
+            .get(key)
+            .and_then(|port| port.as_u64())
+            .filter(|port| *port > 0)
+    };
+    let mixed_port = port("mixed-port");
+    let http_port = mixed_port.or(port("port")).unwrap_or(7890);
+    let socks_port = mixed_port.or(port("socks-port")).unwrap_or(http_port);
+    let http_proxy = format!("http://127.0.0.1:{http_port}");
+    let socks5_proxy = format!("socks5://127.0.0.1:{socks_port}");
+
+    let script = match env_type {
+        "sh" => format!(
+            "export https_proxy={http_proxy} http_proxy={http_proxy} all_proxy={socks5_proxy}"
+        ),
+        "fish" => format!(
+            "set -gx https_proxy {http_proxy}; set -gx http_proxy {http_proxy}; set -gx all_proxy {socks5_proxy}"
+        ),
+        "cmd" => format!("set http_proxy={http_proxy} \n set https_proxy={http_proxy}"),
+        "ps" => format!("$env:HTTP_PROXY=\"{http_proxy}\"; $env:HTTPS_PROXY=\"{http_proxy}\""),
+        _ => return None,
+    };
+    Some(script)
+}
+
+/// 导出核心日志
+/// 文件头附带核心、系统和应用版本等信息，方便反馈问题
+pub async fn export_core_logs() -> Result<PathBuf> {
+    let content = core_logs_content().await?;
+    let log_dir = utils::dirs::app_logs_dir()?;
+    fs::create_dir_all(&log_dir)?;
+    let path = log_dir.join(format!(
+        "core-{}.log",
+        Local::now().format("%Y-%m-%d-%H%M%S")
+    ));
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// 带版本信息文件头的核心日志
+async fn core_logs_content() -> Result<String> {
+    let clash_core = { Config::verge().latest().clash_core.clone() };
+    let clash_core = clash_core.unwrap_or_default();
+    let core_version = {
+        let clash_core = clash_core.clone();
+        tokio::task::spawn_blocking(move || manager::resolve_core_version(&clash_core))
+            .await?
+            .unwrap_or_else(|err| format!("unknown ({err})"))
+    };
+    let (state, run_type) = CoreManager::global().status().await;
+
+    let mut content = format!(
+        "# Clash Nyanpasu Core Logs\n\
+         # app version: {}\n\
+         # os: {} ({})\n\
+         # core: {clash_core} {core_version}\n\
+         # run type: {run_type:?}\n\
+         # core state: {state:?}\n\
+         # exported at: {}\n\n",
+        utils::dirs::get_app_version(),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        Local::now().to_rfc3339(),
+    );
+    for line in logger::Logger::global().get_log().await {
+        content.push_str(&line);
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+/// 诊断包中附带的最近应用日志数量
+/// 应用日志包含崩溃时的 panic 信息，作为崩溃现场
+const DIAGNOSTIC_APP_LOGS: usize = 3;
+
+/// 诊断包中的环境信息
+#[derive(Debug, serde::Serialize)]
+struct DiagnosticInfo {
+    app_version: String,
+    os: &'static str,
+    arch: &'static str,
+    core: nyanpasu::ClashCore,
+    core_version: String,
+    run_type: crate::core::clash::core::RunTypeDiagnostics,
+    exported_at: String,
+}
+
+/// 隐藏文本中出现的敏感字符串
+fn redact_text(text: &str, secrets: &[String]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(text.to_string(), |text, secret| {
+            text.replace(secret.as_str(), "******")
+        })
+}
+
+/// 需要从日志中隐藏的字符串：控制器密钥及订阅链接
+fn collect_secrets() -> Vec<String> {
+    let mut secrets = Vec::new();
+    secrets.extend(Config::clash().latest().get_client_info().secret);
+    if let Some(secret) = Config::verge()
+        .latest()
+        .controller_override
+        .as_ref()
+        .and_then(|o| o.secret.clone())
+    {
+        secrets.push(secret);
+    }
+    if let Some(items) = Config::profiles().latest().items.as_ref() {
+        secrets.extend(items.iter().filter_map(|item| item.url.clone()));
+    }
+    secrets
+}
+
+/// 导出用于反馈问题的诊断包，返回 zip 文件路径
+/// 包含环境信息、核心日志、隐藏敏感信息后的运行配置及最近的应用日志
+pub async fn export_diagnostic_bundle() -> Result<PathBuf> {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let clash_core = { Config::verge().latest().clash_core.clone() };
+    let clash_core = clash_core.unwrap_or_default();
+    let core_version = {
+        let clash_core = clash_core.clone();
+        tokio::task::spawn_blocking(move || manager::resolve_core_version(&clash_core))
+            .await?
+            .unwrap_or_else(|err| format!("unknown ({err})"))
+    };
+    let info = DiagnosticInfo {
+        app_version: utils::dirs::get_app_version().to_string(),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        core: clash_core,
+        core_version,
+        run_type: CoreManager::global().run_type_diagnostics().await,
+        exported_at: Local::now().to_rfc3339(),
+    };
+    let secrets = collect_secrets();
+    let core_logs = redact_text(&core_logs_content().await?, &secrets);
+    let runtime_config = {
+        let mut config = Config::runtime()
+            .latest()
+            .config
+            .clone()
+            .unwrap_or_default();
+        redact_secrets(&mut config);
+        serde_yaml::to_string(&config)?
+    };
+
+    let log_dir = utils::dirs::app_logs_dir()?;
+    fs::create_dir_all(&log_dir)?;
+    let mut app_logs = glob::glob(&format!("{}/*.app.log", log_dir.to_string_lossy()))?
+        .filter_map(|entry| entry.ok())
+        .collect::<Vec<_>>();
+    app_logs.sort_by_key(|path| fs::metadata(path).and_then(|m| m.modified()).ok());
+    app_logs.reverse();
+    app_logs.truncate(DIAGNOSTIC_APP_LOGS);
+
+    let path = log_dir.join(format!(
+        "diagnostic-{}.zip",
+        Local::now().format("%Y-%m-%d-%H%M%S")
+    ));
+    let mut zip = ZipWriter::new(fs::File::create(&path)?);
+    let options = SimpleFileOptions::default();
+    zip.start_file("info.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&info)?.as_bytes())?;
+    zip.start_file("core.log", options)?;
+    zip.write_all(core_logs.as_bytes())?;
+    zip.start_file("config.yaml", options)?;
+    zip.write_all(runtime_config.as_bytes())?;
+    for log in app_logs {
+        let Some(name) = log
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        let content = fs::read(&log)?;
+        let content = redact_text(&String::from_utf8_lossy(&content), &secrets);
+        zip.start_file(format!("logs/{name}"), options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(path)
+}
+
+/// 配置合并后的预览
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ProfilePreview {
+    /// 合并后的最终配置，敏感信息已隐藏
+    pub config: String,
+    pub valid: bool,
+    /// 核心检查配置时输出的错误
+    pub errors: Vec<String>,
+    /// 各个脚本执行的输出
+    pub chain_logs: HashMap<String, Vec<(String, String)>>,
+}
+
+/// 合并后的配置中需要隐藏的字段
+const SECRET_FIELDS: [&str; 6] = [
+    "secret",
+    "password",
+    "uuid",
+    "private-key",
+    "pre-shared-key",
+    "auth-str",
+];
+
+/// 隐藏配置及各个节点中的密码等敏感信息
+fn redact_secrets(config: &mut Mapping) {
+    for (key, value) in config.iter_mut() {
+        if key.as_str().is_some_and(|key| SECRET_FIELDS.contains(&key)) {
+            *value = "******".into();
+        }
     }
+    if let Some(Value::Sequence(proxies)) = config.get_mut("proxies") {
+        for proxy in proxies {
+            if let Value::Mapping(proxy) = proxy {
+                redact_secrets(proxy);
+            }
+        }
+    }
+}
+
+/// 使用指定的配置生成最终配置，并使用当前核心检查，不修改任何配置
+pub fn preview_profile(uid: String) -> Result<ProfilePreview> {
+    // 确认配置存在且能被读取
+    Config::profiles().latest().profile_mapping(&uid)?;
+
+    let (mut config, _, chain_logs) = crate::enhance::enhance_profile(Some(uid));
+    let path = std::env::temp_dir().join(PREVIEW_CONFIG);
+    utils::help::save_yaml(&path, &config, Some("# Generated by Clash Nyanpasu"))?;
+
+    let clash_core = { Config::verge().latest().clash_core.clone() };
+    let clash_core = clash_core.unwrap_or(nyanpasu::ClashCore::ClashPremium);
+    let errors = CoreManager::global().check_config_for(&clash_core, Some(path))?;
+
+    redact_secrets(&mut config);
+    Ok(ProfilePreview {
+        config: serde_yaml::to_string(&config)?,
+        valid: errors.is_empty(),
+        errors,
+        chain_logs,
+    })
+}
+
+/// 占用外部控制端口的进程
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PortOwner {
+    pub port: u16,
+    pub pid: u32,
+    pub name: String,
+    /// 是否为已知的核心
+    pub is_core: bool,
+    pub killed: bool,
+}
+
+/// 进程名是否为已知的核心
+fn is_core_process(name: &str) -> bool {
+    let name = name.strip_suffix(".exe").unwrap_or(name);
+    nyanpasu::ClashCore::ALL
+        .iter()
+        .any(|core| String::from(core.clone()) == name)
+}
+
+/// 进程是否为应用自身管理的核心，服务模式下无法获取 pid，运行中的已知核心均视为由服务启动
+async fn is_managed_core(pid: u32, is_core: bool) -> bool {
+    let manager = CoreManager::global();
+    if manager.managed_pid() == Some(pid) {
+        return true;
+    }
+    matches!(
+        manager.status().await,
+        (CoreState::Running, RunType::Service)
+    ) && is_core
+}
+
+/// 释放被占用的外部控制端口
+///
+/// 占用端口的是已知核心时直接结束它；其他进程只在 `confirm` 为 true 时才结束，
+/// 否则返回进程信息由用户决定。端口未被占用时返回 None
+/// 占用端口的是应用自身管理的核心时不会结束它，应通过重启核心处理
+pub async fn free_controller_port(confirm: bool) -> Result<Option<PortOwner>> {
+    let port = Config::clash().latest().get_external_controller_port();
+    let Some(pid) = utils::help::find_port_owner(port).await? else {
+        return Ok(None);
+    };
+
+    let mut system = sysinfo::System::new();
+    system.refresh_processes();
+    let process = system
+        .process(sysinfo::Pid::from_u32(pid))
+        .with_context(|| format!("process {pid} holding port {port} not found"))?;
+    let name = process.name().to_string();
+    let is_core = is_core_process(&name);
+    if is_managed_core(pid, is_core).await {
+        bail!("the controller port {port} is held by the managed core {name}({pid}), restart the core instead");
+    }
+    let killed = if is_core || confirm {
+        log::warn!(target: "app", "kill process {name}({pid}) holding the controller port {port}");
+        if !process.kill() {
+            bail!("failed to kill process {name}({pid})");
+        }
+        true
+    } else {
+        false
+    };
+    Ok(Some(PortOwner {
+        port,
+        pid,
+        name,
+        is_core,
+        killed,
+    }))
 }
 
 pub fn update_proxies_buff(rx: Option<tokio::sync::oneshot::Receiver<()>>) {
@@ -437,3 +957,53 @@ pub fn update_proxies_buff(rx: Option<tokio::sync::oneshot::Receiver<()>>) {
         }
     });
 }
+
+#[test]
+fn test_clash_env_script() {
+    let config: Mapping = serde_yaml::from_str("port: 7891\nsocks-port: 7892").unwrap();
+    assert_eq!(
+        clash_env_script("sh", &config).unwrap(),
+        "export https_proxy=http://127.0.0.1:7891 http_proxy=http://127.0.0.1:7891 all_proxy=socks5://127.0.0.1:7892"
+    );
+
+    let config: Mapping = serde_yaml::from_str("mixed-port: 7897\nport: 7891").unwrap();
+    assert_eq!(
+        clash_env_script("ps", &config).unwrap(),
+        "$env:HTTP_PROXY=\"http://127.0.0.1:7897\"; $env:HTTPS_PROXY=\"http://127.0.0.1:7897\""
+    );
+    assert!(clash_env_script("unknown", &config).is_none());
+}
+
+#[test]
+fn test_redact_secrets() {
+    let mut config: Mapping = serde_yaml::from_str(
+        "secret: abc\nmode: rule\nproxies:\n  - name: a\n    password: pass\n    uuid: id",
+    )
+    .unwrap();
+    redact_secrets(&mut config);
+    let expected: Mapping = serde_yaml::from_str(
+        "secret: '******'\nmode: rule\nproxies:\n  - name: a\n    password: '******'\n    uuid: '******'",
+    )
+    .unwrap();
+    assert_eq!(config, expected);
+}
+
+#[test]
+fn test_is_core_process() {
+    assert!(is_core_process("mihomo"));
+    assert!(is_core_process("clash-rs.exe"));
+    assert!(!is_core_process("nginx"));
+    assert!(!is_core_process("clash-nyanpasu"));
+}
+
+#[test]
+fn test_redact_text() {
+    let secrets = vec![
+        "https://sub.example.com/?token=abc".to_string(),
+        String::new(),
+    ];
+    assert_eq!(
+        redact_text("fetch https://sub.example.com/?token=abc failed", &secrets),
+        "fetch ****** failed"
+    );
+}
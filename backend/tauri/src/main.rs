@@ -127,23 +127,42 @@ fn main() -> std::io::Result<()> {
             cmds::open_core_dir,
             // cmds::kill_sidecar,
             cmds::restart_sidecar,
+            cmds::restart_core_preserve_config,
+            cmds::cancel_core_start,
             cmds::grant_permission,
             // clash
             cmds::get_clash_info,
             cmds::get_clash_logs,
+            cmds::export_core_logs,
+            cmds::export_diagnostic_bundle,
             cmds::patch_clash_config,
             cmds::change_clash_core,
+            cmds::rollback_core,
+            cmds::has_any_core_installed,
+            cmds::get_tun_status,
+            cmds::set_tun_mode,
+            cmds::diagnose_controller,
             cmds::get_runtime_config,
             cmds::get_runtime_yaml,
             cmds::get_runtime_exists,
             cmds::get_runtime_logs,
+            cmds::get_core_log_level,
+            cmds::set_core_log_level,
             cmds::clash_api_get_proxy_delay,
+            cmds::get_connections,
             cmds::uwp::invoke_uwp_tool,
             // updater
             cmds::fetch_latest_core_versions,
             cmds::update_core,
             cmds::inspect_updater,
             cmds::get_core_version,
+            cmds::list_installed_cores,
+            cmds::supported_cores,
+            cmds::pin_core,
+            cmds::unpin_core,
+            cmds::check_config_for_core,
+            cmds::smoke_test_core,
+            cmds::benchmark_startup,
             // utils
             cmds::collect_logs,
             // verge
@@ -155,6 +174,7 @@ fn main() -> std::io::Result<()> {
             cmds::enhance_profiles,
             cmds::patch_profiles_config,
             cmds::view_profile,
+            cmds::preview_profile,
             cmds::patch_profile,
             cmds::create_profile,
             cmds::import_profile,
@@ -172,8 +192,31 @@ fn main() -> std::io::Result<()> {
             cmds::service::uninstall_service,
             cmds::is_portable,
             cmds::get_proxies,
+            cmds::get_resolved_proxies,
+            cmds::get_proxy_chains,
+            cmds::set_group_mode,
+            cmds::refresh_proxies_now,
+            cmds::test_all_groups,
+            cmds::cancel_latency_tests,
+            cmds::get_core_metrics,
+            cmds::is_safe_mode,
+            cmds::proxies_delta,
             cmds::select_proxy,
+            cmds::select_proxy_by_index,
+            cmds::cycle_proxy,
             cmds::update_proxy_provider,
+            cmds::get_providers,
+            cmds::refresh_provider,
+            cmds::free_controller_port,
+            cmds::resolve_route,
+            cmds::save_selection_snapshot,
+            cmds::apply_selection_snapshot,
+            cmds::run_type_diagnostics,
+            cmds::check_service_mode,
+            cmds::migrate_to_service,
+            cmds::flush_dns_cache,
+            cmds::controller_latency,
+            cmds::get_traffic,
             cmds::restart_application,
         ]);
 
@@ -202,8 +245,13 @@ fn main() -> std::io::Result<()> {
         .expect("error while running tauri application");
 
     app.run(|app_handle, e| match e {
+        // 最后一个窗口关闭时触发
         tauri::RunEvent::ExitRequested { api, .. } => {
-            api.prevent_exit();
+            let keep_running = { Config::verge().latest().keep_core_on_window_close };
+            if keep_running.unwrap_or(true) {
+                api.prevent_exit();
+                Handle::set_in_background(app_handle, true);
+            }
         }
         tauri::RunEvent::Exit => {
             resolve::resolve_reset();
@@ -215,13 +263,21 @@ fn main() -> std::io::Result<()> {
             use tauri::Manager;
 
             if label == "main" {
+                if matches!(event, tauri::WindowEvent::Focused(true)) {
+                    core::CoreManager::global().wake_from_idle();
+                }
                 if let tauri::WindowEvent::CloseRequested { api, .. } = event {
                     api.prevent_close();
                     let _ = resolve::save_window_state(app_handle, true);
 
+                    let keep_running = { Config::verge().latest().keep_core_on_window_close };
+                    if !keep_running.unwrap_or(true) {
+                        utils::help::quit_application(app_handle);
+                    }
                     if let Some(win) = app_handle.get_window("main") {
                         let _ = win.hide();
                     }
+                    Handle::set_in_background(app_handle, true);
                 }
             }
         }
@@ -232,6 +288,9 @@ fn main() -> std::io::Result<()> {
                     tauri::WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
                         core::tray::on_scale_factor_changed(scale_factor);
                     }
+                    tauri::WindowEvent::Focused(true) => {
+                        core::CoreManager::global().wake_from_idle();
+                    }
                     tauri::WindowEvent::CloseRequested { .. } | tauri::WindowEvent::Destroyed => {
                         // log::info!(target: "app", "window close requested");
                         let _ = resolve::save_window_state(app_handle, true);
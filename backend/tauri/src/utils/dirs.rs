@@ -1,6 +1,7 @@
-use crate::core::handle;
+use crate::{config::nyanpasu::ClashCore, core::handle};
 use anyhow::Result;
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use tauri::{
     api::path::{home_dir, resource_dir},
     Env,
@@ -84,7 +85,22 @@ pub fn old_app_home_dir() -> Result<PathBuf> {
         .join(PREVIOUS_APP_NAME))
 }
 
+/// 测试使用的临时数据目录，避免读写用户的配置
+#[cfg(test)]
+static TEST_HOME_DIR: once_cell::sync::Lazy<tempfile::TempDir> = once_cell::sync::Lazy::new(|| {
+    tempfile::Builder::new()
+        .prefix("nyanpasu-test-")
+        .tempdir()
+        .expect("failed to create the test home dir")
+});
+
+#[cfg(test)]
+pub fn app_home_dir() -> Result<PathBuf> {
+    Ok(TEST_HOME_DIR.path().to_path_buf())
+}
+
 /// get the verge app home dir
+#[cfg(not(test))]
 pub fn app_home_dir() -> Result<PathBuf> {
     if cfg!(feature = "verge-dev") {
         return Ok(home_dir()
@@ -166,6 +182,27 @@ pub fn clash_pid_path() -> Result<PathBuf> {
     Ok(app_home_dir()?.join("clash.pid"))
 }
 
+/// 可随时删除的临时文件
+pub fn app_cache_dir() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join("cache"))
+}
+
+/// 连续启动失败的次数，核心稳定运行后清零
+pub fn startup_failures_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join("startup-failures"))
+}
+
+/// 安全模式使用的最小配置
+pub fn safe_mode_config_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join("safe-mode.yaml"))
+}
+
+/// 修改系统 DNS 前保存的原始设置，恢复后删除
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+pub fn dns_recovery_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join("dns-recovery"))
+}
+
 #[cfg(windows)]
 pub fn service_dir() -> Result<PathBuf> {
     Ok(app_home_dir()?.join("service"))
@@ -191,6 +228,227 @@ pub fn service_log_file() -> Result<PathBuf> {
     Ok(log_file)
 }
 
+/// 核心可执行文件所在的目录
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoreSource {
+    /// 环境变量指定的路径，用于开发与调试
+    Env,
+    /// 与主程序相同的安装目录
+    InstallDir,
+    /// 应用数据目录，用户手动放入的核心
+    DataDir,
+}
+
+/// 指定单个核心路径的环境变量，如 `NYANPASU_CORE_PATH_CLASH_RS`
+fn core_path_env(core: &ClashCore) -> String {
+    format!(
+        "NYANPASU_CORE_PATH_{}",
+        core.to_string().to_uppercase().replace('-', "_")
+    )
+}
+
+/// 指定所有核心所在目录的环境变量
+const CORE_DIR_ENV: &str = "NYANPASU_CORE_DIR";
+
+fn core_binary_name(core: &ClashCore) -> String {
+    #[cfg(target_os = "windows")]
+    {
+        format!("{core}.exe")
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        core.to_string()
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    path.is_file()
+}
+
+/// 读取环境变量中指定的核心路径，变量存在但指向的文件不可用时返回错误
+fn find_env_binary_path(core: &ClashCore) -> Result<Option<PathBuf>> {
+    let env = core_path_env(core);
+    let (env, path) = match std::env::var_os(&env) {
+        Some(path) => (env, PathBuf::from(path)),
+        None => match std::env::var_os(CORE_DIR_ENV) {
+            Some(dir) => {
+                let dir = PathBuf::from(dir);
+                if !dir.is_dir() {
+                    anyhow::bail!("`{CORE_DIR_ENV}` points to a missing directory {dir:?}");
+                }
+                // 目录中不一定包含所有核心，缺少时继续查找其他目录
+                let path = dir.join(core_binary_name(core));
+                if !path.exists() {
+                    return Ok(None);
+                }
+                (CORE_DIR_ENV.to_string(), path)
+            }
+            None => return Ok(None),
+        },
+    };
+    if !path.exists() {
+        anyhow::bail!("`{env}` points to a missing file {path:?}");
+    }
+    if !is_executable(&path) {
+        anyhow::bail!("`{env}` points to a non-executable file {path:?}");
+    }
+    Ok(Some(path))
+}
+
+/// 查找核心在各目录中的可执行文件，按使用的优先级排序
+pub fn find_binary_paths(core: &ClashCore) -> Vec<(CoreSource, PathBuf)> {
+    let binary = core_binary_name(core);
+
+    let install_dir = tauri::utils::platform::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(PathBuf::from));
+    let env_path = find_env_binary_path(core).ok().flatten();
+    [
+        (CoreSource::Env, env_path),
+        (
+            CoreSource::InstallDir,
+            install_dir.map(|dir| dir.join(&binary)),
+        ),
+        (
+            CoreSource::DataDir,
+            app_home_dir().ok().map(|dir| dir.join(&binary)),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(source, path)| Some((source, path?)))
+    .filter(|(_, path)| path.is_file())
+    .collect()
+}
+
+/// 查找核心的可执行文件
+/// 优先使用环境变量指定的路径，其次是与主程序相同的目录，最后是数据目录
+/// 不考虑固定的版本，启动核心时使用 `manager::find_binary_path`
+pub fn find_binary_path(core: &ClashCore) -> Result<PathBuf> {
+    // 环境变量指向的文件不可用时直接报错，避免静默使用其他核心
+    find_env_binary_path(core)?;
+    find_binary_paths(core)
+        .into_iter()
+        .next()
+        .map(|(_, path)| path)
+        .ok_or(anyhow::anyhow!("core binary not found `{core}`"))
+}
+
+/// 是否安装了任意一个核心
+pub fn has_any_core_installed() -> bool {
+    ClashCore::ALL
+        .iter()
+        .any(|core| find_binary_path(core).is_ok())
+}
+
+/// 从 ELF、Mach-O 或 PE 文件头中读取可执行文件的架构
+/// 通用二进制会返回其中包含的所有架构，无法识别的格式返回空
+fn binary_arches(header: &[u8]) -> Vec<&'static str> {
+    let u16_le = |at: usize| {
+        header
+            .get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u32_le = |at: usize| {
+        header
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let u32_be = |at: usize| {
+        header
+            .get(at..at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let elf_arch = |machine: u16| match machine {
+        0x03 => Some("x86"),
+        0x3e => Some("x86_64"),
+        0x28 => Some("arm"),
+        0xb7 => Some("aarch64"),
+        0xf3 => Some("riscv64"),
+        0x08 => Some("mips"),
+        0x102 => Some("loongarch64"),
+        _ => None,
+    };
+    let macho_arch = |cputype: u32| match cputype {
+        0x07 => Some("x86"),
+        0x0100_0007 => Some("x86_64"),
+        0x0c => Some("arm"),
+        0x0100_000c => Some("aarch64"),
+        _ => None,
+    };
+
+    match header {
+        [0x7f, b'E', b'L', b'F', ..] => {
+            let machine = match header.get(5) {
+                Some(2) => header.get(18..20).map(|b| u16::from_be_bytes([b[0], b[1]])),
+                _ => u16_le(18),
+            };
+            machine.and_then(elf_arch).into_iter().collect()
+        }
+        [0xcf, 0xfa, 0xed, 0xfe, ..] | [0xce, 0xfa, 0xed, 0xfe, ..] => {
+            u32_le(4).and_then(macho_arch).into_iter().collect()
+        }
+        [0xca, 0xfe, 0xba, 0xbe, ..] => {
+            let count = u32_be(4).unwrap_or(0).min(16) as usize;
+            (0..count)
+                .filter_map(|i| u32_be(8 + i * 20).and_then(macho_arch))
+                .collect()
+        }
+        [b'M', b'Z', ..] => {
+            let Some(pe) = u32_le(0x3c).map(|offset| offset as usize) else {
+                return Vec::new();
+            };
+            if header.get(pe..pe + 4) != Some(b"PE\0\0".as_slice()) {
+                return Vec::new();
+            }
+            let arch = match u16_le(pe + 4) {
+                Some(0x14c) => Some("x86"),
+                Some(0x8664) => Some("x86_64"),
+                Some(0x1c4) => Some("arm"),
+                Some(0xaa64) => Some("aarch64"),
+                _ => None,
+            };
+            arch.into_iter().collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// 指定架构的程序能否在当前系统运行
+/// 64 位系统可以运行 32 位程序，macOS 与 Windows 的 ARM 版本可以转译运行 x86_64 程序
+fn arch_runs_on(binary: &str, os: &str, arch: &str) -> bool {
+    binary == arch
+        || (arch == "x86_64" && binary == "x86")
+        || (arch == "aarch64" && binary == "arm" && os != "macos")
+        || (arch == "aarch64" && binary == "x86_64" && matches!(os, "macos" | "windows"))
+}
+
+/// 检查核心的架构是否与当前系统相符，不相符时返回核心的架构
+/// 无法读取或无法识别时不做判断
+pub fn incompatible_binary_arch(path: &Path) -> Option<String> {
+    use std::io::Read;
+
+    let mut header = Vec::with_capacity(4096);
+    std::fs::File::open(path)
+        .ok()?
+        .take(4096)
+        .read_to_end(&mut header)
+        .ok()?;
+    let arches = binary_arches(&header);
+    let compatible = arches.is_empty()
+        || arches
+            .iter()
+            .any(|binary| arch_runs_on(binary, std::env::consts::OS, std::env::consts::ARCH));
+    (!compatible).then(|| arches.join(", "))
+}
+
 pub fn path_to_str(path: &PathBuf) -> Result<&str> {
     let path_str = path
         .as_os_str()
@@ -214,3 +472,34 @@ pub fn get_single_instance_placeholder() -> String {
             .to_string()
     }
 }
+
+#[test]
+fn test_core_path_env() {
+    assert_eq!(
+        core_path_env(&ClashCore::ClashRs),
+        "NYANPASU_CORE_PATH_CLASH_RS"
+    );
+    assert_eq!(
+        core_path_env(&ClashCore::MihomoAlpha),
+        "NYANPASU_CORE_PATH_MIHOMO_ALPHA"
+    );
+}
+
+#[test]
+fn test_binary_arches() {
+    let mut elf = vec![0u8; 64];
+    elf[..6].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1]);
+    elf[18] = 0xb7;
+    assert_eq!(binary_arches(&elf), vec!["aarch64"]);
+
+    let mut pe = vec![0u8; 0x100];
+    pe[..2].copy_from_slice(b"MZ");
+    pe[0x3c] = 0x80;
+    pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+    pe[0x84..0x86].copy_from_slice(&0x8664u16.to_le_bytes());
+    assert_eq!(binary_arches(&pe), vec!["x86_64"]);
+
+    assert!(binary_arches(b"#!/bin/sh").is_empty());
+    assert!(arch_runs_on("x86_64", "macos", "aarch64"));
+    assert!(!arch_runs_on("aarch64", "linux", "x86_64"));
+}
@@ -129,6 +129,11 @@ pub fn mapping_to_i18n_key(locale_key: &str) -> &'static str {
     }
 }
 
+/// 指定的端口已被占用
+#[derive(Debug, thiserror::Error)]
+#[error("Port {0} is not available")]
+pub struct PortUnavailable(pub u16);
+
 pub fn get_clash_external_port(
     strategy: &ExternalControllerPortStrategy,
     port: u16,
@@ -136,7 +141,7 @@ pub fn get_clash_external_port(
     match strategy {
         ExternalControllerPortStrategy::Fixed => {
             if !port_scanner::local_port_available(port) {
-                bail!("Port {} is not available", port);
+                return Err(PortUnavailable(port).into());
             }
         }
         ExternalControllerPortStrategy::Random | ExternalControllerPortStrategy::AllowFallback => {
@@ -153,6 +158,43 @@ pub fn get_clash_external_port(
     Ok(port)
 }
 
+/// 从 `netstat -ano` 的输出中找到监听指定端口的进程
+fn parse_netstat_listening(output: &str, port: u16) -> Option<u32> {
+    let suffix = format!(":{port}");
+    output.lines().find_map(|line| {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        match columns.as_slice() {
+            ["TCP", local, _, "LISTENING", pid] if local.ends_with(&suffix) => pid.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+/// 查找监听指定 TCP 端口的进程
+pub async fn find_port_owner(port: u16) -> Result<Option<u32>> {
+    if cfg!(windows) {
+        let output = tokio::process::Command::new("netstat")
+            .args(["-ano", "-p", "tcp"])
+            .output()
+            .await
+            .context("failed to run netstat")?;
+        Ok(parse_netstat_listening(
+            &String::from_utf8_lossy(&output.stdout),
+            port,
+        ))
+    } else {
+        let output = tokio::process::Command::new("lsof")
+            .args(["-nP", "-t", &format!("-iTCP:{port}"), "-sTCP:LISTEN"])
+            .output()
+            .await
+            .context("failed to run lsof")?;
+        // 没有进程监听时 lsof 返回非零状态码
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.trim().parse().ok()))
+    }
+}
+
 pub fn resize_tray_image(img: &[u8], scale_factor: f64) -> Result<Vec<u8>> {
     let img = ImageReader::new(Cursor::new(img))
         .with_guessed_format()?
@@ -336,3 +378,19 @@ fn test_parse_value() {
     assert_eq!(parse_str::<usize>(test_1, "expire1"), None);
     assert_eq!(parse_str::<usize>(test_2, "attachment"), None);
 }
+
+#[test]
+fn test_parse_netstat_listening() {
+    let output = "
+Active Connections
+
+  Proto  Local Address          Foreign Address        State           PID
+  TCP    0.0.0.0:135            0.0.0.0:0              LISTENING       1024
+  TCP    127.0.0.1:9090         127.0.0.1:50312        ESTABLISHED     4096
+  TCP    127.0.0.1:9090         0.0.0.0:0              LISTENING       2048
+  TCP    127.0.0.1:19090        0.0.0.0:0              LISTENING       8192
+";
+    assert_eq!(parse_netstat_listening(output, 9090), Some(2048));
+    assert_eq!(parse_netstat_listening(output, 135), Some(1024));
+    assert_eq!(parse_netstat_listening(output, 8080), None);
+}
@@ -9,12 +9,12 @@ use crate::{
         *,
     },
     log_err, trace_err,
-    utils::init,
+    utils::{dirs, init},
 };
 use anyhow::Result;
 use semver::Version;
 use serde_yaml::Mapping;
-use std::net::TcpListener;
+use std::{net::TcpListener, path::Path};
 use tauri::{api::process::Command, App, AppHandle, Manager};
 
 pub fn find_unused_port() -> Result<u16> {
@@ -78,6 +78,8 @@ pub fn resolve_setup(app: &mut App) {
 
     log::trace!("launch core");
     log_err!(CoreManager::global().init());
+    clash::traffic::TrafficMonitor::global().init();
+    log_err!(watcher::ProfileWatcher::global().refresh());
 
     log::trace!("init system tray");
     log_err!(tray::Tray::update_systray(&app.app_handle()));
@@ -110,6 +112,8 @@ pub fn resolve_reset() {
 
 /// create main window
 pub fn create_window(app_handle: &AppHandle) {
+    handle::Handle::set_in_background(app_handle, false);
+    CoreManager::global().wake_from_idle();
     if let Some(window) = app_handle.get_window("main") {
         trace_err!(window.unminimize(), "set win unminimize");
         trace_err!(window.show(), "set win visible");
@@ -342,16 +346,18 @@ pub fn save_window_state(app_handle: &AppHandle, save_to_file: bool) -> Result<(
     Ok(())
 }
 
-/// resolve core version
-// TODO: use enum instead
-pub fn resolve_core_version(core_type: &ClashCore) -> Result<String> {
-    let core = core_type.clone().to_string();
-    log::debug!(target: "app", "check config in `{core}`");
+/// 核心能够运行，但输出中找不到版本号
+#[derive(Debug, thiserror::Error)]
+#[error("failed to parse the core version from the output")]
+pub struct VersionUnparsed;
+
+/// 获取指定路径的核心的版本
+pub fn resolve_binary_version(core_type: &ClashCore, binary: &Path) -> Result<String> {
+    log::debug!(target: "app", "get the version of `{}`", binary.display());
+    let cmd = Command::new(binary.to_string_lossy());
     let cmd = match core_type {
-        ClashCore::ClashPremium | ClashCore::Mihomo | ClashCore::MihomoAlpha => {
-            Command::new_sidecar(core)?.args(["-v"])
-        }
-        ClashCore::ClashRs => Command::new_sidecar(core)?.args(["-V"]),
+        ClashCore::ClashPremium | ClashCore::Mihomo | ClashCore::MihomoAlpha => cmd.args(["-v"]),
+        ClashCore::ClashRs => cmd.args(["-V"]),
     };
     let out = cmd.output()?;
     log::debug!(target: "app", "get core version: {:?}", out);
@@ -369,5 +375,5 @@ pub fn resolve_core_version(core_type: &ClashCore) -> Result<String> {
             return Ok(item.to_string());
         }
     }
-    Err(anyhow::anyhow!("failed to get core version"))
+    Err(VersionUnparsed.into())
 }
@@ -2,9 +2,12 @@ import {
   Clash,
   ProviderItem,
   ProviderRules,
+  SelectionChange,
   clash as clashApi,
 } from "@/service";
 import * as tauri from "@/service/tauri";
+import { listen } from "@tauri-apps/api/event";
+import { useEffect } from "react";
 import useSWR from "swr";
 
 export const useClashCore = () => {
@@ -12,6 +15,22 @@ export const useClashCore = () => {
 
   const { data, isLoading, mutate } = useSWR("getProxies", tauri.getProxies);
 
+  // 窗口发起的选择已经自行刷新，只需同步托盘或外部的修改
+  useEffect(() => {
+    const unlisten = listen<SelectionChange>(
+      "nyanpasu://selection-changed",
+      ({ payload }) => {
+        if (payload.origin !== "window") {
+          mutate();
+        }
+      },
+    );
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
   const updateGroupDelay = async (
     index: number,
     options?: Clash.DelayOptions,
@@ -41,13 +41,16 @@ export const useNyanpasu = (options?: {
 
   const setClashCore = async (
     clashCore: Required<VergeConfig>["clash_core"],
+    force?: boolean,
   ) => {
-    await service.setClashCore(clashCore);
+    const warnings = await service.setClashCore(clashCore, force);
 
     // timeout for restart clash core.
     setTimeout(() => {
       getClashCore.mutate();
     }, 100);
+
+    return warnings;
   };
 
   const getLatestCore = useSWR("getLatestCore", fetchLatestCore, {
@@ -1,25 +1,43 @@
-import { fetchLatestCoreVersions, getCoreVersion } from "./tauri";
-import { VergeConfig } from "./types";
+import {
+  fetchLatestCoreVersions,
+  getCoreVersion,
+  supportedCores,
+} from "./tauri";
+import { SupportedCore, VergeConfig } from "./types";
 
 export type ClashCore = Required<VergeConfig>["clash_core"];
 
-export interface Core {
-  name: string;
+export type ChangeCoreError =
+  | { type: "config_invalid"; details: string[] }
+  | { type: "spawn_failed"; cause: string }
+  | { type: "port_conflict"; port: number }
+  | { type: "check_timed_out"; timeout_secs: number }
+  | { type: "unsupported_features"; features: string[] }
+  | { type: "arch_mismatch"; binary: string; system: string };
+
+export type ChangeCorePhase =
+  | "draft"
+  | "generate"
+  | "check"
+  | "clear_logs"
+  | "start"
+  | "ready";
+
+export interface ChangeCoreProgress {
   core: ClashCore;
+  phase: ChangeCorePhase;
+}
+
+export interface Core extends SupportedCore {
   version?: string;
   latest?: string;
 }
 
-export const VALID_CORE: Core[] = [
-  { name: "Clash Premium", core: "clash" },
-  { name: "Mihomo", core: "mihomo" },
-  { name: "Mihomo Alpha", core: "mihomo-alpha" },
-  { name: "Clash Rust", core: "clash-rs" },
-];
-
 export const fetchCoreVersion = async () => {
+  const cores = await supportedCores();
+
   return await Promise.all(
-    VALID_CORE.map(async (item) => {
+    cores.map(async (item) => {
       const version = await getCoreVersion(item.core);
       return { ...item, version };
     }),
@@ -27,9 +45,12 @@ export const fetchCoreVersion = async () => {
 };
 
 export const fetchLatestCore = async () => {
-  const results = await fetchLatestCoreVersions();
+  const [results, supported] = await Promise.all([
+    fetchLatestCoreVersions(),
+    supportedCores(),
+  ]);
 
-  const cores = VALID_CORE.map((item) => {
+  const cores = supported.map((item) => {
     const key = item.core.replace(/-/g, "_") as keyof typeof results;
 
     let latest: string;
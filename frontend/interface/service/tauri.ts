@@ -6,6 +6,27 @@ import {
   Profile,
   SystemProxy,
   Proxies,
+  ResolvedNode,
+  TunStatus,
+  Connection,
+  CoreAction,
+  ConfigApplyStatus,
+  ControllerDiagnostics,
+  ControllerLatency,
+  CoreMetrics,
+  SmokeTestResult,
+  StartupBenchmark,
+  TrayProxies,
+  ProxiesDelta,
+  InstalledCore,
+  SupportedCore,
+  ProfilePreview,
+  ProviderInfo,
+  PortOwner,
+  RouteResolution,
+  RunTypeDiagnostics,
+  ServiceModeCheck,
+  TrafficStats,
 } from "./types";
 import { ManifestVersion } from "./core";
 
@@ -52,6 +73,22 @@ export const viewProfile = async (uid: string) => {
   return await invoke<void>("view_profile", { index: uid });
 };
 
+export const previewProfile = async (uid: string) => {
+  return await invoke<ProfilePreview>("preview_profile", { uid });
+};
+
+export const getCoreLogLevel = async () => {
+  return await invoke<Required<VergeConfig>["core_log_min_level"]>(
+    "get_core_log_level",
+  );
+};
+
+export const setCoreLogLevel = async (
+  level: Required<VergeConfig>["core_log_min_level"],
+) => {
+  return await invoke<void>("set_core_log_level", { level });
+};
+
 export const getProfiles = async () => {
   return await invoke<Profile.Config>("get_profiles");
 };
@@ -91,14 +128,129 @@ export const getCoreVersion = async (
   return await invoke<string>("get_core_version", { coreType });
 };
 
+export const checkConfigForCore = async (
+  coreType: Required<VergeConfig>["clash_core"],
+  configPath?: string,
+) => {
+  return await invoke<string[]>("check_config_for_core", {
+    coreType,
+    configPath,
+  });
+};
+
+export const smokeTestCore = async (
+  coreType: Required<VergeConfig>["clash_core"],
+) => {
+  return await invoke<SmokeTestResult>("smoke_test_core", { coreType });
+};
+
+export const benchmarkStartup = async (
+  coreType: Required<VergeConfig>["clash_core"],
+  runs?: number,
+) => {
+  return await invoke<StartupBenchmark>("benchmark_startup", {
+    coreType,
+    runs,
+  });
+};
+
+export const rollbackCore = async () => {
+  return await invoke<void>("rollback_core");
+};
+
+export const listInstalledCores = async () => {
+  return await invoke<InstalledCore[]>("list_installed_cores");
+};
+
+export const supportedCores = async () => {
+  return await invoke<SupportedCore[]>("supported_cores");
+};
+
+export const pinCore = async (
+  coreType: Required<VergeConfig>["clash_core"],
+  version: string,
+) => {
+  return await invoke<void>("pin_core", { coreType, version });
+};
+
+export const unpinCore = async (
+  coreType: Required<VergeConfig>["clash_core"],
+) => {
+  return await invoke<void>("unpin_core", { coreType });
+};
+
 export const setClashCore = async (
   clashCore: Required<VergeConfig>["clash_core"],
+  force?: boolean,
 ) => {
-  return await invoke<void>("change_clash_core", { clashCore });
+  return await invoke<string[]>("change_clash_core", { clashCore, force });
+};
+
+export const hasAnyCoreInstalled = async () => {
+  return await invoke<boolean>("has_any_core_installed");
+};
+
+export const getTunStatus = async () => {
+  return await invoke<TunStatus>("get_tun_status");
+};
+
+export const setTunMode = async (enabled: boolean) => {
+  return await invoke<void>("set_tun_mode", { enabled });
+};
+
+export const getConnections = async (filter?: {
+  host?: string;
+  chain?: string;
+}) => {
+  return await invoke<Connection.Item[]>("get_connections", { filter });
+};
+
+export const diagnoseController = async () => {
+  return await invoke<ControllerDiagnostics>("diagnose_controller");
+};
+
+/**
+ * Kill the process holding the controller port. Unknown processes are
+ * only killed when `confirm` is true, otherwise their info is returned.
+ */
+export const freeControllerPort = async (confirm?: boolean) => {
+  return await invoke<PortOwner | null>("free_controller_port", { confirm });
+};
+
+export const runTypeDiagnostics = async () => {
+  return await invoke<RunTypeDiagnostics>("run_type_diagnostics");
+};
+
+export const checkServiceMode = async () => {
+  return await invoke<ServiceModeCheck>("check_service_mode");
+};
+
+export const migrateToService = async () => {
+  return await invoke<void>("migrate_to_service");
+};
+
+export const flushDnsCache = async () => {
+  return await invoke<void>("flush_dns_cache");
+};
+
+export const controllerLatency = async (rounds?: number) => {
+  return await invoke<ControllerLatency>("controller_latency", { rounds });
+};
+
+export const getTraffic = async () => {
+  return await invoke<TrafficStats>("get_traffic");
+};
+
+export const cancelCoreStart = async () => {
+  return await invoke<void>("cancel_core_start");
 };
 
 export const restartSidecar = async () => {
-  return await invoke<void>("restart_sidecar");
+  return await invoke<CoreAction>("restart_sidecar");
+};
+
+export const restartCorePreserveConfig = async () => {
+  return await invoke<CoreAction>("restart_core_preserve_config");
 };
 
 export const fetchLatestCoreVersions = async () => {
@@ -159,6 +311,14 @@ export const collectLogs = async () => {
   return await invoke<void>("collect_logs");
 };
 
+export const exportCoreLogs = async () => {
+  return await invoke<string>("export_core_logs");
+};
+
+export const exportDiagnosticBundle = async () => {
+  return await invoke<string>("export_diagnostic_bundle");
+};
+
 export const setCustomAppDir = async (path: string) => {
   return await invoke<void>("set_custom_app_dir", { path });
 };
@@ -175,14 +335,95 @@ export const getProxies = async () => {
   return await invoke<Proxies>("get_proxies");
 };
 
+export const getProxyChains = async () => {
+  return await invoke<{ [group: string]: string[] }>("get_proxy_chains");
+};
+
+export const setGroupMode = async (group: string, mode: "manual" | "auto") => {
+  return await invoke<void>("set_group_mode", { group, mode });
+};
+
+export const getResolvedProxies = async () => {
+  return await invoke<{ [group: string]: ResolvedNode }>(
+    "get_resolved_proxies",
+  );
+};
+
+export const refreshProxiesNow = async () => {
+  return await invoke<void>("refresh_proxies_now");
+};
+
+/**
+ * Regenerate and push the config. Resolves to `unconfirmed` when the core
+ * does not report the new config in time, see the `config://applied` event.
+ */
+export const enhanceProfiles = async () => {
+  return await invoke<ConfigApplyStatus>("enhance_profiles");
+};
+
+/**
+ * Test every node in all groups, results are emitted one by one
+ * through the `nyanpasu://delay-test-result` event.
+ */
+export const testAllGroups = async () => {
+  return await invoke<void>("test_all_groups");
+};
+
+export const isSafeMode = async () => {
+  return await invoke<boolean>("is_safe_mode");
+};
+
+export const getCoreMetrics = async () => {
+  return await invoke<CoreMetrics>("get_core_metrics");
+};
+
+export const cancelLatencyTests = async () => {
+  return await invoke<void>("cancel_latency_tests");
+};
+
+export const proxiesDelta = async (previous: TrayProxies) => {
+  return await invoke<ProxiesDelta>("proxies_delta", { previous });
+};
+
+export const resolveRoute = async (host: string) => {
+  return await invoke<RouteResolution>("resolve_route", { host });
+};
+
+export const saveSelectionSnapshot = async (name: string) => {
+  return await invoke<void>("save_selection_snapshot", { name });
+};
+
+export const applySelectionSnapshot = async (name: string) => {
+  return await invoke<void>("apply_selection_snapshot", { name });
+};
+
 export const selectProxy = async (group: string, name: string) => {
   return await invoke<void>("select_proxy", { group, name });
 };
 
+export const selectProxyByIndex = async (group: string, index: number) => {
+  return await invoke<string>("select_proxy_by_index", { group, index });
+};
+
+export const cycleProxy = async (
+  group: string,
+  direction: "next" | "previous",
+) => {
+  return await invoke<string>("cycle_proxy", { group, direction });
+};
+
 export const updateProxyProvider = async (name: string) => {
   return await invoke<void>("update_proxy_provider", { name });
 };
 
+export const getProviders = async () => {
+  return await invoke<ProviderInfo[]>("get_providers");
+};
+
+export const refreshProvider = async (name: string) => {
+  return await invoke<ProviderInfo>("refresh_provider", { name });
+};
+
 export const save_window_size_state = async () => {
   return await invoke<void>("save_window_size_state");
 };
@@ -45,6 +45,51 @@ export interface VergeConfig {
   clash_strategy?: {
     external_controller_port_strategy: "fixed" | "random" | "allow_fallback";
   };
+  verify_core_checksum?: boolean;
+  watch_profile_changes?: boolean;
+  service_status_timeout?: number;
+  core_warmup_timeout?: number;
+  proxy_selections?: Record<string, string>;
+  config_check_timeout?: number;
+  delay_test_concurrency?: number;
+  controller_override?: {
+    scheme?: "http" | "https";
+    host?: string;
+    port?: number;
+    secret?: string;
+  };
+  crash_notify_window?: number;
+  proxies_grace_period?: number;
+  core_log_min_level?: "silent" | "trace" | "debug" | "info" | "warn" | "error";
+  tray_max_nodes_per_group?: number;
+  env_type?: "sh" | "fish" | "cmd" | "ps";
+  core_env?: Record<string, string>;
+  keep_core_on_window_close?: boolean;
+  pinned_core_versions?: Partial<
+    Record<Required<VergeConfig>["clash_core"], string>
+  >;
+  partial_config_reload?: boolean;
+  proxies_update_interval?: number;
+  core_clean_exits?: Partial<
+    Record<
+      Required<VergeConfig>["clash_core"],
+      { codes?: number[]; signals?: number[] }
+    >
+  >;
+  enable_observer_mode?: boolean;
+  tray_hidden_groups?: string[];
+  core_restart_grace_ms?: number;
+  defer_config_update?: boolean;
+  selection_snapshots?: Record<string, Record<string, string>>;
+  fallback_core?: VergeConfig["clash_core"];
+  core_extra_args?: Partial<
+    Record<Required<VergeConfig>["clash_core"], string[]>
+  >;
+  controller_auth_recovery?: boolean;
+  idle_auto_stop_minutes?: number;
+  persist_core_logs?: boolean;
+  core_log_file_max_size?: number;
+  core_log_file_max_count?: number;
 }
 
 export interface ClashInfo {
@@ -121,6 +166,206 @@ export interface Proxies {
   records: {
     [key: string]: Clash.Proxy;
   };
+  providers: ProviderInfo[];
+}
+
+export interface RouteResolution {
+  host: string;
+  ip?: string;
+  rule?: {
+    type: string;
+    payload: string;
+    proxy: string;
+  };
+  chain: string[];
+  undetermined: {
+    type: string;
+    payload: string;
+    proxy: string;
+  }[];
+}
+
+export interface PortOwner {
+  port: number;
+  pid: number;
+  name: string;
+  is_core: boolean;
+  killed: boolean;
+}
+
+export interface ProviderInfo {
+  name: string;
+  vehicleType: string;
+  updatedAt?: string;
+  nodeCount: number;
+}
+
+export interface SmokeTestResult {
+  success: boolean;
+  output: string[];
+}
+
+export interface StartupTiming {
+  spawn_ms: number;
+  first_output_ms: number | null;
+  ready_ms: number;
+}
+
+export interface StartupBenchmark {
+  average: StartupTiming;
+  runs: StartupTiming[];
+}
+
+export interface InstalledCore {
+  core_type: Required<VergeConfig>["clash_core"];
+  path: string;
+  version: string | null;
+  source: "env" | "install_dir" | "data_dir";
+  active: boolean;
+}
+
+export interface CoreCapabilities {
+  tun: boolean;
+  tun_hot_toggle: boolean;
+  config_patch: boolean;
+  sniffer: boolean;
+  script: boolean;
+}
+
+export interface SupportedCore {
+  core: Required<VergeConfig>["clash_core"];
+  name: string;
+  installed: boolean;
+  capabilities: CoreCapabilities;
+}
+
+export interface ControllerDiagnostics {
+  endpoint: string;
+  has_secret: boolean;
+  failed_stage: "connect" | "handshake" | "auth" | "version" | null;
+  error: string | null;
+  version: string | null;
+}
+
+export interface TrafficStats {
+  up: number;
+  down: number;
+  up_total: number;
+  down_total: number;
+  connected: boolean;
+}
+
+export interface HistogramSnapshot {
+  count: number;
+  avg_ms: number | null;
+  max_ms: number;
+  buckets: { le_ms: number | null; count: number }[];
+}
+
+export interface CoreMetrics {
+  since: number;
+  starts: number;
+  start_failures: number;
+  stops: number;
+  crashes: number;
+  recoveries: number;
+  recovery_failures: number;
+  config_pushes: number;
+  config_push_failures: number;
+  start_duration: HistogramSnapshot;
+  config_push_duration: HistogramSnapshot;
+}
+
+export interface ControllerLatency {
+  min: number;
+  avg: number;
+  max: number;
+  samples: number;
+  failures: number;
+}
+
+export interface ProfilePreview {
+  config: string;
+  valid: boolean;
+  errors: string[];
+  chain_logs: Record<string, [string, string][]>;
+}
+
+export interface CoreBroken {
+  core: Required<VergeConfig>["clash_core"];
+  reason: string;
+}
+
+export interface CoreSwitchSuggestion {
+  core: Required<VergeConfig>["clash_core"];
+  failures: number;
+  candidates: Required<VergeConfig>["clash_core"][];
+}
+
+export interface RunTypeDiagnostics {
+  chosen: "normal" | "service" | "observer";
+  enable_service_mode: boolean;
+  enable_observer_mode: boolean;
+  ipc_connected: boolean;
+  ipc_error?: string;
+}
+
+export type ServiceModeFailure =
+  | "unsupported"
+  | "not_installed"
+  | "not_running"
+  | "ipc_unreachable"
+  | "version_mismatch";
+
+export interface ServiceModeCheck {
+  failure: ServiceModeFailure | null;
+  error: string | null;
+}
+
+export type ConfigApplyStatus = "applied" | "unconfirmed" | "deferred";
+
+export type CoreAction = "started" | "restarted" | "stopped" | "was_not_running";
+
+export interface TunStatus {
+  requested: boolean;
+  active: boolean;
+  device_name: string | null;
+}
+
+export interface TrayProxyItem {
+  current: string | null;
+  all: string[];
+  type: string;
+  resolved: ResolvedNode | null;
+}
+
+export type TrayProxies = { [group: string]: TrayProxyItem };
+
+export type TrayUpdateType =
+  | { type: "none" }
+  | { type: "full" }
+  | { type: "part"; actions: [string, string, string][] };
+
+export interface ProxiesDelta {
+  update: TrayUpdateType;
+  snapshot: TrayProxies;
+}
+
+export interface ResolvedNode {
+  name: string;
+  delay: number | null;
+}
+
+export interface SelectionChange {
+  group: string;
+  from: string;
+  to: string;
+  origin: "window" | "tray" | "restore" | "external";
+}
+
+export interface DelayTestResult {
+  name: string;
+  delay: number | null;
 }
 
 export namespace Connection {
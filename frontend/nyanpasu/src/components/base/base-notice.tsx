@@ -1,4 +1,9 @@
-import { CheckCircleRounded, Close, ErrorRounded } from "@mui/icons-material";
+import {
+  CheckCircleRounded,
+  Close,
+  ErrorRounded,
+  WarningRounded,
+} from "@mui/icons-material";
 import { Box, IconButton, Slide, Snackbar, Typography } from "@mui/material";
 import { ReactNode, useState } from "react";
 import { createRoot } from "react-dom/client";
@@ -29,6 +34,7 @@ const NoticeInner = (props: InnerProps) => {
       <Box sx={{ width: 328, display: "flex", alignItems: "center" }}>
         {type === "error" && <ErrorRounded color="error" />}
         {type === "success" && <CheckCircleRounded color="success" />}
+        {type === "warn" && <WarningRounded color="warning" />}
 
         <Typography
           component="span"
@@ -64,11 +70,12 @@ interface NoticeInstance {
   info(message: ReactNode, duration?: number): void;
   error(message: ReactNode, duration?: number): void;
   success(message: ReactNode, duration?: number): void;
+  warn(message: ReactNode, duration?: number): void;
 }
 
 let parent: HTMLDivElement = null!;
 
-// @ts-expect-error 90 行动态添加了 info、error、success 属性
+// @ts-expect-error 下方动态添加了 info、error、success、warn 属性
 export const Notice: NoticeInstance = (props) => {
   if (!parent) {
     parent = document.createElement("div");
@@ -87,7 +94,7 @@ export const Notice: NoticeInstance = (props) => {
   root.render(<NoticeInner {...props} onClose={onUnmount} />);
 };
 
-(["info", "error", "success"] as const).forEach((type) => {
+(["info", "error", "success", "warn"] as const).forEach((type) => {
   Notice[type] = (message, duration) => {
     setTimeout(() => Notice({ type, message, duration }), 0);
   };
@@ -18,6 +18,14 @@ export const NoticeProvider = () => {
           });
           break;
 
+        case "set_config::unconfirmed":
+          useNotification({
+            title: t("Warning"),
+            body: t("Config Not Confirmed"),
+            type: NotificationType.Warn,
+          });
+          break;
+
         case "set_config::error":
           useNotification({
             title: t("Error"),
@@ -26,6 +34,14 @@ export const NoticeProvider = () => {
           });
           break;
 
+        case "controller_auth::error":
+          useNotification({
+            title: t("Error"),
+            body: t("Controller Auth Failed"),
+            type: NotificationType.Error,
+          });
+          break;
+
         default:
           break;
       }
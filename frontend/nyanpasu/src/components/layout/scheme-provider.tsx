@@ -1,10 +1,21 @@
+import { useMessage } from "@/hooks/use-notification";
+import {
+  CoreBroken,
+  CoreSwitchSuggestion,
+  setClashCore,
+  updateCore,
+} from "@nyanpasu/interface";
+import { ask } from "@tauri-apps/api/dialog";
 import { listen } from "@tauri-apps/api/event";
 import { useEffect } from "react";
+import { useTranslation } from "react-i18next";
 import { useNavigate } from "react-router-dom";
 
 export const SchemeProvider = () => {
   const navigate = useNavigate();
 
+  const { t } = useTranslation();
+
   useEffect(() => {
     listen("scheme-request-received", (req) => {
       const message: string = req.payload as string;
@@ -36,6 +47,77 @@ export const SchemeProvider = () => {
           });
       }
     });
+
+    listen<string>("nyanpasu://open-proxies-group", ({ payload }) => {
+      navigate("/proxies", { state: { group: payload } });
+    });
+
+    listen("nyanpasu://manage-tray-groups", () => {
+      navigate("/settings");
+    });
+
+    listen<CoreSwitchSuggestion>(
+      "nyanpasu://suggest-switch-core",
+      async ({ payload }) => {
+        const candidate = payload.candidates[0];
+
+        const confirmed = await ask(
+          t("Switch Core Suggestion", {
+            core: payload.core,
+            failures: payload.failures,
+            candidate,
+          }),
+          { title: t("Change Core"), type: "warning" },
+        );
+
+        if (!confirmed) {
+          return;
+        }
+
+        try {
+          await setClashCore(candidate);
+        } catch (e) {
+          useMessage(String(e), { title: t("Error"), type: "error" });
+        }
+      },
+    );
+
+    listen<CoreBroken>("nyanpasu://core-broken", async ({ payload }) => {
+      const redownload = await ask(
+        t("Core Broken", { core: payload.core, reason: payload.reason }),
+        {
+          title: t("Clash Core"),
+          type: "error",
+          okLabel: t("Re-download"),
+          cancelLabel: t("Switch Core"),
+        },
+      );
+
+      if (!redownload) {
+        navigate("/settings");
+        return;
+      }
+
+      // the updater restarts the core after replacing it
+      try {
+        await updateCore(payload.core);
+      } catch (e) {
+        useMessage(String(e), { title: t("Error"), type: "error" });
+      }
+    });
+
+    listen<number>("nyanpasu://safe-mode", async ({ payload }) => {
+      const fix = await ask(t("Safe Mode Hint", { failures: payload }), {
+        title: t("Safe Mode"),
+        type: "warning",
+        okLabel: t("Fix Profile"),
+        cancelLabel: t("Cancel"),
+      });
+
+      if (fix) {
+        navigate("/profiles");
+      }
+    });
   }, []);
 
   return null;
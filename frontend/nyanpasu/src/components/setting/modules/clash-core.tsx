@@ -12,6 +12,7 @@ import FiberManualRecord from "@mui/icons-material/FiberManualRecord";
 import Update from "@mui/icons-material/Update";
 import IconButton from "@mui/material/IconButton";
 import Tooltip from "@mui/material/Tooltip";
+import { useTranslation } from "react-i18next";
 
 export const getImage = (core: ClashCore) => {
   switch (core) {
@@ -30,6 +31,14 @@ export const getImage = (core: ClashCore) => {
   }
 };
 
+const getCapabilities = (data: Core) => {
+  const { tun, sniffer, script } = data.capabilities;
+
+  return [tun && "TUN", sniffer && "Sniffer", script && "Script"]
+    .filter(Boolean)
+    .join(" · ");
+};
+
 export interface ClashCoreItemProps {
   selected: boolean;
   data: Core;
@@ -56,6 +65,8 @@ export const ClashCoreItem = ({
   onClick,
   onUpdate,
 }: ClashCoreItemProps) => {
+  const { t } = useTranslation();
+
   const { palette } = useTheme();
 
   const newVersion = data.latest ? data.latest !== data.version : false;
@@ -89,7 +100,11 @@ export const ClashCoreItem = ({
                 )}
               </Typography>
 
-              <Typography>{data.version}</Typography>
+              <Typography>
+                {data.installed ? data.version : t("Core Not Installed")}
+              </Typography>
+
+              <Typography variant="body2">{getCapabilities(data)}</Typography>
 
               {newVersion && (
                 <Typography variant="body2">
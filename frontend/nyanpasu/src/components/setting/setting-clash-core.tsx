@@ -1,12 +1,21 @@
 import { BaseCard, ExpandMore } from "@nyanpasu/ui";
 import { useTranslation } from "react-i18next";
-import { useMemo, useState } from "react";
+import { useEffect, useMemo, useState } from "react";
 import { Box, List, ListItem, Tooltip } from "@mui/material";
-import { ClashCore, useClash, useNyanpasu } from "@nyanpasu/interface";
+import {
+  ChangeCoreError,
+  ChangeCorePhase,
+  ChangeCoreProgress,
+  ClashCore,
+  useClash,
+  useNyanpasu,
+} from "@nyanpasu/interface";
 import { useLockFn, useReactive } from "ahooks";
 import { useMessage } from "@/hooks/use-notification";
 import LoadingButton from "@mui/lab/LoadingButton";
 import { motion } from "framer-motion";
+import { ask } from "@tauri-apps/api/dialog";
+import { listen } from "@tauri-apps/api/event";
 import { ClashCoreItem } from "./modules/clash-core";
 
 export const SettingClashCore = () => {
@@ -20,6 +29,19 @@ export const SettingClashCore = () => {
 
   const [expand, setExpand] = useState(false);
 
+  const [phase, setPhase] = useState<ChangeCorePhase | null>(null);
+
+  useEffect(() => {
+    const unlisten = listen<ChangeCoreProgress>(
+      "nyanpasu://change-core-progress",
+      ({ payload }) => setPhase(payload.phase),
+    );
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
   const {
     nyanpasuConfig,
     setClashCore,
@@ -47,22 +69,87 @@ export const SettingClashCore = () => {
 
       await deleteConnections();
 
-      await setClashCore(core);
+      let warnings: string[];
 
-      useMessage(`Successfully switch to ${core}`, {
-        type: "info",
-        title: t("Success"),
-      });
+      try {
+        warnings = await setClashCore(core);
+      } catch (e) {
+        const err = e as ChangeCoreError;
+
+        if (err?.type !== "unsupported_features") {
+          throw e;
+        }
+
+        const confirmed = await ask(
+          t("Change Core Unsupported Features", {
+            features: err.features.join("\n"),
+          }),
+          { title: t("Change Core"), type: "warning" },
+        );
+
+        if (!confirmed) {
+          return;
+        }
+
+        warnings = await setClashCore(core, true);
+      }
+
+      if (warnings.length) {
+        useMessage(
+          t("Change Core Warnings", {
+            count: warnings.length,
+            warnings: warnings.join("\n"),
+          }),
+          { type: "warning", title: t("Success") },
+        );
+      } else {
+        useMessage(`Successfully switch to ${core}`, {
+          type: "info",
+          title: t("Success"),
+        });
+      }
     } catch (e) {
-      useMessage(
-        "Switching failed, please check log and modify your profile file.",
-        {
-          type: "error",
-          title: t("Error"),
-        },
-      );
+      const err = e as ChangeCoreError;
+
+      let message: string;
+
+      switch (err?.type) {
+        case "config_invalid":
+          message = t("Change Core Config Invalid", {
+            details: err.details.join("\n"),
+          });
+          break;
+
+        case "port_conflict":
+          message = t("Change Core Port Conflict", { port: err.port });
+          break;
+
+        case "check_timed_out":
+          message = t("Change Core Check Timed Out", {
+            seconds: err.timeout_secs,
+          });
+          break;
+
+        case "arch_mismatch":
+          message = t("Change Core Arch Mismatch", {
+            binary: err.binary,
+            system: err.system,
+          });
+          break;
+
+        default:
+          message =
+            "Switching failed, please check log and modify your profile file.";
+          break;
+      }
+
+      useMessage(message, {
+        type: "error",
+        title: t("Error"),
+      });
     } finally {
       loading.mask = false;
+      setPhase(null);
     }
   });
 
@@ -140,7 +227,9 @@ export const SettingClashCore = () => {
     <BaseCard
       label={t("Clash Core")}
       loading={loading.mask}
-      labelChildren={<span>{version}</span>}
+      labelChildren={
+        <span>{phase ? t(`Change Core Phase ${phase}`) : version}</span>
+      }
     >
       <List disablePadding>
         {mergeCores?.map((item, index) => {
@@ -0,0 +1,129 @@
+import { useMessage } from "@/hooks/use-notification";
+import LoadingButton from "@mui/lab/LoadingButton";
+import { List, ListItem, ListItemText } from "@mui/material";
+import {
+  ControllerDiagnostics,
+  ControllerLatency,
+  controllerLatency,
+  diagnoseController,
+  flushDnsCache,
+} from "@nyanpasu/interface";
+import { BaseCard } from "@nyanpasu/ui";
+import { useLockFn } from "ahooks";
+import { useState } from "react";
+import { useTranslation } from "react-i18next";
+
+export const SettingClashDiagnostics = () => {
+  const { t } = useTranslation();
+
+  const [loading, setLoading] = useState({
+    diagnose: false,
+    latency: false,
+    dns: false,
+  });
+
+  const [diagnostics, setDiagnostics] = useState<ControllerDiagnostics>();
+
+  const [latency, setLatency] = useState<ControllerLatency>();
+
+  const handleDiagnose = useLockFn(async () => {
+    try {
+      setLoading((prev) => ({ ...prev, diagnose: true }));
+
+      setDiagnostics(await diagnoseController());
+    } catch (e) {
+      useMessage(String(e), { title: t("Error"), type: "error" });
+    } finally {
+      setLoading((prev) => ({ ...prev, diagnose: false }));
+    }
+  });
+
+  const handleLatency = useLockFn(async () => {
+    try {
+      setLoading((prev) => ({ ...prev, latency: true }));
+
+      setLatency(await controllerLatency());
+    } catch (e) {
+      useMessage(String(e), { title: t("Error"), type: "error" });
+    } finally {
+      setLoading((prev) => ({ ...prev, latency: false }));
+    }
+  });
+
+  const handleFlushDns = useLockFn(async () => {
+    try {
+      setLoading((prev) => ({ ...prev, dns: true }));
+
+      await flushDnsCache();
+
+      useMessage(t("DNS Cache Flushed"), { title: t("Success"), type: "info" });
+    } catch (e) {
+      useMessage(String(e), { title: t("Error"), type: "error" });
+    } finally {
+      setLoading((prev) => ({ ...prev, dns: false }));
+    }
+  });
+
+  return (
+    <BaseCard label={t("Diagnostics")}>
+      <List disablePadding>
+        <ListItem sx={{ pl: 0, pr: 0 }}>
+          <ListItemText
+            primary={t("Controller Status")}
+            secondary={
+              diagnostics &&
+              (diagnostics.failed_stage
+                ? `${diagnostics.failed_stage}: ${diagnostics.error}`
+                : `${diagnostics.endpoint} ${diagnostics.version}`)
+            }
+          />
+
+          <LoadingButton
+            variant="outlined"
+            loading={loading.diagnose}
+            onClick={handleDiagnose}
+          >
+            {t("Diagnose")}
+          </LoadingButton>
+        </ListItem>
+
+        <ListItem sx={{ pl: 0, pr: 0 }}>
+          <ListItemText
+            primary={t("Controller Latency")}
+            secondary={
+              latency &&
+              t("Controller Latency Result", {
+                min: latency.min.toFixed(1),
+                avg: latency.avg.toFixed(1),
+                max: latency.max.toFixed(1),
+                failures: latency.failures,
+              })
+            }
+          />
+
+          <LoadingButton
+            variant="outlined"
+            loading={loading.latency}
+            onClick={handleLatency}
+          >
+            {t("Measure")}
+          </LoadingButton>
+        </ListItem>
+
+        <ListItem sx={{ pl: 0, pr: 0 }}>
+          <ListItemText primary={t("DNS Cache")} />
+
+          <LoadingButton
+            variant="outlined"
+            loading={loading.dns}
+            onClick={handleFlushDns}
+          >
+            {t("Flush")}
+          </LoadingButton>
+        </ListItem>
+      </List>
+    </BaseCard>
+  );
+};
+
+export default SettingClashDiagnostics;
@@ -1,5 +1,11 @@
 import { List } from "@mui/material";
-import { BaseCard, MenuItem, SwitchItem, TextItem } from "@nyanpasu/ui";
+import {
+  BaseCard,
+  MenuItem,
+  NumberItem,
+  SwitchItem,
+  TextItem,
+} from "@nyanpasu/ui";
 import { useTranslation } from "react-i18next";
 import { nyanpasu } from "./modules/create-props";
 import { useNyanpasu } from "@nyanpasu/interface";
@@ -47,6 +53,40 @@ export const SettingNyanpasuMisc = () => {
           {...createBooleanProps("clash_tray_selector")}
         />
 
+        <TextItem
+          label={t("Tray Hidden Groups")}
+          placeholder="GLOBAL, Auto"
+          value={nyanpasuConfig?.tray_hidden_groups?.join(", ") || ""}
+          onApply={(value) =>
+            setNyanpasuConfig({
+              tray_hidden_groups: value
+                .split(",")
+                .map((group) => group.trim())
+                .filter(Boolean),
+            })
+          }
+        />
+
+        <SwitchItem
+          label={t("Keep Running After Window Closed")}
+          {...createBooleanProps("keep_core_on_window_close")}
+        />
+
+        <SwitchItem
+          label={t("Persist Core Logs")}
+          {...createBooleanProps("persist_core_logs")}
+        />
+
+        <NumberItem
+          label={t("Idle Auto Stop Minutes")}
+          vaule={nyanpasuConfig?.idle_auto_stop_minutes || 0}
+          checkEvent={(value) => value < 0}
+          checkLabel="Value must not be negative."
+          onApply={(value) =>
+            setNyanpasuConfig({ idle_auto_stop_minutes: value })
+          }
+        />
+
         <SwitchItem
           label={t("Lighten up Animation Effects")}
           {...createBooleanProps("lighten_animation_effects")}
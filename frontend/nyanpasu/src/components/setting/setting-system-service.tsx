@@ -6,9 +6,15 @@ import {
   ListItemText,
   Typography,
 } from "@mui/material";
-import { useNyanpasu } from "@nyanpasu/interface";
+import {
+  checkServiceMode,
+  migrateToService,
+  runTypeDiagnostics,
+  useNyanpasu,
+} from "@nyanpasu/interface";
 import { BaseCard, SwitchItem } from "@nyanpasu/ui";
 import { useTranslation } from "react-i18next";
+import useSWR from "swr";
 import { nyanpasu } from "./modules/create-props";
 
 const { createBooleanProps } = nyanpasu;
@@ -16,7 +22,39 @@ const { createBooleanProps } = nyanpasu;
 export const SettingSystemService = () => {
   const { t } = useTranslation();
 
-  const { getServiceStatus, setServiceStatus } = useNyanpasu();
+  const { nyanpasuConfig, getServiceStatus, setServiceStatus } =
+    useNyanpasu();
+
+  const diagnostics = useSWR(
+    ["runTypeDiagnostics", nyanpasuConfig?.enable_service_mode],
+    runTypeDiagnostics,
+  );
+
+  const serviceCheck = useSWR(
+    ["checkServiceMode", getServiceStatus.data],
+    checkServiceMode,
+  );
+
+  const serviceNotUsed =
+    diagnostics.data?.enable_service_mode &&
+    diagnostics.data.chosen !== "service";
+
+  const canMigrate =
+    diagnostics.data?.chosen === "normal" &&
+    serviceCheck.data &&
+    !serviceCheck.data.failure;
+
+  const handleMigrate = async () => {
+    try {
+      await migrateToService();
+      await diagnostics.mutate();
+    } catch (e) {
+      useMessage(String(e), {
+        type: "error",
+        title: t("Error"),
+      });
+    }
+  };
 
   const getButtonString = () => {
     switch (getServiceStatus.data) {
@@ -83,6 +121,43 @@ export const SettingSystemService = () => {
           </ListItem>
         )}
 
+        {serviceNotUsed && (
+          <ListItem sx={{ pl: 0, pr: 0 }}>
+            <Typography>
+              {diagnostics.data?.ipc_connected
+                ? t("Service Mode Not Used")
+                : t("Service Mode Not Connected", {
+                    reason: diagnostics.data?.ipc_error,
+                  })}
+            </Typography>
+          </ListItem>
+        )}
+
+        {serviceCheck.data && (
+          <ListItem sx={{ pl: 0, pr: 0 }}>
+            <ListItemText
+              primary={t("Service Mode Status")}
+              secondary={
+                serviceCheck.data.failure
+                  ? t(`Service Mode Failure ${serviceCheck.data.failure}`, {
+                      reason: serviceCheck.data.error,
+                    })
+                  : t("Service Mode Available")
+              }
+            />
+          </ListItem>
+        )}
+
+        {canMigrate && (
+          <ListItem sx={{ pl: 0, pr: 0 }}>
+            <ListItemText primary={t("Migrate To Service Hint")} />
+
+            <Button variant="contained" onClick={handleMigrate}>
+              {t("Migrate To Service")}
+            </Button>
+          </ListItem>
+        )}
+
         <ListItem sx={{ pl: 0, pr: 0 }}>
           <ListItemText primary={`Current State: ${getServiceStatus.data}`} />
 
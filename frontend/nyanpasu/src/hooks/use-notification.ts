@@ -31,7 +31,7 @@ export type NotificationOptions = {
 export enum NotificationType {
   Success = "success",
   Info = "info",
-  // Warn = "warn",
+  Warn = "warn",
   Error = "error",
 }
 
@@ -22,6 +22,7 @@ import { SidePage } from "@nyanpasu/ui";
 import { useAtom } from "jotai";
 import { useEffect, useRef, useState } from "react";
 import { useTranslation } from "react-i18next";
+import { useLocation, useNavigate } from "react-router-dom";
 
 export default function ProxyPage() {
   const { t } = useTranslation();
@@ -32,7 +33,22 @@ export default function ProxyPage() {
 
   const { data, updateGroupDelay } = useClashCore();
 
-  const [proxyGroup] = useAtom(proxyGroupAtom);
+  const [proxyGroup, setProxyGroup] = useAtom(proxyGroupAtom);
+
+  const location = useLocation();
+
+  const navigate = useNavigate();
+
+  // opened from the tray with a specific group
+  useEffect(() => {
+    const name = (location.state as { group?: string } | null)?.group;
+    const index = data?.groups.findIndex((item) => item.name === name) ?? -1;
+    if (index !== -1) {
+      setProxyGroup({ selector: index });
+      // only jump once, let the user switch groups afterwards
+      navigate(location.pathname, { replace: true, state: null });
+    }
+  }, [location.state, data?.groups]);
 
   const [group, setGroup] =
     useState<Clash.Proxy<Clash.Proxy<string> | string>>();
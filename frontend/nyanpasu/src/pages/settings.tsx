@@ -17,6 +17,7 @@ const asyncComponents = [
   () => import("@/components/setting/setting-clash-web"),
   () => import("@/components/setting/setting-clash-field"),
   () => import("@/components/setting/setting-clash-core"),
+  () => import("@/components/setting/setting-clash-diagnostics"),
   () => import("@/components/setting/setting-system-behavior"),
   () => import("@/components/setting/setting-system-service"),
   () => import("@/components/setting/setting-nyanpasu-tasks"),
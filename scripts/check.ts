@@ -4,6 +4,7 @@ import { colorize, consola } from "./utils/logger";
 import { archCheck } from "./utils/arch-check";
 import { Resolve } from "./utils/resolve";
 import { printNyanpasu } from "./utils";
+import { generateCoreChecksums } from "./utils/checksums";
 import { generateLatestVersion } from "generate-latest-version";
 
 // force download
@@ -105,7 +106,10 @@ const jobs = new Array(Math.ceil(os.cpus.length / 2) || 2)
   .fill(0)
   .map(() => runTask());
 
-Promise.all(jobs).then(() => {
+Promise.all(jobs).then(async () => {
+  // generate the core checksums after all cores are downloaded
+  await generateCoreChecksums(SIDECAR_HOST!, platform);
+
   printNyanpasu();
 
   consola.success("all resources download finished\n");
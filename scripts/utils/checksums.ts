@@ -0,0 +1,58 @@
+import crypto from "node:crypto";
+import path from "path";
+import fs from "fs-extra";
+import { TAURI_APP_DIR } from "./env";
+import { colorize, consola } from "./logger";
+import versionManifest from "../../manifest/version.json";
+
+const CORE_CHECKSUMS_FILE = "core-checksums.json";
+
+/**
+ * the bundled cores and the versions they are downloaded with
+ */
+const BUNDLED_CORES = [
+  { name: "clash", version: versionManifest.latest.clash_premium },
+  { name: "mihomo", version: versionManifest.latest.mihomo },
+  { name: "mihomo-alpha", version: versionManifest.latest.mihomo_alpha },
+  { name: "clash-rs", version: versionManifest.latest.clash_rs },
+];
+
+/**
+ * write the sha256 of the bundled cores to the resources dir
+ * format: { "mihomo": { "v1.18.0": "<sha256>" } }
+ */
+export const generateCoreChecksums = async (
+  sidecarHost: string,
+  platform: string,
+) => {
+  const isWin = platform === "win32";
+
+  const checksums: Record<string, Record<string, string>> = {};
+
+  for (const { name, version } of BUNDLED_CORES) {
+    const sidecarPath = path.join(
+      TAURI_APP_DIR,
+      "sidecar",
+      `${name}-${sidecarHost}${isWin ? ".exe" : ""}`,
+    );
+
+    if (!(await fs.pathExists(sidecarPath))) continue;
+
+    const hash = crypto
+      .createHash("sha256")
+      .update(await fs.readFile(sidecarPath))
+      .digest("hex");
+
+    checksums[name] = { [version]: hash };
+  }
+
+  const resDir = path.join(TAURI_APP_DIR, "resources");
+
+  await fs.mkdirp(resDir);
+
+  await fs.writeJSON(path.join(resDir, CORE_CHECKSUMS_FILE), checksums, {
+    spaces: 2,
+  });
+
+  consola.success(colorize`generate {green ${CORE_CHECKSUMS_FILE}} finished`);
+};